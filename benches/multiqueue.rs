@@ -0,0 +1,131 @@
+// (c) Copyright 2021 Christian Saide
+// SPDX-License-Identifier: MIT
+
+//! Benchmarks measuring the per-packet overhead of the any-queue `send`/`recv` calls of the async
+//! devices as the number of queues grows, which is expected to remain flat.
+//!
+//! Each receive iteration drains a burst of packets injected ahead of time by sockets bound to
+//! distinct ports, so the flows are spread over every queue, while keeping the cost of injecting
+//! the packets out of the measurement.
+//!
+//! Creating devices requires `CAP_NET_ADMIN`, so these need to be run as root:
+//!
+//! ```bash
+//! sudo -E cargo bench --bench multiqueue
+//! ```
+
+use riptun::{AsyncStdTun, DeviceConfig, TokioTun};
+
+use std::net::UdpSocket;
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use tokio::runtime::Builder;
+
+const QUEUE_COUNTS: [usize; 6] = [1, 2, 4, 8, 16, 32];
+/// The number of packets, and distinct flows, injected per receive iteration.
+const BURST: usize = 64;
+const LOCAL: &str = "10.213.0.1";
+const PEER: &str = "10.213.0.2:9";
+
+/// A minimal IPv4/UDP packet from the peer to the local address. The header checksum is left
+/// zeroed, so the kernel discards it immediately after it is injected.
+const PACKET: [u8; 28] = [
+    0x45, 0x00, 0x00, 0x1c, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0x00, 0x00, 10, 213, 0, 2, 10, 213,
+    0, 1, 0x00, 0x09, 0x00, 0x09, 0x00, 0x08, 0x00, 0x00,
+];
+
+fn config(num_queues: usize) -> DeviceConfig {
+    let mut config = DeviceConfig::new("ripbench%d");
+    config.queues = num_queues;
+    config.addresses = vec![format!("{}/24", LOCAL).parse().unwrap()];
+    // Keep IPv6 autoconfiguration from injecting unrelated packets into the queues.
+    config.sysctls.disable_ipv6 = Some(true);
+    config.up = true;
+    config
+}
+
+/// Bind a socket per flow, whose packets to the peer are routed out of the device.
+fn sockets() -> Vec<UdpSocket> {
+    (0..BURST)
+        .map(|_| UdpSocket::bind((LOCAL, 0)).expect("Failed to bind socket."))
+        .collect()
+}
+
+/// Inject a packet per flow, each of which the device hashes to one of its queues.
+fn inject(sockets: &[UdpSocket]) {
+    for socket in sockets {
+        socket.send_to(&PACKET[20..], PEER).unwrap();
+    }
+}
+
+fn tokio(c: &mut Criterion) {
+    let rt = Builder::new_current_thread()
+        .enable_io()
+        .build()
+        .expect("Failed to create runtime.");
+    let _guard = rt.enter();
+
+    let mut group = c.benchmark_group("tokio");
+    group.throughput(Throughput::Elements(1));
+    group.measurement_time(Duration::from_secs(3));
+    for num_queues in QUEUE_COUNTS {
+        let tun: TokioTun = config(num_queues)
+            .build_tokio()
+            .expect("Failed to create device.");
+        let sockets = sockets();
+        let mut buffer = [0u8; 1500];
+
+        group.throughput(Throughput::Elements(BURST as u64));
+        group.bench_with_input(BenchmarkId::new("recv", num_queues), &tun, |b, tun| {
+            b.iter_batched(
+                || inject(&sockets),
+                |_| {
+                    for _ in 0..BURST {
+                        rt.block_on(tun.recv(&mut buffer)).unwrap();
+                    }
+                },
+                BatchSize::PerIteration,
+            )
+        });
+        group.throughput(Throughput::Elements(1));
+        group.bench_with_input(BenchmarkId::new("send", num_queues), &tun, |b, tun| {
+            b.iter(|| rt.block_on(tun.send(&PACKET)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn async_std(c: &mut Criterion) {
+    let mut group = c.benchmark_group("async_std");
+    group.throughput(Throughput::Elements(1));
+    group.measurement_time(Duration::from_secs(3));
+    for num_queues in QUEUE_COUNTS {
+        let tun: AsyncStdTun = config(num_queues)
+            .build_async_std()
+            .expect("Failed to create device.");
+        let sockets = sockets();
+        let mut buffer = [0u8; 1500];
+
+        group.throughput(Throughput::Elements(BURST as u64));
+        group.bench_with_input(BenchmarkId::new("recv", num_queues), &tun, |b, tun| {
+            b.iter_batched(
+                || inject(&sockets),
+                |_| {
+                    for _ in 0..BURST {
+                        async_io::block_on(tun.recv(&mut buffer)).unwrap();
+                    }
+                },
+                BatchSize::PerIteration,
+            )
+        });
+        group.throughput(Throughput::Elements(1));
+        group.bench_with_input(BenchmarkId::new("send", num_queues), &tun, |b, tun| {
+            b.iter(|| async_io::block_on(tun.send(&PACKET)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, tokio, async_std);
+criterion_main!(benches);
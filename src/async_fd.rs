@@ -0,0 +1,18 @@
+// (c) Copyright 2021 Christian Saide
+// SPDX-License-Identifier: MIT
+
+use std::io;
+use std::os::unix::io::AsRawFd;
+
+use tokio::io::unix::AsyncFd;
+
+/// Register the supplied descriptor owner with the current tokio reactor.
+///
+/// Newer tokio releases deprecate [`AsyncFd::new()`] in favour of the unsafe `AsyncFd::register`,
+/// as nothing stops an arbitrary [AsRawFd] implementation from closing its descriptor while it is
+/// still registered. Every caller passes a type owning its descriptor until dropped, which upholds
+/// that requirement, so the deprecation is silenced here rather than raising the tokio floor.
+pub(crate) fn new<T: AsRawFd>(inner: T) -> io::Result<AsyncFd<T>> {
+    #[allow(deprecated)]
+    AsyncFd::new(inner)
+}
@@ -0,0 +1,372 @@
+// (c) Copyright 2021 Christian Saide
+// SPDX-License-Identifier: MIT
+
+use super::{
+    AcceptRa, AddrGenMode, Error, IpNet, Link, Mode, Result, Route, RpFilter, Rule, SendPolicy,
+    Sysctl, Tun, TunBuilder,
+};
+
+use super::queue::FromQueue;
+use super::tun::Selector;
+
+use nix::errno::Errno;
+
+#[cfg(feature = "async-std-impl")]
+use super::AsyncStdTun;
+#[cfg(feature = "tokio-impl")]
+use super::TokioTun;
+
+/// A declarative description of a device and its link level configuration, which can be applied
+/// in a single transactional step via [`DeviceConfig::build()`].
+///
+/// When the `serde` feature is enabled this can be deserialized from any serde supported format,
+/// for instance as JSON:
+///
+/// ```json
+/// {
+///     "name": "rip%d",
+///     "mode": "tap",
+///     "queues": 2,
+///     "mtu": 1400,
+///     "addresses": ["10.0.0.1/24", "fd00::1/64"],
+///     "routes": [{ "destination": "10.1.0.0/16", "gateway": "10.0.0.254" }],
+///     "rules": [{ "from": "10.0.0.0/24", "table": 100 }],
+///     "sysctls": { "rp_filter": "loose" },
+///     "up": true,
+///     "cleanup_on_drop": true
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceConfig {
+    /// The name of the device, which can be augmented with `%d` to denote a OS determined
+    /// incrementing ID.
+    pub name: String,
+    /// The mode the device operates in.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub mode: Mode,
+    /// The number of queues to open against the device.
+    #[cfg_attr(feature = "serde", serde(default = "default_queues"))]
+    pub queues: usize,
+    /// Whether to enable the packet information header, see [`TunBuilder::packet_info()`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub packet_info: bool,
+    /// Whether to enable the virtio net header, see [`TunBuilder::vnet_hdr()`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub vnet_hdr: bool,
+    /// The policy used to select the queue packets are sent via, see
+    /// [`TunBuilder::send_policy()`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub send_policy: SendPolicy,
+    /// The MTU of the device, or `None` to retain the kernel default.
+    pub mtu: Option<u32>,
+    /// The addresses to assign to the device.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub addresses: Vec<IpNet>,
+    /// The routes to add out of the device, which requires the device to be up.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub routes: Vec<Route>,
+    /// The policy routing rules to add.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub rules: Vec<Rule>,
+    /// The per-interface sysctls to set.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub sysctls: SysctlConfig,
+    /// Whether to administratively bring the device up.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub up: bool,
+    /// Whether to undo the applied configuration when the device is dropped without being
+    /// closed, see [`Tun::set_cleanup_on_drop()`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub cleanup_on_drop: bool,
+}
+
+/// The per-interface sysctls to set as part of a [DeviceConfig], see [Sysctl] for details on
+/// each. Any sysctl left as `None` retains its current value.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SysctlConfig {
+    /// See [`Sysctl::set_disable_ipv6()`].
+    pub disable_ipv6: Option<bool>,
+    /// See [`Sysctl::set_addr_gen_mode()`].
+    pub addr_gen_mode: Option<AddrGenMode>,
+    /// See [`Sysctl::set_accept_ra()`].
+    pub accept_ra: Option<AcceptRa>,
+    /// See [`Sysctl::set_rp_filter()`].
+    pub rp_filter: Option<RpFilter>,
+    /// See [`Sysctl::set_ipv4_forwarding()`].
+    pub ipv4_forwarding: Option<bool>,
+    /// See [`Sysctl::set_ipv6_forwarding()`].
+    pub ipv6_forwarding: Option<bool>,
+}
+
+impl SysctlConfig {
+    fn apply(&self, sysctl: &mut Sysctl) -> Result<()> {
+        if let Some(on) = self.disable_ipv6 {
+            sysctl.set_disable_ipv6(on)?;
+        }
+        if let Some(mode) = self.addr_gen_mode {
+            sysctl.set_addr_gen_mode(mode)?;
+        }
+        if let Some(accept_ra) = self.accept_ra {
+            sysctl.set_accept_ra(accept_ra)?;
+        }
+        if let Some(mode) = self.rp_filter {
+            sysctl.set_rp_filter(mode)?;
+        }
+        if let Some(on) = self.ipv4_forwarding {
+            sysctl.set_ipv4_forwarding(on)?;
+        }
+        if let Some(on) = self.ipv6_forwarding {
+            sysctl.set_ipv6_forwarding(on)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serde")]
+fn default_queues() -> usize {
+    1
+}
+
+impl DeviceConfig {
+    /// Create a new configuration for a single queue [`Mode::Tun`] device using the specified
+    /// name, with no link level configuration.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: String::from(name),
+            mode: Mode::Tun,
+            queues: 1,
+            packet_info: false,
+            vnet_hdr: false,
+            send_policy: SendPolicy::RoundRobin,
+            mtu: None,
+            addresses: Vec::new(),
+            routes: Vec::new(),
+            rules: Vec::new(),
+            sysctls: SysctlConfig::default(),
+            up: false,
+            cleanup_on_drop: false,
+        }
+    }
+
+    /// Create a blocking [Tun] device and apply this configuration to it.
+    ///
+    /// The configuration is applied in the order sysctls, MTU, addresses, up-state, routes, and
+    /// rules. If any step fails all previously applied steps are rolled back, the device is
+    /// closed, and the error is returned. Otherwise the applied configuration is torn down
+    /// again on [`Tun::close()`], or when the device is dropped if `cleanup_on_drop` is set.
+    pub fn build(&self) -> Result<Tun> {
+        let (queues, link, selector, teardown) = self.open()?;
+        let mut tun = Tun::from_parts(queues, link, selector);
+        tun.set_teardown(teardown);
+        tun.set_cleanup_on_drop(self.cleanup_on_drop);
+        Ok(tun)
+    }
+
+    /// Create an async [TokioTun] device and apply this configuration to it, see
+    /// [`DeviceConfig::build()`] for more details. This must be called from within the context
+    /// of a `tokio` runtime.
+    #[cfg(feature = "tokio-impl")]
+    pub fn build_tokio(&self) -> Result<TokioTun> {
+        let (queues, link, selector, teardown) = self.open()?;
+        let mut tun = TokioTun::from_parts(queues, link, selector);
+        tun.set_teardown(teardown);
+        tun.set_cleanup_on_drop(self.cleanup_on_drop);
+        Ok(tun)
+    }
+
+    /// Create an async [AsyncStdTun] device and apply this configuration to it, see
+    /// [`DeviceConfig::build()`] for more details.
+    #[cfg(feature = "async-std-impl")]
+    pub fn build_async_std(&self) -> Result<AsyncStdTun> {
+        let (queues, link, selector, teardown) = self.open()?;
+        let mut tun = AsyncStdTun::from_parts(queues, link, selector);
+        tun.set_teardown(teardown);
+        tun.set_cleanup_on_drop(self.cleanup_on_drop);
+        Ok(tun)
+    }
+
+    /// Open the queues of the device and apply this configuration to its link. If any step
+    /// fails the queues are dropped, closing the device.
+    fn open<T: FromQueue>(&self) -> Result<(Vec<T>, Link, Selector, Teardown)> {
+        let (queues, link, selector) = self.builder().open()?;
+        let teardown = self.apply(&link)?;
+        Ok((queues, link, selector, teardown))
+    }
+
+    fn builder(&self) -> TunBuilder {
+        TunBuilder::new(&self.name)
+            .mode(self.mode)
+            .queues(self.queues)
+            .packet_info(self.packet_info)
+            .vnet_hdr(self.vnet_hdr)
+            .send_policy(self.send_policy)
+    }
+
+    /// Apply the link level configuration to the supplied link, returning the log required to
+    /// undo it. On failure every step applied so far is rolled back.
+    fn apply(&self, link: &Link) -> Result<Teardown> {
+        let mut teardown = Teardown::default();
+        match self.apply_steps(link, &mut teardown) {
+            Ok(()) => Ok(teardown),
+            Err(err) => {
+                let _ = teardown.run(link);
+                Err(err)
+            }
+        }
+    }
+
+    fn apply_steps(&self, link: &Link, teardown: &mut Teardown) -> Result<()> {
+        // The handle records partially applied sysctls itself, so log it prior to checking.
+        let mut sysctl = link.sysctl();
+        let result = self.sysctls.apply(&mut sysctl);
+        teardown.push(Step::Sysctl(sysctl));
+        result?;
+
+        if let Some(mtu) = self.mtu {
+            let original = link.mtu()?;
+            link.set_mtu(mtu)?;
+            teardown.push(Step::Mtu(original));
+        }
+        for net in self.addresses.iter() {
+            link.add_address(*net)?;
+            teardown.push(Step::Address(*net));
+        }
+        if self.up && !link.is_up()? {
+            link.set_up(true)?;
+            teardown.push(Step::Up);
+        }
+        for route in self.routes.iter() {
+            link.add_route(route)?;
+            teardown.push(Step::Route(*route));
+        }
+        for rule in self.rules.iter() {
+            link.add_rule(rule)?;
+            teardown.push(Step::Rule(*rule));
+        }
+        Ok(())
+    }
+}
+
+/// A single configuration step applied to a link, holding the state required to undo it.
+#[derive(Debug)]
+enum Step {
+    Sysctl(Sysctl),
+    Mtu(u32),
+    Address(IpNet),
+    Up,
+    Route(Route),
+    Rule(Rule),
+}
+
+/// A log of configuration applied to a link by riptun, which is undone in reverse order.
+#[derive(Debug, Default)]
+pub(crate) struct Teardown {
+    steps: Vec<Step>,
+}
+
+impl Teardown {
+    fn push(&mut self, step: Step) {
+        self.steps.push(step);
+    }
+
+    /// Undo every logged step in reverse order. Undoing continues past failures, and the first
+    /// error encountered is returned.
+    pub(crate) fn run(&mut self, link: &Link) -> Result<()> {
+        let mut result = Ok(());
+        while let Some(step) = self.steps.pop() {
+            let undone = match step {
+                Step::Sysctl(mut sysctl) => sysctl.restore(),
+                Step::Mtu(mtu) => link.set_mtu(mtu),
+                Step::Address(net) => link.delete_address(net),
+                Step::Up => link.set_up(false),
+                Step::Route(route) => link.delete_route(&route),
+                Step::Rule(rule) => link.delete_rule(&rule),
+            };
+            if result.is_ok() && !already_undone(&undone) {
+                result = undone;
+            }
+        }
+        result
+    }
+
+    /// Discard every logged step, leaving the applied configuration in place.
+    pub(crate) fn forget(&mut self) {
+        for step in self.steps.iter_mut() {
+            if let Step::Sysctl(sysctl) = step {
+                sysctl.persist();
+            }
+        }
+        self.steps.clear();
+    }
+}
+
+/// Whether the supplied result indicates the configuration was already removed, for instance
+/// the kernel flushes IPv6 addresses when a link is brought down, or the link itself is gone.
+fn already_undone(result: &Result<()>) -> bool {
+    matches!(
+        result.as_ref().map_err(Error::raw_errno),
+        Err(Some(
+            Errno::ENOENT | Errno::ESRCH | Errno::EADDRNOTAVAIL | Errno::ENODEV
+        ))
+    )
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize() {
+        let config: DeviceConfig = serde_json::from_str(
+            r#"{
+                "name": "rip%d",
+                "mode": "tap",
+                "vnet_hdr": true,
+                "send_policy": "flow_hash",
+                "mtu": 1400,
+                "addresses": ["10.0.0.1/24", "fd00::1/64"],
+                "routes": [{ "destination": "10.1.0.0/16", "gateway": "10.0.0.254" }],
+                "rules": [{ "from": "10.0.0.0/24", "table": 100 }],
+                "sysctls": { "rp_filter": "loose", "addr_gen_mode": "stable_privacy" },
+                "up": true,
+                "cleanup_on_drop": true
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(Mode::Tap, config.mode);
+        assert_eq!(1, config.queues);
+        assert!(config.vnet_hdr && !config.packet_info);
+        assert_eq!(SendPolicy::FlowHash, config.send_policy);
+        assert_eq!(Some(1400), config.mtu);
+        assert_eq!("fd00::1/64".parse::<IpNet>().unwrap(), config.addresses[1]);
+        assert_eq!(
+            Route {
+                gateway: Some("10.0.0.254".parse().unwrap()),
+                ..Route::new("10.1.0.0/16".parse().unwrap())
+            },
+            config.routes[0]
+        );
+        assert_eq!(
+            Rule {
+                from: Some("10.0.0.0/24".parse().unwrap()),
+                ..Rule::new(100)
+            },
+            config.rules[0]
+        );
+        assert_eq!(Some(RpFilter::Loose), config.sysctls.rp_filter);
+        assert_eq!(
+            Some(AddrGenMode::StablePrivacy),
+            config.sysctls.addr_gen_mode
+        );
+        assert!(config.up);
+        assert!(config.cleanup_on_drop);
+
+        assert!(serde_json::from_str::<DeviceConfig>(
+            r#"{ "name": "rip%d", "addresses": ["10.0.0.1/33"] }"#
+        )
+        .is_err());
+    }
+}
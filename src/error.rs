@@ -5,6 +5,7 @@
 use std::{io, result};
 
 // extern usings
+use nix::errno::Errno;
 use thiserror::Error;
 
 /// Custom Result wrapper to simplify usage.
@@ -14,12 +15,48 @@ pub type Result<T> = result::Result<T, Error>;
 /// Represents store errors based on user configuration or
 /// general operations.
 pub enum Error {
-    /// Internal raw unix error.
+    /// Internal raw unix error, without any additional context.
     #[error("fatal unix error encountered: {source}")]
     Unix {
         /// The internal unix error encountered.
-        source: nix::errno::Errno,
+        source: Errno,
     },
+    /// A unix error encountered while performing an operation against a device.
+    #[error("{op} failed for device '{device}': {source}")]
+    Os {
+        /// The operation that failed, such as `TUNSETIFF` or `RTM_NEWADDR`.
+        op: &'static str,
+        /// The name of the device the operation was performed against.
+        device: String,
+        /// The internal unix error encountered.
+        source: Errno,
+    },
+    /// The caller lacks the privileges required to perform an operation, which generally means
+    /// the `CAP_NET_ADMIN` capability is missing.
+    #[error("{op} failed for device '{device}': permission denied, CAP_NET_ADMIN is required")]
+    PermissionDenied {
+        /// The operation that failed.
+        op: &'static str,
+        /// The name of the device the operation was performed against.
+        device: String,
+    },
+    /// The device is busy, for instance a single queue device which already has a queue attached.
+    #[error("device '{device}' is busy")]
+    DeviceBusy {
+        /// The name of the busy device.
+        device: String,
+    },
+    /// A device with the requested name already exists, but isn't a TUN/TAP device with the
+    /// requested mode and flags.
+    #[error("device '{device}' already exists with an incompatible type or flags")]
+    DeviceMismatch {
+        /// The name of the existing device.
+        device: String,
+    },
+    /// The TUN/TAP driver is unavailable, generally because the `tun` kernel module isn't loaded
+    /// or `/dev/net/tun` doesn't exist.
+    #[error("the TUN/TAP driver is unavailable, ensure the 'tun' kernel module is loaded")]
+    NoTunModule,
     /// Invalid ioctl return code encountered.
     #[error("ioctl failed with unexpected return code: got '{0}'")]
     UnixIoctl(i32),
@@ -41,6 +78,9 @@ pub enum Error {
     /// The specified queue descriptor is invalid.
     #[error("invalid queue descriptor specified '{0}' is out of range")]
     InvalidQueue(usize),
+    /// The queue has already been closed.
+    #[error("queue is closed")]
+    QueueClosed,
     /// The specified number of queues was less than or equal to 0.
     #[error("invalid number of queues specified must be greater than 0")]
     InvalidNumQueues,
@@ -54,24 +94,115 @@ pub enum Error {
         /// The max size of the name.
         max_size: usize,
     },
+    /// The specified hardware address is invalid.
+    #[error("invalid hardware address '{0}' expected six colon separated hex octets")]
+    InvalidMacAddr(String),
+    /// The specified network prefix is invalid.
+    #[error("invalid network prefix '{0}' expected an IP address with an optional prefix length")]
+    InvalidIpNet(String),
+    /// The specified CPU mask is invalid.
+    #[error("invalid CPU mask '{0}' expected comma separated hex words")]
+    InvalidCpuSet(String),
 }
 
 impl Error {
     /// Returns the last errno observed on unix systems.
     pub fn errno() -> Self {
-        Self::from(nix::errno::Errno::last())
+        Self::from(Errno::last())
     }
 
-    /// Consume this error and return the equivalent [std::io::Error].
-    pub fn into_io(self) -> std::io::Error {
-        use std::io::ErrorKind;
+    /// Annotate this error with the operation and device it occurred on, classifying well known
+    /// errno values into their dedicated variants. Errors other than [Error::Unix] are returned
+    /// unchanged.
+    pub(crate) fn context(self, op: &'static str, device: &str) -> Self {
+        let source = match self {
+            Self::Unix { source } => source,
+            err => return err,
+        };
+
+        let device = String::from(device);
+        match source {
+            Errno::EPERM | Errno::EACCES => Self::PermissionDenied { op, device },
+            Errno::EBUSY => Self::DeviceBusy { device },
+            source => Self::Os { op, device, source },
+        }
+    }
+
+    /// Return the underlying unix error, if any, that caused this error.
+    pub fn raw_errno(&self) -> Option<Errno> {
         match self {
-            Self::FS { source, .. } => source,
-            Self::IO { source } => source,
-            Self::Unix { source } => std::io::Error::from_raw_os_error(source as i32),
-            err => std::io::Error::new(ErrorKind::Other, err),
+            Self::Unix { source } | Self::Os { source, .. } => Some(*source),
+            Self::PermissionDenied { .. } => Some(Errno::EPERM),
+            Self::DeviceBusy { .. } => Some(Errno::EBUSY),
+            Self::FS { source, .. } | Self::IO { source } => {
+                source.raw_os_error().map(Errno::from_i32)
+            }
+            _ => None,
         }
     }
+
+    /// Return whether this error is transient, in which case retrying the failed operation
+    /// later may succeed without any other intervention.
+    pub fn is_transient(&self) -> bool {
+        if let Self::FS { source, .. } | Self::IO { source } = self {
+            if matches!(
+                source.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted | io::ErrorKind::TimedOut
+            ) {
+                return true;
+            }
+        }
+        matches!(
+            self.raw_errno(),
+            Some(Errno::EAGAIN | Errno::EINTR | Errno::EBUSY | Errno::ENOBUFS | Errno::ENOMEM)
+        )
+    }
+
+    /// Return whether this error is fatal, in which case retrying the failed operation can't
+    /// succeed without changes to the supplied configuration, or to the host itself. Errors may
+    /// be neither transient nor fatal, for instance when the device was removed from under us.
+    pub fn is_fatal(&self) -> bool {
+        if matches!(self.raw_errno(), Some(Errno::EPERM | Errno::EACCES)) {
+            return true;
+        }
+        matches!(
+            self,
+            Self::PermissionDenied { .. }
+                | Self::DeviceMismatch { .. }
+                | Self::NoTunModule
+                | Self::InvalidQueue(_)
+                | Self::QueueClosed
+                | Self::InvalidNumQueues
+                | Self::InvalidName { .. }
+                | Self::InvalidMacAddr(_)
+                | Self::InvalidIpNet(_)
+                | Self::InvalidCpuSet(_)
+        )
+    }
+
+    /// Consume this error and return the equivalent [std::io::Error]. Errors originating from
+    /// the OS retain the [kind][std::io::ErrorKind] of their errno, and errors carrying additional
+    /// context retain it as the inner error.
+    pub fn into_io(self) -> std::io::Error {
+        let kind = match self {
+            Self::FS { source, .. } | Self::IO { source } => return source,
+            Self::Unix { source } => return std::io::Error::from_raw_os_error(source as i32),
+            Self::Os { source, .. } => std::io::Error::from_raw_os_error(source as i32).kind(),
+            Self::PermissionDenied { .. } => io::ErrorKind::PermissionDenied,
+            Self::DeviceBusy { .. } => io::ErrorKind::ResourceBusy,
+            Self::DeviceMismatch { .. } => io::ErrorKind::AlreadyExists,
+            Self::NoTunModule => io::ErrorKind::NotFound,
+            Self::QueueClosed => io::ErrorKind::NotConnected,
+            Self::InvalidQueue(_)
+            | Self::InvalidNumQueues
+            | Self::InvalidName { .. }
+            | Self::InvalidMacAddr(_)
+            | Self::InvalidIpNet(_)
+            | Self::InvalidCpuSet(_) => io::ErrorKind::InvalidInput,
+            Self::UnixIoctl(_) => io::ErrorKind::Other,
+        };
+        std::io::Error::new(kind, self)
+    }
 }
 
 impl From<i32> for Error {
@@ -86,8 +217,58 @@ impl From<usize> for Error {
     }
 }
 
-impl From<nix::errno::Errno> for Error {
-    fn from(e: nix::errno::Errno) -> Self {
+impl From<Errno> for Error {
+    fn from(e: Errno) -> Self {
         Self::Unix { source: e }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context() {
+        let err = Error::from(Errno::EPERM).context("TUNSETIFF", "rip0");
+        assert!(matches!(
+            err,
+            Error::PermissionDenied {
+                op: "TUNSETIFF",
+                ..
+            }
+        ));
+        assert!(err.is_fatal() && !err.is_transient());
+        assert_eq!(io::ErrorKind::PermissionDenied, err.into_io().kind());
+
+        let err = Error::from(Errno::EBUSY).context("TUNSETIFF", "rip0");
+        assert!(matches!(err, Error::DeviceBusy { .. }));
+        assert!(err.is_transient() && !err.is_fatal());
+
+        let err = Error::from(Errno::ENODEV).context("RTM_NEWADDR", "rip0");
+        assert_eq!(
+            "RTM_NEWADDR failed for device 'rip0': ENODEV: No such device",
+            err.to_string()
+        );
+        assert_eq!(Some(Errno::ENODEV), err.raw_errno());
+        assert!(!err.is_transient() && !err.is_fatal());
+        assert_eq!(
+            io::Error::from_raw_os_error(Errno::ENODEV as i32).kind(),
+            err.into_io().kind()
+        );
+
+        let err = Error::InvalidNumQueues.context("TUNSETIFF", "rip0");
+        assert!(matches!(err, Error::InvalidNumQueues));
+        assert_eq!(io::ErrorKind::InvalidInput, err.into_io().kind());
+    }
+
+    #[test]
+    fn test_into_io() {
+        let err = Error::from(Errno::EAGAIN);
+        assert!(err.is_transient());
+        assert_eq!(Some(Errno::EAGAIN as i32), err.into_io().raw_os_error());
+
+        let err = Error::from(io::Error::from(io::ErrorKind::WouldBlock));
+        assert!(err.is_transient());
+        assert_eq!(io::ErrorKind::WouldBlock, err.into_io().kind());
+    }
+}
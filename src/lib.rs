@@ -71,6 +71,20 @@
 //! sudo ip link set dev rip0 up
 //! ```
 //!
+//! Layer 2 TAP devices can be created by supplying [`Mode::Tap`] to [`Tun::with_mode()`], a deterministic hardware
+//! address can then be assigned using [`Tun::set_mac_address()`] and the [`MacAddr::from_name()`] helper:
+//!
+//! ```no_run
+//! use riptun::{MacAddr, Mode, Tun};
+//!
+//! let tap = Tun::with_mode("tap%d", Mode::Tap, 1).unwrap();
+//! tap.set_mac_address(MacAddr::from_name(tap.name())).unwrap();
+//! ```
+//!
+//! Devices created via [`DeviceConfig::build()`] remember the configuration steps the [DeviceConfig] applied, and undo
+//! them when the device is closed, or when it is dropped if [`Tun::set_cleanup_on_drop()`] was enabled. Only those steps
+//! are undone, changes made afterwards through [`Tun::link()`] are not tracked and are left in place.
+//!
 //! # Examples
 //!
 //! There is a suite of included examples demonstrating the functionality of `riptun`. Note that the following examples
@@ -152,7 +166,11 @@
 //! using feature flags:
 //! - The `async-std-impl` feature exposes the [AsyncStdQueue]/[AsyncStdTun] structs.
 //! - The `tokio-impl` feature exposes the [TokioQueue]/[TokioTun] structs.
+//! - The `tokio-codec-impl` feature exposes the `TokioFramed` struct, framing packets via `tokio_util::codec`.
 //! - The `mio-impl` enables registration of [Queue] structs in a mio poll registry.
+//! - The opt-in `pool-impl` feature exposes the `PacketPool` struct and the `recv_pooled`/`stream` calls, backed by `bytes`.
+//! - The `io-uring-impl` feature exposes the runtime free `UringQueue` struct, driven by `io_uring`.
+//! - The `tokio-uring-impl` feature exposes the `TokioUringQueue` struct for the `tokio-uring` runtime.
 //!
 //! # Platform support
 //!
@@ -184,17 +202,46 @@
 
 use cfg_if::cfg_if;
 
+#[cfg(feature = "tokio-impl")]
+mod async_fd;
+mod config;
 mod error;
+#[cfg_attr(target_os = "linux", path = "link/linux/mod.rs")]
+mod link;
 #[cfg_attr(target_os = "linux", path = "queue/linux/mod.rs")]
 mod queue;
 mod tun;
 
+pub use config::{DeviceConfig, SysctlConfig};
 pub use error::{Error, Result};
-pub use queue::Queue;
-pub use tun::Tun;
+pub use link::{
+    AcceptRa, AddrGenMode, CpuSet, FdbEntry, FqCodel, IpNet, Link, LinkEvent, LinkStats, MacAddr,
+    Monitor, Neighbor, NeighborState, NetNs, Qdisc, QueueAffinity, Route, RpFilter, Rule, Sysctl,
+};
+pub use queue::{Mode, PacketBatch, PacketInfo, Queue, RecvMeta, VnetHdr};
+pub use tun::{SendPolicy, Tun, TunBuilder};
+
+cfg_if! {
+    if #[cfg(any(feature = "async-std-impl", feature = "tokio-impl"))] {
+        pub use queue::PacketSink;
+    }
+}
+
+cfg_if! {
+    if #[cfg(feature = "pool-impl")] {
+        pub use queue::{PacketPool, PooledPacket};
+    }
+}
+
+cfg_if! {
+    if #[cfg(all(feature = "pool-impl", any(feature = "async-std-impl", feature = "tokio-impl")))] {
+        pub use queue::PacketStream;
+    }
+}
 
 cfg_if! {
     if #[cfg(feature = "async-std-impl")] {
+        pub use link::AsyncStdMonitor;
         pub use queue::AsyncStdQueue;
         pub use tun::AsyncStdTun;
     }
@@ -202,7 +249,26 @@ cfg_if! {
 
 cfg_if! {
     if #[cfg(feature = "tokio-impl")] {
+        pub use link::TokioMonitor;
         pub use queue::TokioQueue;
         pub use tun::TokioTun;
     }
 }
+
+cfg_if! {
+    if #[cfg(feature = "tokio-codec-impl")] {
+        pub use queue::{PacketTransport, TokioFramed};
+    }
+}
+
+cfg_if! {
+    if #[cfg(feature = "io-uring-impl")] {
+        pub use queue::UringQueue;
+    }
+}
+
+cfg_if! {
+    if #[cfg(feature = "tokio-uring-impl")] {
+        pub use queue::TokioUringQueue;
+    }
+}
@@ -0,0 +1,211 @@
+// (c) Copyright 2021 Christian Saide
+// SPDX-License-Identifier: MIT
+
+use super::netlink::{self, encode_addr, parse_addr, IfAddrMsg, Message};
+use super::{Error, Link, Result};
+
+use nix::libc;
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// An IPv4 or IPv6 address paired with a prefix length, such as `10.0.0.1/24`, used to describe
+/// interface addresses as well as route and rule selectors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "String", into = "String")
+)]
+pub struct IpNet {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpNet {
+    /// Create a new prefix from the supplied address and prefix length.
+    ///
+    /// # Errors
+    /// If the prefix length exceeds the length of the address, 32 for IPv4 and 128 for IPv6.
+    pub fn new(addr: IpAddr, prefix_len: u8) -> Result<Self> {
+        if prefix_len > max_prefix_len(&addr) {
+            return Err(Error::InvalidIpNet(format!("{}/{}", addr, prefix_len)));
+        }
+        Ok(Self { addr, prefix_len })
+    }
+
+    /// Return the address portion of this prefix.
+    #[inline]
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    /// Return the prefix length of this prefix.
+    #[inline]
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+}
+
+impl From<IpAddr> for IpNet {
+    /// Create a host prefix covering only the supplied address.
+    fn from(addr: IpAddr) -> Self {
+        Self {
+            addr,
+            prefix_len: max_prefix_len(&addr),
+        }
+    }
+}
+
+impl fmt::Display for IpNet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+impl FromStr for IpNet {
+    type Err = Error;
+
+    /// Parse a prefix in CIDR notation, a bare address is treated as a host prefix.
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::InvalidIpNet(String::from(s));
+        match s.split_once('/') {
+            Some((addr, prefix_len)) => {
+                let addr = addr.parse().map_err(|_| invalid())?;
+                let prefix_len = prefix_len.parse().map_err(|_| invalid())?;
+                Self::new(addr, prefix_len).map_err(|_| invalid())
+            }
+            None => s.parse::<IpAddr>().map(Self::from).map_err(|_| invalid()),
+        }
+    }
+}
+
+impl TryFrom<String> for IpNet {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<IpNet> for String {
+    fn from(net: IpNet) -> Self {
+        net.to_string()
+    }
+}
+
+fn max_prefix_len(addr: &IpAddr) -> u8 {
+    match addr {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    }
+}
+
+/// Parse an `RTM_NEWADDR` or `RTM_DELADDR` message, returning the address if it belongs to the
+/// interface with the supplied index.
+pub(super) fn parse(resp: &netlink::Response, index: i32) -> Option<IpNet> {
+    let hdr: IfAddrMsg = netlink::from_bytes(&resp.payload)?;
+    if hdr.index as i32 != index {
+        return None;
+    }
+
+    // IPv4 point-to-point links report the peer in IFA_ADDRESS, so prefer IFA_LOCAL.
+    let mut addr = None;
+    for (ty, data) in resp.attrs::<IfAddrMsg>() {
+        match ty {
+            libc::IFA_LOCAL => addr = parse_addr(hdr.family, data),
+            libc::IFA_ADDRESS if addr.is_none() => addr = parse_addr(hdr.family, data),
+            _ => {}
+        }
+    }
+    addr.map(|addr| IpNet {
+        addr,
+        prefix_len: hdr.prefix_len,
+    })
+}
+
+fn message(ty: u16, flags: u16, index: i32, net: &IpNet) -> Message {
+    let (family, addr) = encode_addr(&net.addr);
+    let hdr = IfAddrMsg {
+        family,
+        prefix_len: net.prefix_len,
+        index: index as u32,
+        ..Default::default()
+    };
+
+    let mut msg = Message::new(ty, flags, &hdr);
+    msg.attr(libc::IFA_LOCAL, &addr);
+    msg.attr(libc::IFA_ADDRESS, &addr);
+    msg
+}
+
+pub(super) fn add(link: &Link, net: &IpNet) -> Result<()> {
+    let mut msg = message(
+        libc::RTM_NEWADDR,
+        netlink::NLM_F_CREATE | netlink::NLM_F_EXCL,
+        link.index()?,
+        net,
+    );
+    link.request(&mut msg).map(|_| ())
+}
+
+pub(super) fn delete(link: &Link, net: &IpNet) -> Result<()> {
+    let mut msg = message(libc::RTM_DELADDR, 0, link.index()?, net);
+    link.request(&mut msg).map(|_| ())
+}
+
+pub(super) fn list(link: &Link) -> Result<Vec<IpNet>> {
+    let index = link.index()?;
+    let mut msg = Message::new(
+        libc::RTM_GETADDR,
+        netlink::NLM_F_DUMP,
+        &IfAddrMsg::default(),
+    );
+    let addrs = link
+        .request(&mut msg)?
+        .iter()
+        .filter(|resp| resp.ty == libc::RTM_NEWADDR)
+        .filter_map(|resp| parse(resp, index))
+        .collect();
+    Ok(addrs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    #[test]
+    fn test_parse_display() {
+        let net: IpNet = "10.0.0.1/24".parse().unwrap();
+        assert_eq!(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), net.addr());
+        assert_eq!(24, net.prefix_len());
+        assert_eq!("10.0.0.1/24", net.to_string());
+
+        let net: IpNet = "::1".parse().unwrap();
+        assert_eq!(IpAddr::V6(Ipv6Addr::LOCALHOST), net.addr());
+        assert_eq!(128, net.prefix_len());
+
+        for invalid in ["10.0.0.1/33", "::1/129", "10.0.0/24", "10.0.0.1/", "bogus"] {
+            assert!(matches!(
+                invalid.parse::<IpNet>(),
+                Err(Error::InvalidIpNet(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_message_parse() {
+        let net: IpNet = "fd00::1/64".parse().unwrap();
+        let msg = message(libc::RTM_NEWADDR, 0, 3, &net);
+        let resp = netlink::Response {
+            ty: libc::RTM_NEWADDR,
+            payload: msg.payload().to_vec(),
+        };
+        assert_eq!(Some(net), parse(&resp, 3));
+        assert_eq!(None, parse(&resp, 4));
+    }
+}
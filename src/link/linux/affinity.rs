@@ -0,0 +1,279 @@
+// (c) Copyright 2021 Christian Saide
+// SPDX-License-Identifier: MIT
+
+use super::{Error, Link, NetNs, Result, SYSFS_ROOT};
+
+use std::fmt;
+use std::fs;
+use std::iter::FromIterator;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+const WORD_BITS: usize = 32;
+
+/// A set of CPUs, formatted as the comma separated 32-bit hex words used by the kernel for CPU
+/// masks, most significant word first, for instance `00000001,00000003` for CPUs 0, 1, and 32.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct CpuSet {
+    words: Vec<u32>,
+}
+
+impl CpuSet {
+    /// Create a new empty set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the supplied CPU to this set.
+    pub fn insert(&mut self, cpu: usize) {
+        let word = cpu / WORD_BITS;
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+        self.words[word] |= 1 << (cpu % WORD_BITS);
+    }
+
+    /// Remove the supplied CPU from this set.
+    pub fn remove(&mut self, cpu: usize) {
+        if let Some(word) = self.words.get_mut(cpu / WORD_BITS) {
+            *word &= !(1 << (cpu % WORD_BITS));
+        }
+        self.normalize();
+    }
+
+    /// Return whether the supplied CPU is a member of this set.
+    pub fn contains(&self, cpu: usize) -> bool {
+        self.words
+            .get(cpu / WORD_BITS)
+            .map(|word| word & (1 << (cpu % WORD_BITS)) != 0)
+            .unwrap_or_default()
+    }
+
+    /// Return whether this set is empty, which disables RPS or XPS for a queue.
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|word| *word == 0)
+    }
+
+    /// Drop trailing empty words, so that equal sets compare equal.
+    fn normalize(&mut self) {
+        while self.words.last() == Some(&0) {
+            self.words.pop();
+        }
+    }
+
+    /// Iterate over the members of this set in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.words.len() * WORD_BITS).filter(move |cpu| self.contains(*cpu))
+    }
+}
+
+impl FromIterator<usize> for CpuSet {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut set = Self::new();
+        iter.into_iter().for_each(|cpu| set.insert(cpu));
+        set
+    }
+}
+
+impl fmt::Display for CpuSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let used = self
+            .words
+            .iter()
+            .rposition(|word| *word != 0)
+            .map(|idx| idx + 1)
+            .unwrap_or(1);
+        let mut words = (0..used)
+            .rev()
+            .map(|idx| self.words.get(idx).copied().unwrap_or(0));
+
+        write!(f, "{:x}", words.next().unwrap_or(0))?;
+        for word in words {
+            write!(f, ",{:08x}", word)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for CpuSet {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let words = s
+            .trim()
+            .rsplit(',')
+            .map(|word| u32::from_str_radix(word, 16))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|_| Error::InvalidCpuSet(String::from(s)))?;
+        let mut set = Self { words };
+        set.normalize();
+        Ok(set)
+    }
+}
+
+/// Access to the receive packet steering (RPS) and transmit packet steering (XPS) CPU masks of
+/// the queues of a link, exposed under `/sys/class/net/<name>/queues/{rx,tx}-<index>`. For links
+/// within a network namespace the masks are accessed through a sysfs mounted from within it.
+///
+/// RPS determines which CPUs process packets written to a [Queue][crate::Queue], and XPS which
+/// transmit queue, and therefore which [Queue][crate::Queue], is used for packets sent by a CPU.
+/// Pairing both with the CPU of the thread servicing each queue keeps packets local to a CPU.
+///
+/// Queues are identified by their kernel index, which matches the order the queues of a device
+/// were opened in. Once a queue is closed the kernel moves its last queue into the vacated index,
+/// so the indexes no longer line up with the queues remaining in a riptun device.
+///
+/// ```no_run
+/// use riptun::{CpuSet, Tun};
+///
+/// let tun = Tun::new("rip%d", 2).expect("Failed to create device.");
+/// let affinity = tun.queue_affinity();
+/// for idx in 0..2 {
+///     let cpus: CpuSet = std::iter::once(idx).collect();
+///     affinity.set_cpus(idx, &cpus).expect("Failed to set queue affinity.");
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct QueueAffinity {
+    root: PathBuf,
+    name: String,
+    netns: Option<NetNs>,
+}
+
+impl QueueAffinity {
+    /// Create a new handle for the queues of the supplied [Link], within its namespace.
+    pub fn new(link: &Link) -> Self {
+        Self {
+            root: PathBuf::from(SYSFS_ROOT),
+            name: String::from(link.name()),
+            netns: link.netns().cloned(),
+        }
+    }
+
+    /// Replace the root directory the queues are resolved against, which defaults to
+    /// `/sys/class/net`.
+    pub fn with_root<P: AsRef<Path>>(mut self, root: P) -> Self {
+        self.root = root.as_ref().to_path_buf();
+        self
+    }
+
+    /// Retrieve the RPS CPU mask of the supplied queue.
+    pub fn rps(&self, queue: usize) -> Result<CpuSet> {
+        self.read(&self.path("rx", queue, "rps_cpus"))
+    }
+
+    /// Set the RPS CPU mask of the supplied queue.
+    pub fn set_rps(&self, queue: usize, cpus: &CpuSet) -> Result<()> {
+        self.write(&self.path("rx", queue, "rps_cpus"), cpus)
+    }
+
+    /// Retrieve the XPS CPU mask of the supplied queue.
+    pub fn xps(&self, queue: usize) -> Result<CpuSet> {
+        self.read(&self.path("tx", queue, "xps_cpus"))
+    }
+
+    /// Set the XPS CPU mask of the supplied queue.
+    pub fn set_xps(&self, queue: usize, cpus: &CpuSet) -> Result<()> {
+        self.write(&self.path("tx", queue, "xps_cpus"), cpus)
+    }
+
+    /// Set both the RPS and XPS CPU masks of the supplied queue.
+    pub fn set_cpus(&self, queue: usize, cpus: &CpuSet) -> Result<()> {
+        self.set_rps(queue, cpus)?;
+        self.set_xps(queue, cpus)
+    }
+
+    fn path(&self, dir: &str, queue: usize, file: &str) -> PathBuf {
+        self.root
+            .join(&self.name)
+            .join("queues")
+            .join(format!("{}-{}", dir, queue))
+            .join(file)
+    }
+
+    fn read(&self, path: &Path) -> Result<CpuSet> {
+        let content = self.run(|| {
+            fs::read_to_string(path).map_err(|source| Error::FS {
+                path: path.display().to_string(),
+                source,
+            })
+        })?;
+        content.parse()
+    }
+
+    fn write(&self, path: &Path, cpus: &CpuSet) -> Result<()> {
+        let content = cpus.to_string();
+        self.run(|| {
+            fs::write(path, &content).map_err(|source| Error::FS {
+                path: path.display().to_string(),
+                source,
+            })
+        })
+    }
+
+    fn run<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce() -> Result<T> + Send,
+        T: Send,
+    {
+        match &self.netns {
+            Some(netns) => netns.run_with_sysfs(f),
+            None => f(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cpu_set() {
+        let set: CpuSet = [0, 1, 32].iter().copied().collect();
+        assert_eq!("1,00000003", set.to_string());
+        assert_eq!(vec![0, 1, 32], set.iter().collect::<Vec<_>>());
+        assert_eq!(set, "00000001,00000003\n".parse().unwrap());
+
+        let mut set = set;
+        set.remove(32);
+        assert_eq!("3", set.to_string());
+        assert!(set.contains(1) && !set.contains(32));
+        assert_eq!("0", CpuSet::new().to_string());
+        assert!(CpuSet::new().is_empty());
+
+        assert!(matches!(
+            "xyz".parse::<CpuSet>(),
+            Err(Error::InvalidCpuSet(_))
+        ));
+    }
+
+    #[test]
+    fn test_fake_sysfs() {
+        let root = tempfile::tempdir().unwrap();
+        for (dir, file) in [("rx", "rps_cpus"), ("tx", "xps_cpus")] {
+            for queue in 0..2 {
+                let path = root
+                    .path()
+                    .join("rip0/queues")
+                    .join(format!("{}-{}", dir, queue));
+                fs::create_dir_all(&path).unwrap();
+                fs::write(path.join(file), "00000000,00000000\n").unwrap();
+            }
+        }
+
+        let affinity = QueueAffinity::new(&Link::new("rip0")).with_root(root.path());
+        assert!(affinity.rps(0).unwrap().is_empty());
+
+        for queue in 0..2 {
+            let cpus: CpuSet = [queue, queue + 32].iter().copied().collect();
+            affinity.set_cpus(queue, &cpus).unwrap();
+            assert_eq!(cpus, affinity.rps(queue).unwrap());
+            assert_eq!(cpus, affinity.xps(queue).unwrap());
+        }
+        assert_eq!(
+            "2,00000002",
+            fs::read_to_string(root.path().join("rip0/queues/tx-1/xps_cpus")).unwrap()
+        );
+        assert!(affinity.set_rps(2, &CpuSet::new()).is_err());
+    }
+}
@@ -0,0 +1,41 @@
+// (c) Copyright 2021 Christian Saide
+// SPDX-License-Identifier: MIT
+
+use super::{Error, LinkEvent, Monitor, Result};
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use async_io::Async;
+use futures_util::{ready, Stream};
+
+/// An async wrapper around the [Monitor] object leveraging the [Async] struct internally,
+/// exposing link changes as a [Stream] of [LinkEvent]s within the `async-std`/`smol` ecosystems.
+///
+/// The stream completes after yielding [`LinkEvent::Deleted`].
+pub struct AsyncStdMonitor(Async<Monitor>);
+
+impl AsyncStdMonitor {
+    /// Wrap the supplied [Monitor], registering it with the global async-io reactor.
+    pub(crate) fn new(monitor: Monitor) -> Result<Self> {
+        let async_fd = Async::new(monitor)?;
+        Ok(Self(async_fd))
+    }
+}
+
+impl Stream for AsyncStdMonitor {
+    type Item = Result<LinkEvent>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            match this.0.get_mut().next_event() {
+                Ok(event) => return Poll::Ready(event.map(Ok)),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+                Err(err) => return Poll::Ready(Some(Err(Error::from(err)))),
+            }
+            ready!(this.0.poll_readable(cx))?;
+        }
+    }
+}
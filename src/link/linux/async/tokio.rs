@@ -0,0 +1,41 @@
+// (c) Copyright 2021 Christian Saide
+// SPDX-License-Identifier: MIT
+
+use super::{Error, LinkEvent, Monitor, Result};
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_util::{ready, Stream};
+use tokio::io::unix::AsyncFd;
+
+/// An async wrapper around the [Monitor] object leveraging the [AsyncFd] struct internally,
+/// exposing link changes as a [Stream] of [LinkEvent]s within the `tokio` ecosystem.
+///
+/// The stream completes after yielding [`LinkEvent::Deleted`].
+pub struct TokioMonitor(AsyncFd<Monitor>);
+
+impl TokioMonitor {
+    /// Wrap the supplied [Monitor], registering it with the current tokio reactor.
+    pub(crate) fn new(monitor: Monitor) -> Result<Self> {
+        monitor.set_non_blocking(true)?;
+        let async_fd = crate::async_fd::new(monitor)?;
+        Ok(Self(async_fd))
+    }
+}
+
+impl Stream for TokioMonitor {
+    type Item = Result<LinkEvent>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            let mut guard = ready!(this.0.poll_read_ready_mut(cx))?;
+            match guard.try_io(|monitor| monitor.get_mut().next_event()) {
+                Ok(Ok(event)) => return Poll::Ready(event.map(Ok)),
+                Ok(Err(err)) => return Poll::Ready(Some(Err(Error::from(err)))),
+                Err(_) => continue,
+            }
+        }
+    }
+}
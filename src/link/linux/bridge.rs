@@ -0,0 +1,156 @@
+// (c) Copyright 2021 Christian Saide
+// SPDX-License-Identifier: MIT
+
+use super::netlink::{self, IfInfoMsg, Message, NdMsg};
+use super::{Link, MacAddr, NeighborState, Result};
+
+use nix::libc;
+
+const KIND: &str = "bridge";
+
+/// An entry in the forwarding database (FDB) of the bridge a link is attached to, directing
+/// frames destined to the hardware address out of that link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FdbEntry {
+    /// The destination hardware address.
+    pub mac: MacAddr,
+    /// The VLAN the entry applies to, or `None` for untagged traffic.
+    pub vlan: Option<u16>,
+    /// The state of the entry, [`NeighborState::NoArp`] for static entries, and
+    /// [`NeighborState::Reachable`] for dynamically learned ones.
+    pub state: NeighborState,
+}
+
+impl FdbEntry {
+    /// Create a new static entry, which the bridge will never age out.
+    pub fn fixed(mac: MacAddr) -> Self {
+        Self {
+            mac,
+            vlan: None,
+            state: NeighborState::NoArp,
+        }
+    }
+
+    /// Restrict this entry to the supplied VLAN.
+    pub fn with_vlan(mut self, vlan: u16) -> Self {
+        self.vlan = Some(vlan);
+        self
+    }
+
+    fn parse(resp: &netlink::Response, index: i32) -> Option<Self> {
+        let hdr: NdMsg = netlink::from_bytes(&resp.payload)?;
+        if hdr.index != index || hdr.flags & libc::NTF_SELF != 0 {
+            return None;
+        }
+
+        let mut mac = None;
+        let mut vlan = None;
+        for (ty, data) in resp.attrs::<NdMsg>() {
+            match ty {
+                libc::NDA_LLADDR => mac = netlink::from_bytes(data).map(MacAddr::new),
+                libc::NDA_VLAN => vlan = netlink::from_bytes(data),
+                _ => {}
+            }
+        }
+
+        Some(Self {
+            mac: mac?,
+            vlan,
+            state: NeighborState::from_raw(hdr.state),
+        })
+    }
+
+    fn message(&self, ty: u16, flags: u16, index: i32) -> Message {
+        let hdr = NdMsg {
+            family: libc::AF_BRIDGE as u8,
+            index,
+            state: self.state.to_raw(),
+            flags: libc::NTF_MASTER,
+            ..Default::default()
+        };
+
+        let mut msg = Message::new(ty, flags, &hdr);
+        msg.attr(libc::NDA_LLADDR, &self.mac.octets());
+        if let Some(vlan) = self.vlan {
+            msg.attr(libc::NDA_VLAN, &vlan.to_ne_bytes());
+        }
+        msg
+    }
+}
+
+pub(super) fn create(link: &Link) -> Result<()> {
+    let mut msg = Message::new(
+        libc::RTM_NEWLINK,
+        netlink::NLM_F_CREATE | netlink::NLM_F_EXCL,
+        &IfInfoMsg::default(),
+    );
+    msg.attr_str(libc::IFLA_IFNAME, link.name());
+    msg.nested(libc::IFLA_LINKINFO, |msg| {
+        msg.attr_str(libc::IFLA_INFO_KIND, KIND);
+    });
+    link.request(&mut msg).map(|_| ())
+}
+
+pub(super) fn set_master(link: &Link, master: Option<&Link>) -> Result<()> {
+    let master = match master {
+        Some(master) => master.index()? as u32,
+        None => 0,
+    };
+    let hdr = IfInfoMsg {
+        index: link.index()?,
+        ..Default::default()
+    };
+
+    let mut msg = Message::new(libc::RTM_NEWLINK, 0, &hdr);
+    msg.attr_u32(libc::IFLA_MASTER, master);
+    link.request(&mut msg).map(|_| ())
+}
+
+pub(super) fn add_fdb(link: &Link, entry: &FdbEntry) -> Result<()> {
+    let mut msg = entry.message(
+        libc::RTM_NEWNEIGH,
+        netlink::NLM_F_CREATE | netlink::NLM_F_REPLACE,
+        link.index()?,
+    );
+    link.request(&mut msg).map(|_| ())
+}
+
+pub(super) fn delete_fdb(link: &Link, entry: &FdbEntry) -> Result<()> {
+    let mut msg = entry.message(libc::RTM_DELNEIGH, 0, link.index()?);
+    link.request(&mut msg).map(|_| ())
+}
+
+pub(super) fn list_fdb(link: &Link) -> Result<Vec<FdbEntry>> {
+    let index = link.index()?;
+    let hdr = NdMsg {
+        family: libc::AF_BRIDGE as u8,
+        ..Default::default()
+    };
+
+    let mut msg = Message::new(libc::RTM_GETNEIGH, netlink::NLM_F_DUMP, &hdr);
+    let entries = link
+        .request(&mut msg)?
+        .iter()
+        .filter(|resp| resp.ty == libc::RTM_NEWNEIGH)
+        .filter_map(|resp| FdbEntry::parse(resp, index))
+        .collect();
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fdb_parse() {
+        let entry = FdbEntry::fixed(MacAddr::new([0x02, 0, 0, 0, 0, 1])).with_vlan(10);
+        let msg = entry.message(libc::RTM_NEWNEIGH, 0, 7);
+
+        let resp = netlink::Response {
+            ty: libc::RTM_NEWNEIGH,
+            payload: msg.payload().to_vec(),
+        };
+        assert_eq!(Some(entry), FdbEntry::parse(&resp, 7));
+        assert_eq!(None, FdbEntry::parse(&resp, 8));
+    }
+}
@@ -0,0 +1,223 @@
+// (c) Copyright 2021 Christian Saide
+// SPDX-License-Identifier: MIT
+
+use super::{Error, Result};
+
+use nix::libc;
+
+use std::fmt;
+use std::os::unix::io::{AsRawFd, OwnedFd};
+use std::str::FromStr;
+
+const IF_NAME_SIZE: usize = libc::IFNAMSIZ;
+const MAC_SIZE: usize = 6;
+const LOCAL_BIT: u8 = 0x02;
+const MULTICAST_BIT: u8 = 0x01;
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+nix::ioctl_read_bad!(get_hw_addr, libc::SIOCGIFHWADDR, HwAddrReq);
+nix::ioctl_write_ptr_bad!(set_hw_addr, libc::SIOCSIFHWADDR, HwAddrReq);
+
+/// Mirrors the layout of the kernel's `struct ifreq` when used with the `SIOC[GS]IFHWADDR` calls.
+#[repr(C)]
+struct HwAddrReq {
+    name: [u8; IF_NAME_SIZE],
+    addr: libc::sockaddr,
+    _pad: [u8; 8],
+}
+
+impl HwAddrReq {
+    fn new(name: &str) -> Result<Self> {
+        if name.is_empty() || !name.is_ascii() || name.len() >= IF_NAME_SIZE {
+            return Err(Error::InvalidName {
+                max_size: IF_NAME_SIZE,
+                name: String::from(name),
+            });
+        }
+
+        let mut req: Self = unsafe { std::mem::zeroed() };
+        req.name[..name.len()].copy_from_slice(name.as_bytes());
+        Ok(req)
+    }
+}
+
+/// A 48-bit ethernet hardware address, as assigned to layer 2 ([`Mode::Tap`][crate::Mode::Tap]) devices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct MacAddr([u8; MAC_SIZE]);
+
+impl MacAddr {
+    /// Create a new hardware address from the supplied octets.
+    #[inline]
+    pub const fn new(octets: [u8; MAC_SIZE]) -> Self {
+        Self(octets)
+    }
+
+    /// Generate a random locally administered unicast hardware address, sourcing randomness from the
+    /// OS random number generator.
+    pub fn random() -> Result<Self> {
+        let mut octets = [0u8; MAC_SIZE];
+        let ret = unsafe { libc::getrandom(octets.as_mut_ptr() as *mut libc::c_void, MAC_SIZE, 0) };
+        if ret < 0 {
+            return Err(Error::errno());
+        }
+        Ok(Self::local(octets))
+    }
+
+    /// Derive a stable locally administered unicast hardware address from the supplied name. The same
+    /// name will always produce the same address, across processes and hosts, which makes this suitable
+    /// for DHCP reservations and static forwarding database entries.
+    pub fn from_name(name: &str) -> Self {
+        let hash = name.as_bytes().iter().fold(FNV_OFFSET, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(FNV_PRIME)
+        });
+
+        let mut octets = [0u8; MAC_SIZE];
+        octets.copy_from_slice(&hash.to_be_bytes()[..MAC_SIZE]);
+        Self::local(octets)
+    }
+
+    /// Return the raw octets making up this hardware address.
+    #[inline]
+    pub const fn octets(&self) -> [u8; MAC_SIZE] {
+        self.0
+    }
+
+    /// Whether or not this is a locally administered address.
+    #[inline]
+    pub const fn is_local(&self) -> bool {
+        self.0[0] & LOCAL_BIT != 0
+    }
+
+    /// Whether or not this is a multicast (or broadcast) address.
+    #[inline]
+    pub const fn is_multicast(&self) -> bool {
+        self.0[0] & MULTICAST_BIT != 0
+    }
+
+    fn local(mut octets: [u8; MAC_SIZE]) -> Self {
+        octets[0] = (octets[0] | LOCAL_BIT) & !MULTICAST_BIT;
+        Self(octets)
+    }
+}
+
+impl From<[u8; MAC_SIZE]> for MacAddr {
+    #[inline]
+    fn from(octets: [u8; MAC_SIZE]) -> Self {
+        Self(octets)
+    }
+}
+
+impl fmt::Display for MacAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let o = &self.0;
+        write!(
+            f,
+            "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+            o[0], o[1], o[2], o[3], o[4], o[5]
+        )
+    }
+}
+
+impl FromStr for MacAddr {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::InvalidMacAddr(String::from(s));
+
+        let mut octets = [0u8; MAC_SIZE];
+        let mut parts = s.split(':');
+        for octet in octets.iter_mut() {
+            let part = parts.next().ok_or_else(invalid)?;
+            if part.len() != 2 {
+                return Err(invalid());
+            }
+            *octet = u8::from_str_radix(part, 16).map_err(|_| invalid())?;
+        }
+
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(Self(octets))
+    }
+}
+
+pub(super) fn get(sock: &OwnedFd, name: &str) -> Result<MacAddr> {
+    let mut req = HwAddrReq::new(name)?;
+    unsafe { get_hw_addr(sock.as_raw_fd(), &mut req) }
+        .map_err(|err| Error::from(err).context("SIOCGIFHWADDR", name))?;
+
+    if req.addr.sa_family != libc::ARPHRD_ETHER {
+        return Err(Error::from(nix::errno::Errno::EOPNOTSUPP));
+    }
+
+    let mut octets = [0u8; MAC_SIZE];
+    octets
+        .iter_mut()
+        .zip(req.addr.sa_data.iter())
+        .for_each(|(octet, data)| *octet = *data as u8);
+    Ok(MacAddr(octets))
+}
+
+pub(super) fn set(sock: &OwnedFd, name: &str, addr: MacAddr) -> Result<()> {
+    let mut req = HwAddrReq::new(name)?;
+    req.addr.sa_family = libc::ARPHRD_ETHER;
+    req.addr
+        .sa_data
+        .iter_mut()
+        .zip(addr.0.iter())
+        .for_each(|(data, octet)| *data = *octet as libc::c_char);
+
+    unsafe { set_hw_addr(sock.as_raw_fd(), &req) }
+        .map_err(|err| Error::from(err).context("SIOCSIFHWADDR", name))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_roundtrip() {
+        let addr = MacAddr::from_str("02:00:5e:10:ab:ff");
+        assert!(addr.is_ok());
+        let addr = addr.unwrap();
+        assert_eq!([0x02, 0x00, 0x5e, 0x10, 0xab, 0xff], addr.octets());
+        assert_eq!("02:00:5e:10:ab:ff", addr.to_string());
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for input in &[
+            "",
+            "02:00:5e:10:ab",
+            "02:00:5e:10:ab:ff:00",
+            "2:0:5e:10:ab:ff",
+            "zz:00:5e:10:ab:ff",
+        ] {
+            match MacAddr::from_str(input) {
+                Err(Error::InvalidMacAddr(..)) => {}
+                res => panic!("unexpected result for '{}': {:?}", input, res),
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_name_stable() {
+        let first = MacAddr::from_name("tap0");
+        let second = MacAddr::from_name("tap0");
+        assert_eq!(first, second);
+        assert_ne!(first, MacAddr::from_name("tap1"));
+        assert!(first.is_local());
+        assert!(!first.is_multicast());
+    }
+
+    #[test]
+    fn test_random_local_unicast() {
+        let addr = MacAddr::random();
+        assert!(addr.is_ok());
+        let addr = addr.unwrap();
+        assert!(addr.is_local());
+        assert!(!addr.is_multicast());
+    }
+}
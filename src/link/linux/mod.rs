@@ -0,0 +1,439 @@
+// (c) Copyright 2021 Christian Saide
+// SPDX-License-Identifier: MIT
+
+use super::{Error, Result};
+
+use cfg_if::cfg_if;
+use nix::libc;
+
+use std::net::IpAddr;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd};
+
+mod addr;
+mod affinity;
+mod bridge;
+mod mac;
+mod monitor;
+mod neigh;
+mod netlink;
+mod netns;
+mod qdisc;
+mod route;
+mod stats;
+mod sysctl;
+
+pub use addr::IpNet;
+pub use affinity::{CpuSet, QueueAffinity};
+pub use bridge::FdbEntry;
+pub use mac::MacAddr;
+pub use monitor::{LinkEvent, Monitor};
+pub use neigh::{Neighbor, NeighborState};
+pub use netns::NetNs;
+pub use qdisc::{FqCodel, Qdisc};
+pub use route::{Route, Rule};
+pub use stats::LinkStats;
+pub use sysctl::{AcceptRa, AddrGenMode, RpFilter, Sysctl};
+
+cfg_if! {
+    if #[cfg(feature = "async-std-impl")] {
+        #[path = "async/std.rs"]
+        mod async_std;
+        pub use self::async_std::AsyncStdMonitor;
+    }
+}
+
+cfg_if! {
+    if #[cfg(feature = "tokio-impl")] {
+        #[path = "async/tokio.rs"]
+        mod async_tokio;
+        pub use self::async_tokio::TokioMonitor;
+    }
+}
+
+const IFF_UP: u32 = libc::IFF_UP as u32;
+const SYSFS_ROOT: &str = "/sys/class/net";
+
+/// A handle to the kernel network interface backing a virtual device, exposing link level
+/// configuration such as the hardware address.
+#[derive(Debug, Clone)]
+pub struct Link {
+    name: String,
+    netns: Option<NetNs>,
+}
+
+impl Link {
+    /// Create a new handle referencing the network interface with the supplied name. Note that
+    /// no validation is done here, any errors will be surfaced when the link is operated on.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: String::from(name),
+            netns: None,
+        }
+    }
+
+    /// Create a new handle referencing the network interface with the supplied name, residing
+    /// in the supplied network namespace. All operations on the link are executed within that
+    /// namespace, see [`NetNs`] for more details.
+    pub fn with_netns(name: &str, netns: NetNs) -> Self {
+        Self {
+            name: String::from(name),
+            netns: Some(netns),
+        }
+    }
+
+    /// Create a new bridge with the supplied name in the network namespace of the calling thread,
+    /// returning a handle referencing it. Other links are attached to the bridge via
+    /// [`Link::set_master()`].
+    pub fn create_bridge(name: &str) -> Result<Self> {
+        let link = Self::new(name);
+        bridge::create(&link)?;
+        Ok(link)
+    }
+
+    /// Delete this link from the kernel, for instance a bridge previously created via
+    /// [`Link::create_bridge()`]. Any links attached to a deleted bridge are released.
+    pub fn delete(&self) -> Result<()> {
+        let hdr = netlink::IfInfoMsg {
+            index: self.index()?,
+            ..Default::default()
+        };
+        let mut msg = netlink::Message::new(libc::RTM_DELLINK, 0, &hdr);
+        self.request(&mut msg).map(|_| ())
+    }
+
+    /// Return the name of the network interface this handle references.
+    #[inline]
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    /// Return the network namespace this link resides in, or `None` if it resides in the
+    /// namespace of the calling thread.
+    #[inline]
+    pub fn netns(&self) -> Option<&NetNs> {
+        self.netns.as_ref()
+    }
+
+    /// Move this link into the supplied network namespace. All subsequent operations on this
+    /// handle are executed within the new namespace, and any open queues remain valid.
+    ///
+    /// # Errors
+    /// If a link with the same name already exists in the target namespace the move fails.
+    pub fn move_to_netns(&mut self, netns: &NetNs) -> Result<()> {
+        let hdr = netlink::IfInfoMsg {
+            index: self.index()?,
+            ..Default::default()
+        };
+        let mut msg = netlink::Message::new(libc::RTM_NEWLINK, 0, &hdr);
+        msg.attr_u32(libc::IFLA_NET_NS_FD, netns.as_raw_fd() as u32);
+        self.request(&mut msg)?;
+
+        self.netns = Some(netns.clone());
+        Ok(())
+    }
+
+    /// Retrieve the kernel assigned interface index of this link, as required by socket options
+    /// such as `IPV6_MULTICAST_IF` or `IP_PKTINFO`.
+    pub fn ifindex(&self) -> Result<u32> {
+        self.index().map(|index| index as u32)
+    }
+
+    /// Rename this link, updating the name referenced by this handle on success. Note that older
+    /// kernels refuse to rename links which are up, failing with `EBUSY`.
+    pub fn rename(&mut self, name: &str) -> Result<()> {
+        if name.is_empty() || !name.is_ascii() || name.len() >= libc::IFNAMSIZ {
+            return Err(Error::InvalidName {
+                max_size: libc::IFNAMSIZ,
+                name: String::from(name),
+            });
+        }
+
+        let hdr = netlink::IfInfoMsg {
+            index: self.index()?,
+            ..Default::default()
+        };
+        let mut msg = netlink::Message::new(libc::RTM_NEWLINK, 0, &hdr);
+        msg.attr_str(libc::IFLA_IFNAME, name);
+        self.request(&mut msg)?;
+
+        self.name = String::from(name);
+        Ok(())
+    }
+
+    /// Retrieve the alias (`ifalias`) description of this link, or `None` if it has none.
+    pub fn alias(&self) -> Result<Option<String>> {
+        let alias = self
+            .info()?
+            .iter()
+            .filter(|resp| resp.ty == libc::RTM_NEWLINK)
+            .flat_map(|resp| resp.attrs::<netlink::IfInfoMsg>())
+            .find(|(ty, _)| *ty == libc::IFLA_IFALIAS)
+            .map(|(_, data)| netlink::parse_str(data))
+            .filter(|alias| !alias.is_empty());
+        Ok(alias)
+    }
+
+    /// Set the alias (`ifalias`) description of this link, for instance to identify the service
+    /// owning it. An empty alias removes any existing alias.
+    pub fn set_alias(&self, alias: &str) -> Result<()> {
+        let hdr = netlink::IfInfoMsg {
+            index: self.index()?,
+            ..Default::default()
+        };
+        let mut msg = netlink::Message::new(libc::RTM_NEWLINK, 0, &hdr);
+        msg.attr(libc::IFLA_IFALIAS, alias.as_bytes());
+        self.request(&mut msg).map(|_| ())
+    }
+
+    /// Retrieve the MTU of this link.
+    pub fn mtu(&self) -> Result<u32> {
+        self.info()?
+            .iter()
+            .filter(|resp| resp.ty == libc::RTM_NEWLINK)
+            .flat_map(|resp| resp.attrs::<netlink::IfInfoMsg>())
+            .find(|(ty, _)| *ty == libc::IFLA_MTU)
+            .and_then(|(_, data)| netlink::from_bytes(data))
+            .ok_or_else(|| Error::from(nix::errno::Errno::ENODATA))
+    }
+
+    /// Set the MTU of this link.
+    pub fn set_mtu(&self, mtu: u32) -> Result<()> {
+        let hdr = netlink::IfInfoMsg {
+            index: self.index()?,
+            ..Default::default()
+        };
+        let mut msg = netlink::Message::new(libc::RTM_NEWLINK, 0, &hdr);
+        msg.attr_u32(libc::IFLA_MTU, mtu);
+        self.request(&mut msg).map(|_| ())
+    }
+
+    /// Return whether this link is administratively up.
+    pub fn is_up(&self) -> Result<bool> {
+        self.header().map(|hdr| hdr.flags & IFF_UP != 0)
+    }
+
+    /// Administratively bring this link up (`true`) or down (`false`), which is analogous to
+    /// `ip link set <name> up`.
+    pub fn set_up(&self, up: bool) -> Result<()> {
+        let hdr = netlink::IfInfoMsg {
+            index: self.index()?,
+            flags: if up { IFF_UP } else { 0 },
+            change: IFF_UP,
+            ..Default::default()
+        };
+        let mut msg = netlink::Message::new(libc::RTM_NEWLINK, 0, &hdr);
+        self.request(&mut msg).map(|_| ())
+    }
+
+    /// List the IPv4 and IPv6 addresses assigned to this link.
+    pub fn addresses(&self) -> Result<Vec<IpNet>> {
+        addr::list(self)
+    }
+
+    /// Assign the supplied address and prefix length to this link.
+    ///
+    /// # Errors
+    /// If the address is already assigned to this link the operation fails.
+    pub fn add_address(&self, net: IpNet) -> Result<()> {
+        addr::add(self, &net)
+    }
+
+    /// Remove the supplied address and prefix length from this link.
+    pub fn delete_address(&self, net: IpNet) -> Result<()> {
+        addr::delete(self, &net)
+    }
+
+    /// Add the supplied route out of this link. Note that the kernel rejects routes on links
+    /// which are down.
+    pub fn add_route(&self, route: &Route) -> Result<()> {
+        route::add_route(self, route)
+    }
+
+    /// Remove the supplied route out of this link.
+    pub fn delete_route(&self, route: &Route) -> Result<()> {
+        route::delete_route(self, route)
+    }
+
+    /// Add the supplied policy routing rule, within the namespace of this link.
+    pub fn add_rule(&self, rule: &Rule) -> Result<()> {
+        route::add_rule(self, rule)
+    }
+
+    /// Remove the supplied policy routing rule, within the namespace of this link.
+    pub fn delete_rule(&self, rule: &Rule) -> Result<()> {
+        route::delete_rule(self, rule)
+    }
+
+    /// Retrieve the root queueing discipline of this link.
+    pub fn qdisc(&self) -> Result<Qdisc> {
+        qdisc::get(self)
+    }
+
+    /// Replace the root queueing discipline of this link, which is analogous to
+    /// `tc qdisc replace dev <name> root <qdisc>`.
+    pub fn set_qdisc(&self, qdisc: &Qdisc) -> Result<()> {
+        qdisc::set(self, qdisc)
+    }
+
+    /// Remove the root queueing discipline of this link, reverting to the kernel default.
+    pub fn reset_qdisc(&self) -> Result<()> {
+        qdisc::reset(self)
+    }
+
+    /// Retrieve the hardware (MAC) address currently assigned to this link.
+    ///
+    /// # Errors
+    /// Layer 3 devices, for instance those created in [`Mode::Tun`][crate::Mode::Tun], have no
+    /// hardware address and will return an error.
+    pub fn mac_address(&self) -> Result<MacAddr> {
+        mac::get(&self.control_socket()?, self.name())
+    }
+
+    /// Assign the supplied hardware (MAC) address to this link. See [MacAddr] for helpers to
+    /// generate random or stable locally administered addresses.
+    ///
+    /// # Errors
+    /// Layer 3 devices, for instance those created in [`Mode::Tun`][crate::Mode::Tun], have no
+    /// hardware address and will return an error.
+    pub fn set_mac_address(&self, addr: MacAddr) -> Result<()> {
+        mac::set(&self.control_socket()?, self.name(), addr)
+    }
+
+    /// Retrieve the kernel's 64-bit interface statistics for this link. The statistics are queried
+    /// via rtnetlink, falling back to `/sys/class/net/<name>/statistics` if that fails.
+    pub fn stats(&self) -> Result<LinkStats> {
+        stats::get(self)
+    }
+
+    /// Create a handle exposing typed access to the per-interface sysctls of this link, see
+    /// [Sysctl] for more details.
+    pub fn sysctl(&self) -> Sysctl {
+        Sysctl::new(self)
+    }
+
+    /// Create a handle exposing the RPS and XPS CPU masks of the queues of this link, see
+    /// [QueueAffinity] for more details.
+    pub fn queue_affinity(&self) -> QueueAffinity {
+        QueueAffinity::new(self)
+    }
+
+    /// Create a blocking [Monitor] reporting changes to this link, such as MTU, carrier, and
+    /// address changes, or its deletion.
+    pub fn monitor(&self) -> Result<Monitor> {
+        Monitor::new(self)
+    }
+
+    /// Add the supplied entry to the neighbor (ARP/NDP) table of this link, replacing any existing
+    /// entry for the same address.
+    pub fn add_neighbor(&self, neighbor: &Neighbor) -> Result<()> {
+        neigh::add(self, neighbor)
+    }
+
+    /// Remove the entry for the supplied address from the neighbor (ARP/NDP) table of this link.
+    pub fn delete_neighbor(&self, addr: IpAddr) -> Result<()> {
+        neigh::delete(self, addr)
+    }
+
+    /// List all IPv4 and IPv6 entries in the neighbor (ARP/NDP) table of this link.
+    pub fn neighbors(&self) -> Result<Vec<Neighbor>> {
+        neigh::list(self)
+    }
+
+    /// Attach this link to the supplied master link, such as a bridge, which is analogous to
+    /// `ip link set <name> master <master>`. Both links must reside in the same namespace.
+    pub fn set_master(&self, master: &Link) -> Result<()> {
+        bridge::set_master(self, Some(master))
+    }
+
+    /// Release this link from its current master link, if any.
+    pub fn clear_master(&self) -> Result<()> {
+        bridge::set_master(self, None)
+    }
+
+    /// Add the supplied entry to the forwarding database of the bridge this link is attached to,
+    /// replacing any existing entry for the same address and VLAN.
+    pub fn add_fdb(&self, entry: &FdbEntry) -> Result<()> {
+        bridge::add_fdb(self, entry)
+    }
+
+    /// Remove the supplied entry from the forwarding database of the bridge this link is
+    /// attached to.
+    pub fn delete_fdb(&self, entry: &FdbEntry) -> Result<()> {
+        bridge::delete_fdb(self, entry)
+    }
+
+    /// List all forwarding database entries of the bridge this link is attached to, which direct
+    /// frames out of this link.
+    pub fn fdb(&self) -> Result<Vec<FdbEntry>> {
+        bridge::list_fdb(self)
+    }
+
+    /// Retrieve the kernel assigned interface index of this link.
+    fn index(&self) -> Result<i32> {
+        self.header().map(|hdr| hdr.index)
+    }
+
+    /// Retrieve the fixed `ifinfomsg` header describing this link.
+    fn header(&self) -> Result<netlink::IfInfoMsg> {
+        self.info()?
+            .iter()
+            .filter(|resp| resp.ty == libc::RTM_NEWLINK)
+            .find_map(|resp| netlink::from_bytes::<netlink::IfInfoMsg>(&resp.payload))
+            .ok_or_else(|| Error::from(nix::errno::Errno::ENODEV))
+    }
+
+    /// Retrieve the raw `RTM_NEWLINK` description of this link from the kernel.
+    fn info(&self) -> Result<Vec<netlink::Response>> {
+        let mut msg = netlink::Message::new(libc::RTM_GETLINK, 0, &netlink::IfInfoMsg::default());
+        msg.attr_str(libc::IFLA_IFNAME, self.name());
+        self.request(&mut msg)
+    }
+
+    /// Issue the supplied rtnetlink request within the namespace of this link.
+    fn request(&self, msg: &mut netlink::Message) -> Result<Vec<netlink::Response>> {
+        self.socket(0)?
+            .request(msg)
+            .map_err(|err| err.context(msg.op(), self.name()))
+    }
+
+    /// Open a `NETLINK_ROUTE` socket within the namespace of this link, subscribed to the
+    /// supplied multicast groups.
+    fn socket(&self, groups: u32) -> Result<netlink::Socket> {
+        match &self.netns {
+            Some(netns) => netns.run(|| netlink::Socket::route(groups)),
+            None => netlink::Socket::route(groups),
+        }
+    }
+
+    /// Open a generic datagram socket within the namespace of this link, used as the target for
+    /// the legacy interface ioctl calls.
+    fn control_socket(&self) -> Result<OwnedFd> {
+        let open = || {
+            let fd =
+                unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0) };
+            if fd < 0 {
+                return Err(Error::errno());
+            }
+            Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+        };
+
+        match &self.netns {
+            Some(netns) => netns.run(open),
+            None => open(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rename_invalid_name() {
+        let mut link = Link::new("rip0");
+        for name in ["", "😀", "a-very-long-device-name"] {
+            assert!(matches!(link.rename(name), Err(Error::InvalidName { .. })));
+        }
+        assert_eq!("rip0", link.name());
+    }
+}
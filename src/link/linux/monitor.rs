@@ -0,0 +1,239 @@
+// (c) Copyright 2021 Christian Saide
+// SPDX-License-Identifier: MIT
+
+use super::netlink::{self, parse_str, IfInfoMsg, Messages, Socket};
+use super::{addr, Error, Link, Result};
+
+use nix::libc;
+
+use std::collections::VecDeque;
+use std::io;
+use std::net::IpAddr;
+use std::os::unix::io::{AsRawFd, RawFd};
+
+const GROUPS: u32 =
+    (libc::RTMGRP_LINK | libc::RTMGRP_IPV4_IFADDR | libc::RTMGRP_IPV6_IFADDR) as u32;
+const IFF_UP: u32 = libc::IFF_UP as u32;
+const IFF_LOWER_UP: u32 = libc::IFF_LOWER_UP as u32;
+
+/// A change to a network interface observed via a [Monitor].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkEvent {
+    /// The interface was deleted, or moved to another network namespace. No further events
+    /// will be reported.
+    Deleted,
+    /// The interface was renamed to the contained name.
+    Renamed(String),
+    /// The MTU of the interface was changed to the contained value.
+    MtuChanged(u32),
+    /// The interface was administratively brought up (`true`) or down (`false`).
+    AdminStateChanged(bool),
+    /// The carrier of the interface was gained (`true`) or lost (`false`).
+    CarrierChanged(bool),
+    /// The contained address and prefix length was assigned to the interface.
+    AddressAdded(IpAddr, u8),
+    /// The contained address and prefix length was removed from the interface.
+    AddressRemoved(IpAddr, u8),
+}
+
+/// The subset of link attributes tracked in order to compute [LinkEvent]s.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct LinkState {
+    name: String,
+    mtu: u32,
+    up: bool,
+    carrier: bool,
+}
+
+impl LinkState {
+    fn parse(resp: &netlink::Response) -> Option<(i32, Self)> {
+        let hdr: IfInfoMsg = netlink::from_bytes(&resp.payload)?;
+        let mut state = Self {
+            up: hdr.flags & IFF_UP != 0,
+            carrier: hdr.flags & IFF_LOWER_UP != 0,
+            ..Default::default()
+        };
+
+        for (ty, data) in resp.attrs::<IfInfoMsg>() {
+            match ty {
+                libc::IFLA_IFNAME => state.name = parse_str(data),
+                libc::IFLA_MTU => state.mtu = netlink::from_bytes(data).unwrap_or_default(),
+                _ => {}
+            }
+        }
+        Some((hdr.index, state))
+    }
+
+    fn diff(&self, next: &Self, events: &mut VecDeque<LinkEvent>) {
+        if self.name != next.name {
+            events.push_back(LinkEvent::Renamed(next.name.clone()));
+        }
+        if self.mtu != next.mtu {
+            events.push_back(LinkEvent::MtuChanged(next.mtu));
+        }
+        if self.up != next.up {
+            events.push_back(LinkEvent::AdminStateChanged(next.up));
+        }
+        if self.carrier != next.carrier {
+            events.push_back(LinkEvent::CarrierChanged(next.carrier));
+        }
+    }
+}
+
+/// A blocking monitor reporting changes to a single network interface, as an [Iterator] over
+/// [LinkEvent]s. Changes are observed by subscribing to the rtnetlink link and address
+/// notification groups, and are filtered to the interface index of the monitored link.
+///
+/// The iterator completes after yielding [`LinkEvent::Deleted`].
+pub struct Monitor {
+    sock: Socket,
+    index: i32,
+    state: LinkState,
+    events: VecDeque<LinkEvent>,
+    buf: Vec<u8>,
+    deleted: bool,
+}
+
+impl Monitor {
+    /// Subscribe to changes of the supplied [Link].
+    pub(crate) fn new(link: &Link) -> Result<Self> {
+        // Subscribe prior to snapshotting the current state, so no changes are missed.
+        let sock = link.socket(GROUPS)?;
+        let (index, state) = link
+            .info()?
+            .iter()
+            .find_map(LinkState::parse)
+            .ok_or_else(|| Error::from(nix::errno::Errno::ENODEV))?;
+
+        Ok(Self {
+            sock,
+            index,
+            state,
+            events: VecDeque::new(),
+            buf: vec![0u8; netlink::RECV_BUFFER_SIZE],
+            deleted: false,
+        })
+    }
+
+    /// Either enable or disable non-blocking mode on the underlying netlink socket. In non-blocking
+    /// mode iteration yields an error of kind [`WouldBlock`][std::io::ErrorKind::WouldBlock] when no
+    /// notifications are pending, which is useful when integrating with an external event loop.
+    #[inline]
+    pub fn set_non_blocking(&self, on: bool) -> Result<()> {
+        self.sock.set_non_blocking(on)
+    }
+
+    /// Attempt to retrieve the next event, reading at most one notification datagram from the
+    /// kernel. `Ok(None)` is returned once the link has been deleted and all events consumed.
+    ///
+    /// In non-blocking mode this can and will return [`WouldBlock`][std::io::ErrorKind::WouldBlock].
+    pub(crate) fn next_event(&mut self) -> io::Result<Option<LinkEvent>> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(Some(event));
+            }
+            if self.deleted {
+                return Ok(None);
+            }
+
+            let read = self.sock.recv(&mut self.buf)?;
+            let buf = std::mem::take(&mut self.buf);
+            for (hdr, payload) in Messages::new(&buf[..read]) {
+                let resp = netlink::Response {
+                    ty: hdr.ty,
+                    payload: payload.to_vec(),
+                };
+                self.handle(&resp);
+            }
+            self.buf = buf;
+        }
+    }
+
+    fn handle(&mut self, resp: &netlink::Response) {
+        if self.deleted {
+            return;
+        }
+
+        match resp.ty {
+            libc::RTM_NEWLINK => {
+                if let Some((index, state)) = LinkState::parse(resp) {
+                    if index == self.index {
+                        self.state.diff(&state, &mut self.events);
+                        self.state = state;
+                    }
+                }
+            }
+            libc::RTM_DELLINK => {
+                let hdr: Option<IfInfoMsg> = netlink::from_bytes(&resp.payload);
+                if hdr.map(|hdr| hdr.index) == Some(self.index) {
+                    self.events.push_back(LinkEvent::Deleted);
+                    self.deleted = true;
+                }
+            }
+            libc::RTM_NEWADDR | libc::RTM_DELADDR => {
+                if let Some(net) = addr::parse(resp, self.index) {
+                    let (addr, prefix_len) = (net.addr(), net.prefix_len());
+                    self.events.push_back(match resp.ty {
+                        libc::RTM_NEWADDR => LinkEvent::AddressAdded(addr, prefix_len),
+                        _ => LinkEvent::AddressRemoved(addr, prefix_len),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Iterator for Monitor {
+    type Item = Result<LinkEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.next_event() {
+                Ok(event) => return event.map(Ok),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Some(Err(Error::from(err))),
+            }
+        }
+    }
+}
+
+impl AsRawFd for Monitor {
+    fn as_raw_fd(&self) -> RawFd {
+        self.sock.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(name: &str, mtu: u32, up: bool, carrier: bool) -> LinkState {
+        LinkState {
+            name: String::from(name),
+            mtu,
+            up,
+            carrier,
+        }
+    }
+
+    #[test]
+    fn test_state_diff() {
+        let mut events = VecDeque::new();
+        let prev = state("rip0", 1500, false, false);
+
+        prev.diff(&prev.clone(), &mut events);
+        assert!(events.is_empty());
+
+        prev.diff(&state("rip1", 1400, true, true), &mut events);
+        assert_eq!(
+            vec![
+                LinkEvent::Renamed(String::from("rip1")),
+                LinkEvent::MtuChanged(1400),
+                LinkEvent::AdminStateChanged(true),
+                LinkEvent::CarrierChanged(true),
+            ],
+            events.into_iter().collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,191 @@
+// (c) Copyright 2021 Christian Saide
+// SPDX-License-Identifier: MIT
+
+use super::netlink::{self, encode_addr, parse_addr, Message, NdMsg};
+use super::{Link, MacAddr, Result};
+
+use nix::libc;
+
+use std::net::IpAddr;
+
+/// The state of a neighbor table entry, mirroring the kernel's `NUD_*` states.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NeighborState {
+    /// Address resolution is in progress.
+    Incomplete,
+    /// The neighbor is confirmed reachable.
+    Reachable,
+    /// The neighbor has not been confirmed reachable recently.
+    Stale,
+    /// Waiting for upper layer confirmation before probing.
+    Delay,
+    /// The neighbor is being actively probed.
+    Probe,
+    /// Address resolution failed.
+    Failed,
+    /// The neighbor does not require address resolution.
+    NoArp,
+    /// A static entry which is never expired or re-resolved by the kernel.
+    Permanent,
+    /// The entry has no state.
+    None,
+}
+
+impl NeighborState {
+    pub(super) fn to_raw(self) -> u16 {
+        match self {
+            Self::Incomplete => libc::NUD_INCOMPLETE,
+            Self::Reachable => libc::NUD_REACHABLE,
+            Self::Stale => libc::NUD_STALE,
+            Self::Delay => libc::NUD_DELAY,
+            Self::Probe => libc::NUD_PROBE,
+            Self::Failed => libc::NUD_FAILED,
+            Self::NoArp => libc::NUD_NOARP,
+            Self::Permanent => libc::NUD_PERMANENT,
+            Self::None => libc::NUD_NONE,
+        }
+    }
+
+    pub(super) fn from_raw(raw: u16) -> Self {
+        match raw {
+            libc::NUD_INCOMPLETE => Self::Incomplete,
+            libc::NUD_REACHABLE => Self::Reachable,
+            libc::NUD_STALE => Self::Stale,
+            libc::NUD_DELAY => Self::Delay,
+            libc::NUD_PROBE => Self::Probe,
+            libc::NUD_FAILED => Self::Failed,
+            libc::NUD_NOARP => Self::NoArp,
+            libc::NUD_PERMANENT => Self::Permanent,
+            _ => Self::None,
+        }
+    }
+}
+
+/// An entry in the neighbor (ARP/NDP) table of a link, mapping an IPv4 or IPv6 address to the
+/// hardware address it is reachable at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Neighbor {
+    /// The protocol address of the neighbor.
+    pub addr: IpAddr,
+    /// The hardware address of the neighbor, if it is known.
+    pub mac: Option<MacAddr>,
+    /// The state of the entry.
+    pub state: NeighborState,
+}
+
+impl Neighbor {
+    /// Create a new static entry, which the kernel will never expire.
+    pub fn permanent(addr: IpAddr, mac: MacAddr) -> Self {
+        Self {
+            addr,
+            mac: Some(mac),
+            state: NeighborState::Permanent,
+        }
+    }
+
+    /// Create a new reachable entry, which the kernel will expire and re-resolve as usual.
+    pub fn reachable(addr: IpAddr, mac: MacAddr) -> Self {
+        Self {
+            addr,
+            mac: Some(mac),
+            state: NeighborState::Reachable,
+        }
+    }
+
+    fn parse(resp: &netlink::Response, index: i32) -> Option<Self> {
+        let hdr: NdMsg = netlink::from_bytes(&resp.payload)?;
+        if hdr.index != index {
+            return None;
+        }
+
+        let mut addr = None;
+        let mut mac = None;
+        for (ty, data) in resp.attrs::<NdMsg>() {
+            match ty {
+                libc::NDA_DST => addr = parse_addr(hdr.family, data),
+                libc::NDA_LLADDR => mac = netlink::from_bytes(data).map(MacAddr::new),
+                _ => {}
+            }
+        }
+
+        Some(Self {
+            addr: addr?,
+            mac,
+            state: NeighborState::from_raw(hdr.state),
+        })
+    }
+}
+
+pub(super) fn add(link: &Link, neighbor: &Neighbor) -> Result<()> {
+    let (family, addr) = encode_addr(&neighbor.addr);
+    let hdr = NdMsg {
+        family,
+        index: link.index()?,
+        state: neighbor.state.to_raw(),
+        ..Default::default()
+    };
+
+    let mut msg = Message::new(
+        libc::RTM_NEWNEIGH,
+        netlink::NLM_F_CREATE | netlink::NLM_F_REPLACE,
+        &hdr,
+    );
+    msg.attr(libc::NDA_DST, &addr);
+    if let Some(mac) = neighbor.mac {
+        msg.attr(libc::NDA_LLADDR, &mac.octets());
+    }
+    link.request(&mut msg).map(|_| ())
+}
+
+pub(super) fn delete(link: &Link, addr: IpAddr) -> Result<()> {
+    let (family, addr) = encode_addr(&addr);
+    let hdr = NdMsg {
+        family,
+        index: link.index()?,
+        ..Default::default()
+    };
+
+    let mut msg = Message::new(libc::RTM_DELNEIGH, 0, &hdr);
+    msg.attr(libc::NDA_DST, &addr);
+    link.request(&mut msg).map(|_| ())
+}
+
+pub(super) fn list(link: &Link) -> Result<Vec<Neighbor>> {
+    let index = link.index()?;
+    let hdr = NdMsg {
+        index,
+        ..Default::default()
+    };
+
+    let mut msg = Message::new(libc::RTM_GETNEIGH, netlink::NLM_F_DUMP, &hdr);
+    let neighbors = link
+        .request(&mut msg)?
+        .iter()
+        .filter(|resp| resp.ty == libc::RTM_NEWNEIGH)
+        .filter_map(|resp| Neighbor::parse(resp, index))
+        .collect();
+    Ok(neighbors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_roundtrip() {
+        let states = [
+            NeighborState::Incomplete,
+            NeighborState::Reachable,
+            NeighborState::Stale,
+            NeighborState::Delay,
+            NeighborState::Probe,
+            NeighborState::Failed,
+            NeighborState::NoArp,
+            NeighborState::Permanent,
+            NeighborState::None,
+        ];
+        for state in states.iter() {
+            assert_eq!(*state, NeighborState::from_raw(state.to_raw()));
+        }
+    }
+}
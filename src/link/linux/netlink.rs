@@ -0,0 +1,567 @@
+// (c) Copyright 2021 Christian Saide
+// SPDX-License-Identifier: MIT
+
+use super::{Error, Result};
+
+use nix::fcntl::{fcntl, FcntlArg, OFlag};
+use nix::libc;
+
+use std::io;
+use std::mem::{self, MaybeUninit};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+
+const HEADER_SIZE: usize = mem::size_of::<NlMsgHdr>();
+const ATTR_HEADER_SIZE: usize = 4;
+pub(crate) const RECV_BUFFER_SIZE: usize = 64 * 1024;
+
+const NLM_F_REQUEST: u16 = libc::NLM_F_REQUEST as u16;
+const NLM_F_ACK: u16 = libc::NLM_F_ACK as u16;
+pub(crate) const NLM_F_DUMP: u16 = libc::NLM_F_DUMP as u16;
+pub(crate) const NLM_F_CREATE: u16 = libc::NLM_F_CREATE as u16;
+pub(crate) const NLM_F_REPLACE: u16 = libc::NLM_F_REPLACE as u16;
+pub(crate) const NLM_F_EXCL: u16 = libc::NLM_F_EXCL as u16;
+
+const NLMSG_ERROR: u16 = libc::NLMSG_ERROR as u16;
+const NLMSG_DONE: u16 = libc::NLMSG_DONE as u16;
+const NLA_TYPE_MASK: u16 = libc::NLA_TYPE_MASK as u16;
+
+/// Round the supplied length up to the 4 byte netlink alignment.
+#[inline]
+pub(crate) const fn align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// Mirrors the kernel's `struct nlmsghdr`.
+#[derive(Debug, Default, Clone, Copy)]
+#[repr(C)]
+pub(crate) struct NlMsgHdr {
+    pub len: u32,
+    pub ty: u16,
+    pub flags: u16,
+    pub seq: u32,
+    pub pid: u32,
+}
+
+/// Mirrors the kernel's `struct ifinfomsg`, the fixed header for all `RTM_*LINK` messages.
+#[derive(Debug, Default, Clone, Copy)]
+#[repr(C)]
+pub(crate) struct IfInfoMsg {
+    pub family: u8,
+    pub pad: u8,
+    pub ty: u16,
+    pub index: i32,
+    pub flags: u32,
+    pub change: u32,
+}
+
+/// Mirrors the kernel's `struct ifaddrmsg`, the fixed header for all `RTM_*ADDR` messages.
+#[derive(Debug, Default, Clone, Copy)]
+#[repr(C)]
+pub(crate) struct IfAddrMsg {
+    pub family: u8,
+    pub prefix_len: u8,
+    pub flags: u8,
+    pub scope: u8,
+    pub index: u32,
+}
+
+/// Mirrors the kernel's `struct ndmsg`, the fixed header for all `RTM_*NEIGH` messages.
+#[derive(Debug, Default, Clone, Copy)]
+#[repr(C)]
+pub(crate) struct NdMsg {
+    pub family: u8,
+    pub pad1: u8,
+    pub pad2: u16,
+    pub index: i32,
+    pub state: u16,
+    pub flags: u8,
+    pub ty: u8,
+}
+
+/// Mirrors the kernel's `struct rtmsg`, the fixed header for all `RTM_*ROUTE` messages.
+#[derive(Debug, Default, Clone, Copy)]
+#[repr(C)]
+pub(crate) struct RtMsg {
+    pub family: u8,
+    pub dst_len: u8,
+    pub src_len: u8,
+    pub tos: u8,
+    pub table: u8,
+    pub protocol: u8,
+    pub scope: u8,
+    pub ty: u8,
+    pub flags: u32,
+}
+
+/// Mirrors the kernel's `struct fib_rule_hdr`, the fixed header for all `RTM_*RULE` messages.
+#[derive(Debug, Default, Clone, Copy)]
+#[repr(C)]
+pub(crate) struct FibRuleHdr {
+    pub family: u8,
+    pub dst_len: u8,
+    pub src_len: u8,
+    pub tos: u8,
+    pub table: u8,
+    pub res1: u8,
+    pub res2: u8,
+    pub action: u8,
+    pub flags: u32,
+}
+
+/// Mirrors the kernel's `struct tcmsg`, the fixed header for all `RTM_*QDISC` messages.
+#[derive(Debug, Default, Clone, Copy)]
+#[repr(C)]
+pub(crate) struct TcMsg {
+    pub family: u8,
+    pub pad1: u8,
+    pub pad2: u16,
+    pub index: i32,
+    pub handle: u32,
+    pub parent: u32,
+    pub info: u32,
+}
+
+/// A single netlink request under construction, comprised of a fixed family specific header
+/// followed by any number of attributes.
+pub(crate) struct Message {
+    buf: Vec<u8>,
+}
+
+impl Message {
+    /// Create a new request of the given type, with the supplied flags, and the family specific
+    /// fixed header. The `NLM_F_REQUEST` flag is always set.
+    pub fn new<T: Copy>(ty: u16, flags: u16, header: &T) -> Self {
+        let hdr = NlMsgHdr {
+            ty,
+            flags: flags | NLM_F_REQUEST,
+            ..Default::default()
+        };
+
+        let mut msg = Self {
+            buf: Vec::with_capacity(256),
+        };
+        msg.put(as_bytes(&hdr));
+        msg.put(as_bytes(header));
+        msg
+    }
+
+    /// Append an attribute with the supplied raw payload.
+    pub fn attr(&mut self, ty: u16, data: &[u8]) -> &mut Self {
+        let len = (ATTR_HEADER_SIZE + data.len()) as u16;
+        self.buf.extend_from_slice(&len.to_ne_bytes());
+        self.buf.extend_from_slice(&ty.to_ne_bytes());
+        self.put(data);
+        self
+    }
+
+    /// Append an attribute containing a native endian u32.
+    #[inline]
+    pub fn attr_u32(&mut self, ty: u16, value: u32) -> &mut Self {
+        self.attr(ty, &value.to_ne_bytes())
+    }
+
+    /// Append an attribute containing a nul terminated string.
+    pub fn attr_str(&mut self, ty: u16, value: &str) -> &mut Self {
+        let mut data = Vec::with_capacity(value.len() + 1);
+        data.extend_from_slice(value.as_bytes());
+        data.push(b'\0');
+        self.attr(ty, &data)
+    }
+
+    /// Append a nested attribute, containing the attributes appended by the supplied closure.
+    pub fn nested<F>(&mut self, ty: u16, f: F) -> &mut Self
+    where
+        F: FnOnce(&mut Self),
+    {
+        let start = self.buf.len();
+        self.attr(ty, &[]);
+        f(self);
+        let len = (self.buf.len() - start) as u16;
+        self.buf[start..start + 2].copy_from_slice(&len.to_ne_bytes());
+        self
+    }
+
+    /// Return the family specific header and attributes of this message, minus its `nlmsghdr`.
+    #[cfg(test)]
+    pub fn payload(&self) -> &[u8] {
+        &self.buf[HEADER_SIZE..]
+    }
+
+    fn put(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+        self.buf.resize(align(self.buf.len()), 0);
+    }
+
+    fn finish(&mut self, seq: u32) -> &[u8] {
+        let len = self.buf.len() as u32;
+        self.buf[0..4].copy_from_slice(&len.to_ne_bytes());
+        self.buf[8..12].copy_from_slice(&seq.to_ne_bytes());
+        &self.buf
+    }
+
+    /// Return the name of the request type, used to annotate errors.
+    pub fn op(&self) -> &'static str {
+        match u16::from_ne_bytes([self.buf[4], self.buf[5]]) {
+            libc::RTM_NEWLINK => "RTM_NEWLINK",
+            libc::RTM_DELLINK => "RTM_DELLINK",
+            libc::RTM_GETLINK => "RTM_GETLINK",
+            libc::RTM_NEWADDR => "RTM_NEWADDR",
+            libc::RTM_DELADDR => "RTM_DELADDR",
+            libc::RTM_GETADDR => "RTM_GETADDR",
+            libc::RTM_NEWROUTE => "RTM_NEWROUTE",
+            libc::RTM_DELROUTE => "RTM_DELROUTE",
+            libc::RTM_NEWNEIGH => "RTM_NEWNEIGH",
+            libc::RTM_DELNEIGH => "RTM_DELNEIGH",
+            libc::RTM_GETNEIGH => "RTM_GETNEIGH",
+            libc::RTM_NEWRULE => "RTM_NEWRULE",
+            libc::RTM_DELRULE => "RTM_DELRULE",
+            libc::RTM_NEWQDISC => "RTM_NEWQDISC",
+            libc::RTM_DELQDISC => "RTM_DELQDISC",
+            libc::RTM_GETQDISC => "RTM_GETQDISC",
+            _ => "rtnetlink request",
+        }
+    }
+
+    fn is_dump(&self) -> bool {
+        u16::from_ne_bytes([self.buf[6], self.buf[7]]) & NLM_F_DUMP == NLM_F_DUMP
+    }
+}
+
+/// A single netlink message received from the kernel, minus its `nlmsghdr`.
+#[derive(Debug, Clone)]
+pub(crate) struct Response {
+    pub ty: u16,
+    pub payload: Vec<u8>,
+}
+
+impl Response {
+    /// Iterate over the attributes following the family specific fixed header `T`.
+    pub fn attrs<T>(&self) -> Attrs<'_> {
+        let offset = align(mem::size_of::<T>()).min(self.payload.len());
+        Attrs::new(&self.payload[offset..])
+    }
+}
+
+/// An iterator over a set of netlink attributes, yielding the attribute type and its payload.
+pub(crate) struct Attrs<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Attrs<'a> {
+    /// Create an iterator over the attributes contained in the supplied buffer, for instance the
+    /// payload of a nested attribute.
+    #[inline]
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+}
+
+impl<'a> Iterator for Attrs<'a> {
+    type Item = (u16, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buf.len() < ATTR_HEADER_SIZE {
+            return None;
+        }
+
+        let len = u16::from_ne_bytes([self.buf[0], self.buf[1]]) as usize;
+        let ty = u16::from_ne_bytes([self.buf[2], self.buf[3]]) & NLA_TYPE_MASK;
+        if len < ATTR_HEADER_SIZE || len > self.buf.len() {
+            self.buf = &[];
+            return None;
+        }
+
+        let data = &self.buf[ATTR_HEADER_SIZE..len];
+        self.buf = &self.buf[align(len).min(self.buf.len())..];
+        Some((ty, data))
+    }
+}
+
+/// A `NETLINK_ROUTE` socket used to issue requests to, and receive notifications from, the kernel.
+pub(crate) struct Socket {
+    fd: OwnedFd,
+    seq: u32,
+}
+
+impl Socket {
+    /// Open a new `NETLINK_ROUTE` socket, subscribing to the supplied multicast groups. Supply `0`
+    /// for groups if the socket is only used for issuing requests.
+    pub fn route(groups: u32) -> Result<Self> {
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+                libc::NETLINK_ROUTE,
+            )
+        };
+        if fd < 0 {
+            return Err(Error::errno());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let mut addr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        addr.nl_groups = groups;
+        let ret = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+        if ret < 0 {
+            return Err(Error::errno());
+        }
+
+        Ok(Self { fd, seq: 0 })
+    }
+
+    /// Send the supplied request and wait for its completion. For dump requests every message
+    /// returned by the kernel is collected, otherwise the request is acknowledged and any reply
+    /// preceding the acknowledgement is returned.
+    ///
+    /// # Errors
+    /// Any error reported by the kernel is returned as the equivalent [Error::Unix], without
+    /// any context as to the device the request targeted.
+    pub fn request(&mut self, msg: &mut Message) -> Result<Vec<Response>> {
+        self.seq = self.seq.wrapping_add(1);
+        let seq = self.seq;
+        let dump = msg.is_dump();
+        if !dump {
+            let flags = u16::from_ne_bytes([msg.buf[6], msg.buf[7]]) | NLM_F_ACK;
+            msg.buf[6..8].copy_from_slice(&flags.to_ne_bytes());
+        }
+
+        let data = msg.finish(seq);
+        let ret = unsafe {
+            libc::send(
+                self.fd.as_raw_fd(),
+                data.as_ptr() as *const libc::c_void,
+                data.len(),
+                0,
+            )
+        };
+        if ret < 0 {
+            return Err(Error::errno());
+        }
+
+        let mut buf = vec![0u8; RECV_BUFFER_SIZE];
+        let mut responses = Vec::new();
+        loop {
+            let read = self.recv(&mut buf).map_err(Error::from)?;
+            for (hdr, payload) in Messages::new(&buf[..read]) {
+                if hdr.seq != seq {
+                    continue;
+                }
+
+                match hdr.ty {
+                    NLMSG_DONE => return Ok(responses),
+                    NLMSG_ERROR => {
+                        let code = from_bytes::<i32>(payload).unwrap_or(0);
+                        if code == 0 {
+                            return Ok(responses);
+                        }
+                        return Err(Error::from(nix::errno::Errno::from_i32(-code)));
+                    }
+                    ty => responses.push(Response {
+                        ty,
+                        payload: payload.to_vec(),
+                    }),
+                }
+            }
+        }
+    }
+
+    /// Receive a single datagram, potentially containing multiple messages, from the kernel. This
+    /// is used directly when monitoring multicast groups, see [`Messages`] for parsing the result.
+    ///
+    /// In non-blocking mode this can and will return [`WouldBlock`][std::io::ErrorKind::WouldBlock].
+    pub fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = unsafe {
+            libc::recv(
+                self.fd.as_raw_fd(),
+                buf.as_mut_ptr() as *mut libc::c_void,
+                buf.len(),
+                0,
+            )
+        };
+        if read < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(read as usize)
+    }
+
+    /// Either enable or disable non-blocking mode on the underlying socket.
+    pub fn set_non_blocking(&self, on: bool) -> Result<()> {
+        let flags = fcntl(self.fd.as_raw_fd(), FcntlArg::F_GETFL)?;
+        let mut flags = OFlag::from_bits_truncate(flags);
+        flags.set(OFlag::O_NONBLOCK, on);
+        fcntl(self.fd.as_raw_fd(), FcntlArg::F_SETFL(flags))?;
+        Ok(())
+    }
+}
+
+impl AsRawFd for Socket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+/// An iterator over the individual netlink messages contained in a single datagram.
+pub(crate) struct Messages<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Messages<'a> {
+    /// Create an iterator over the messages contained in the supplied datagram.
+    #[inline]
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+}
+
+impl<'a> Iterator for Messages<'a> {
+    type Item = (NlMsgHdr, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let hdr: NlMsgHdr = from_bytes(self.buf)?;
+        let len = hdr.len as usize;
+        if len < HEADER_SIZE || len > self.buf.len() {
+            self.buf = &[];
+            return None;
+        }
+
+        let payload = &self.buf[HEADER_SIZE..len];
+        self.buf = &self.buf[align(len).min(self.buf.len())..];
+        Some((hdr, payload))
+    }
+}
+
+/// View the supplied plain old data struct as raw bytes.
+pub(crate) fn as_bytes<T: Copy>(value: &T) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(value as *const T as *const u8, mem::size_of::<T>()) }
+}
+
+/// Read a plain old data struct from the start of the supplied buffer, returning `None` if the
+/// buffer is too short.
+pub(crate) fn from_bytes<T: Copy>(buf: &[u8]) -> Option<T> {
+    if buf.len() < mem::size_of::<T>() {
+        return None;
+    }
+
+    let mut value = MaybeUninit::<T>::uninit();
+    unsafe {
+        std::ptr::copy_nonoverlapping(
+            buf.as_ptr(),
+            value.as_mut_ptr() as *mut u8,
+            mem::size_of::<T>(),
+        );
+        Some(value.assume_init())
+    }
+}
+
+/// Parse a nul terminated string attribute.
+pub(crate) fn parse_str(data: &[u8]) -> String {
+    data.iter()
+        .take_while(|char| **char != b'\0')
+        .map(|char| *char as char)
+        .collect()
+}
+
+/// Parse an address attribute of the supplied address family.
+pub(crate) fn parse_addr(family: u8, data: &[u8]) -> Option<IpAddr> {
+    match family as i32 {
+        libc::AF_INET => from_bytes::<[u8; 4]>(data).map(|o| IpAddr::V4(Ipv4Addr::from(o))),
+        libc::AF_INET6 => from_bytes::<[u8; 16]>(data).map(|o| IpAddr::V6(Ipv6Addr::from(o))),
+        _ => None,
+    }
+}
+
+/// Encode the supplied address, returning its address family and raw octets.
+pub(crate) fn encode_addr(addr: &IpAddr) -> (u8, Vec<u8>) {
+    match addr {
+        IpAddr::V4(addr) => (libc::AF_INET as u8, addr.octets().to_vec()),
+        IpAddr::V6(addr) => (libc::AF_INET6 as u8, addr.octets().to_vec()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_layout() {
+        let mut msg = Message::new(libc::RTM_GETLINK, 0, &IfInfoMsg::default());
+        msg.attr_str(libc::IFLA_IFNAME, "rip0");
+        let data = msg.finish(7).to_vec();
+
+        let hdr: NlMsgHdr = from_bytes(&data).unwrap();
+        assert_eq!(data.len(), hdr.len as usize);
+        assert_eq!(libc::RTM_GETLINK, hdr.ty);
+        assert_eq!(NLM_F_REQUEST, hdr.flags);
+        assert_eq!(7, hdr.seq);
+
+        let response = Response {
+            ty: hdr.ty,
+            payload: data[HEADER_SIZE..].to_vec(),
+        };
+        let attrs: Vec<_> = response.attrs::<IfInfoMsg>().collect();
+        assert_eq!(vec![(libc::IFLA_IFNAME, &b"rip0\0"[..])], attrs);
+    }
+
+    #[test]
+    fn test_nested_attrs() {
+        let mut msg = Message::new(libc::RTM_NEWLINK, 0, &IfInfoMsg::default());
+        msg.nested(libc::IFLA_LINKINFO, |msg| {
+            msg.attr_str(libc::IFLA_INFO_KIND, "bridge");
+        });
+        msg.attr_u32(libc::IFLA_MTU, 1400);
+
+        let response = Response {
+            ty: libc::RTM_NEWLINK,
+            payload: msg.payload().to_vec(),
+        };
+        let attrs: Vec<_> = response.attrs::<IfInfoMsg>().collect();
+        assert_eq!(2, attrs.len());
+        assert_eq!(libc::IFLA_LINKINFO, attrs[0].0);
+        assert_eq!(
+            vec![(libc::IFLA_INFO_KIND, &b"bridge\0"[..])],
+            Attrs::new(attrs[0].1).collect::<Vec<_>>()
+        );
+        assert_eq!((libc::IFLA_MTU, &1400u32.to_ne_bytes()[..]), attrs[1]);
+    }
+
+    #[test]
+    fn test_truncated_attrs() {
+        let buf = [0xff, 0x00, 0x01, 0x00, 0xaa];
+        assert_eq!(0, Attrs::new(&buf).count());
+        assert_eq!(0, Attrs::new(&buf[..2]).count());
+    }
+
+    #[test]
+    fn test_parse_addr() {
+        assert_eq!(
+            Some(IpAddr::V4(Ipv4Addr::new(203, 0, 113, 2))),
+            parse_addr(libc::AF_INET as u8, &[203, 0, 113, 2])
+        );
+        assert_eq!(
+            Some(IpAddr::V6(Ipv6Addr::LOCALHOST)),
+            parse_addr(libc::AF_INET6 as u8, &Ipv6Addr::LOCALHOST.octets())
+        );
+        assert_eq!(None, parse_addr(libc::AF_INET6 as u8, &[203, 0, 113, 2]));
+        assert_eq!(None, parse_addr(libc::AF_PACKET as u8, &[203, 0, 113, 2]));
+    }
+
+    #[test]
+    fn test_encode_addr() {
+        let addr = IpAddr::V4(Ipv4Addr::new(203, 0, 113, 2));
+        let (family, data) = encode_addr(&addr);
+        assert_eq!(Some(addr), parse_addr(family, &data));
+
+        let addr = IpAddr::V6(Ipv6Addr::LOCALHOST);
+        let (family, data) = encode_addr(&addr);
+        assert_eq!(libc::AF_INET6 as u8, family);
+        assert_eq!(Some(addr), parse_addr(family, &data));
+    }
+}
@@ -0,0 +1,116 @@
+// (c) Copyright 2021 Christian Saide
+// SPDX-License-Identifier: MIT
+
+use super::{Error, Result};
+
+use nix::libc;
+use nix::mount::{mount, MsFlags};
+use nix::sched::{unshare, CloneFlags};
+
+use std::fs::File;
+use std::os::unix::io::{AsRawFd, OwnedFd, RawFd};
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+
+const NAMED_ROOT: &str = "/var/run/netns";
+
+/// A handle to a network namespace, used to move devices between namespaces or to create devices
+/// directly within a namespace.
+///
+/// The namespace is kept alive for as long as this handle, or any of its clones, exist.
+#[derive(Debug, Clone)]
+pub struct NetNs(Arc<OwnedFd>);
+
+impl NetNs {
+    /// Reference a namespace via an already open namespace file descriptor, for instance one
+    /// received from a container runtime.
+    pub fn from_fd(fd: OwnedFd) -> Self {
+        Self(Arc::new(fd))
+    }
+
+    /// Reference a namespace via the supplied path, for instance `/proc/<pid>/ns/net`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        File::open(path)
+            .map(|file| Self::from_fd(OwnedFd::from(file)))
+            .map_err(|source| Error::FS {
+                path: path.display().to_string(),
+                source,
+            })
+    }
+
+    /// Reference a named namespace, as created by `ip netns add <name>`.
+    pub fn from_name(name: &str) -> Result<Self> {
+        Self::from_path(Path::new(NAMED_ROOT).join(name))
+    }
+
+    /// Reference the namespace of the network namespace of the process with the supplied pid.
+    pub fn from_pid(pid: u32) -> Result<Self> {
+        Self::from_path(format!("/proc/{}/ns/net", pid))
+    }
+
+    /// Reference the namespace of the calling thread, which is useful for later moving a device
+    /// back to where it was created.
+    pub fn current() -> Result<Self> {
+        Self::from_path("/proc/thread-self/ns/net")
+    }
+
+    /// Execute the supplied closure on a helper thread which has entered this namespace, so that
+    /// any sockets or devices it opens are bound to it. The calling thread is left untouched.
+    pub(crate) fn run<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce() -> Result<T> + Send,
+        T: Send,
+    {
+        thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    let ret = unsafe { libc::setns(self.as_raw_fd(), libc::CLONE_NEWNET) };
+                    if ret < 0 {
+                        return Err(Error::errno());
+                    }
+                    f()
+                })
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+        })
+    }
+}
+
+impl NetNs {
+    /// Execute the supplied closure as per [`NetNs::run()`], with the sysfs of this namespace
+    /// mounted at `/sys` within a private mount namespace. Unlike procfs, sysfs reflects the
+    /// namespace it was mounted from rather than that of the calling thread.
+    pub(crate) fn run_with_sysfs<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce() -> Result<T> + Send,
+        T: Send,
+    {
+        self.run(|| {
+            unshare(CloneFlags::CLONE_NEWNS)?;
+            // Keep the new mount from propagating back to the mount namespace of the process.
+            mount(
+                None::<&str>,
+                "/",
+                None::<&str>,
+                MsFlags::MS_REC | MsFlags::MS_PRIVATE,
+                None::<&str>,
+            )?;
+            mount(
+                Some("sysfs"),
+                "/sys",
+                Some("sysfs"),
+                MsFlags::empty(),
+                None::<&str>,
+            )?;
+            f()
+        })
+    }
+}
+
+impl AsRawFd for NetNs {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
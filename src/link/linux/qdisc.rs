@@ -0,0 +1,226 @@
+// (c) Copyright 2021 Christian Saide
+// SPDX-License-Identifier: MIT
+
+use super::netlink::{self, parse_str, Attrs, Message, TcMsg};
+use super::{Error, Link, Result};
+
+use nix::libc;
+
+use std::time::Duration;
+
+const TC_H_ROOT: u32 = 0xffff_ffff;
+const TCA_FQ_CODEL_TARGET: u16 = 1;
+const TCA_FQ_CODEL_LIMIT: u16 = 2;
+const TCA_FQ_CODEL_INTERVAL: u16 = 3;
+const TCA_FQ_CODEL_ECN: u16 = 4;
+const TCA_FQ_CODEL_FLOWS: u16 = 5;
+const TCA_FQ_CODEL_QUANTUM: u16 = 6;
+
+/// The parameters of the `fq_codel` queueing discipline. Any parameter left as `None` retains
+/// the kernel default when applied.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FqCodel {
+    /// The hard limit on the number of queued packets.
+    pub limit: Option<u32>,
+    /// The number of flows packets are hashed into.
+    pub flows: Option<u32>,
+    /// The acceptable minimum standing queue delay.
+    pub target: Option<Duration>,
+    /// The width of the moving window used to compute the minimum queue delay.
+    pub interval: Option<Duration>,
+    /// The number of bytes dequeued from a flow per round.
+    pub quantum: Option<u32>,
+    /// Whether to mark packets with ECN rather than dropping them.
+    pub ecn: Option<bool>,
+}
+
+impl FqCodel {
+    fn encode(&self, msg: &mut Message) {
+        msg.nested(libc::TCA_OPTIONS, |msg| {
+            if let Some(target) = self.target {
+                msg.attr_u32(TCA_FQ_CODEL_TARGET, micros(target));
+            }
+            if let Some(limit) = self.limit {
+                msg.attr_u32(TCA_FQ_CODEL_LIMIT, limit);
+            }
+            if let Some(interval) = self.interval {
+                msg.attr_u32(TCA_FQ_CODEL_INTERVAL, micros(interval));
+            }
+            if let Some(ecn) = self.ecn {
+                msg.attr_u32(TCA_FQ_CODEL_ECN, ecn as u32);
+            }
+            if let Some(flows) = self.flows {
+                msg.attr_u32(TCA_FQ_CODEL_FLOWS, flows);
+            }
+            if let Some(quantum) = self.quantum {
+                msg.attr_u32(TCA_FQ_CODEL_QUANTUM, quantum);
+            }
+        });
+    }
+
+    fn parse(data: &[u8]) -> Self {
+        let mut params = Self::default();
+        for (ty, data) in Attrs::new(data) {
+            let value: Option<u32> = netlink::from_bytes(data);
+            match ty {
+                TCA_FQ_CODEL_TARGET => params.target = value.map(from_micros),
+                TCA_FQ_CODEL_LIMIT => params.limit = value,
+                TCA_FQ_CODEL_INTERVAL => params.interval = value.map(from_micros),
+                TCA_FQ_CODEL_ECN => params.ecn = value.map(|ecn| ecn != 0),
+                TCA_FQ_CODEL_FLOWS => params.flows = value,
+                TCA_FQ_CODEL_QUANTUM => params.quantum = value,
+                _ => {}
+            }
+        }
+        params
+    }
+}
+
+/// The root queueing discipline of a link, determining how packets transmitted by the kernel are
+/// queued prior to being read off a [Queue][crate::Queue].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Qdisc {
+    /// Transmit packets without queueing, which minimizes latency.
+    NoQueue,
+    /// The legacy three band priority queue.
+    PfifoFast,
+    /// A single FIFO queue, bounded to the supplied number of packets, or the transmit queue
+    /// length of the link if `None`.
+    Pfifo(Option<u32>),
+    /// Fair queueing with controlled delay.
+    FqCodel(FqCodel),
+    /// A classful discipline attaching a child discipline to each transmit queue, the default for
+    /// multi-queue devices.
+    Mq,
+    /// Any other discipline, identified by its kernel name. Setting such a discipline uses the
+    /// kernel defaults for all of its parameters.
+    Other(String),
+}
+
+impl Qdisc {
+    fn kind(&self) -> &str {
+        match self {
+            Self::NoQueue => "noqueue",
+            Self::PfifoFast => "pfifo_fast",
+            Self::Pfifo(_) => "pfifo",
+            Self::FqCodel(_) => "fq_codel",
+            Self::Mq => "mq",
+            Self::Other(kind) => kind.as_str(),
+        }
+    }
+
+    fn encode(&self, msg: &mut Message) {
+        msg.attr_str(libc::TCA_KIND, self.kind());
+        match self {
+            Self::Pfifo(Some(limit)) => {
+                msg.attr_u32(libc::TCA_OPTIONS, *limit);
+            }
+            Self::FqCodel(params) => params.encode(msg),
+            _ => {}
+        }
+    }
+
+    fn parse(resp: &netlink::Response, index: i32) -> Option<Self> {
+        let hdr: TcMsg = netlink::from_bytes(&resp.payload)?;
+        if hdr.index != index || hdr.parent != TC_H_ROOT {
+            return None;
+        }
+
+        let mut kind = None;
+        let mut options: &[u8] = &[];
+        for (ty, data) in resp.attrs::<TcMsg>() {
+            match ty {
+                libc::TCA_KIND => kind = Some(parse_str(data)),
+                libc::TCA_OPTIONS => options = data,
+                _ => {}
+            }
+        }
+
+        Some(match kind?.as_str() {
+            "noqueue" => Self::NoQueue,
+            "pfifo_fast" => Self::PfifoFast,
+            "pfifo" => Self::Pfifo(netlink::from_bytes(options)),
+            "fq_codel" => Self::FqCodel(FqCodel::parse(options)),
+            "mq" => Self::Mq,
+            kind => Self::Other(String::from(kind)),
+        })
+    }
+}
+
+fn micros(duration: Duration) -> u32 {
+    duration.as_micros().min(u32::MAX as u128) as u32
+}
+
+fn from_micros(micros: u32) -> Duration {
+    Duration::from_micros(micros as u64)
+}
+
+fn header(index: i32) -> TcMsg {
+    TcMsg {
+        index,
+        parent: TC_H_ROOT,
+        ..Default::default()
+    }
+}
+
+pub(super) fn get(link: &Link) -> Result<Qdisc> {
+    let index = link.index()?;
+    let mut msg = Message::new(libc::RTM_GETQDISC, netlink::NLM_F_DUMP, &TcMsg::default());
+    link.request(&mut msg)?
+        .iter()
+        .filter(|resp| resp.ty == libc::RTM_NEWQDISC)
+        .find_map(|resp| Qdisc::parse(resp, index))
+        .ok_or_else(|| Error::from(nix::errno::Errno::ENOENT))
+}
+
+pub(super) fn set(link: &Link, qdisc: &Qdisc) -> Result<()> {
+    let mut msg = Message::new(
+        libc::RTM_NEWQDISC,
+        netlink::NLM_F_CREATE | netlink::NLM_F_REPLACE,
+        &header(link.index()?),
+    );
+    qdisc.encode(&mut msg);
+    link.request(&mut msg).map(|_| ())
+}
+
+pub(super) fn reset(link: &Link) -> Result<()> {
+    let mut msg = Message::new(libc::RTM_DELQDISC, 0, &header(link.index()?));
+    link.request(&mut msg).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(qdisc: &Qdisc) -> Option<Qdisc> {
+        let mut msg = Message::new(libc::RTM_NEWQDISC, 0, &header(4));
+        qdisc.encode(&mut msg);
+        let resp = netlink::Response {
+            ty: libc::RTM_NEWQDISC,
+            payload: msg.payload().to_vec(),
+        };
+        Qdisc::parse(&resp, 4)
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let fq_codel = Qdisc::FqCodel(FqCodel {
+            limit: Some(1024),
+            target: Some(Duration::from_millis(5)),
+            interval: Some(Duration::from_millis(100)),
+            ecn: Some(true),
+            ..Default::default()
+        });
+
+        for qdisc in [
+            Qdisc::NoQueue,
+            Qdisc::PfifoFast,
+            Qdisc::Pfifo(Some(100)),
+            fq_codel,
+            Qdisc::Mq,
+            Qdisc::Other(String::from("sfq")),
+        ] {
+            assert_eq!(Some(qdisc.clone()), roundtrip(&qdisc));
+        }
+    }
+}
@@ -0,0 +1,252 @@
+// (c) Copyright 2021 Christian Saide
+// SPDX-License-Identifier: MIT
+
+use super::netlink::{self, encode_addr, FibRuleHdr, Message, RtMsg};
+use super::{IpNet, Link, Result};
+
+use nix::libc;
+
+use std::net::IpAddr;
+
+const RT_TABLE_MAIN: u32 = libc::RT_TABLE_MAIN as u32;
+const FR_ACT_TO_TBL: u8 = 1;
+const FRA_DST: u16 = 1;
+const FRA_SRC: u16 = 2;
+const FRA_IIFNAME: u16 = 3;
+const FRA_PRIORITY: u16 = 6;
+const FRA_TABLE: u16 = 15;
+const FRA_OIFNAME: u16 = 17;
+
+/// A route directing traffic for a destination prefix out of a link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Route {
+    /// The destination prefix of the route.
+    pub destination: IpNet,
+    /// The next hop to forward traffic via, or `None` if the destination is directly reachable
+    /// on the link.
+    pub gateway: Option<IpAddr>,
+    /// The priority of the route, lower values are preferred.
+    pub metric: Option<u32>,
+    /// The routing table to add the route to, or `None` for the main table.
+    pub table: Option<u32>,
+}
+
+impl Route {
+    /// Create a new route for the supplied destination prefix, directly reachable on the link via
+    /// the main routing table.
+    pub fn new(destination: IpNet) -> Self {
+        Self {
+            destination,
+            gateway: None,
+            metric: None,
+            table: None,
+        }
+    }
+
+    fn message(&self, ty: u16, flags: u16, index: i32) -> Message {
+        let (family, dst) = encode_addr(&self.destination.addr());
+        let table = self.table.unwrap_or(RT_TABLE_MAIN);
+        let mut hdr = RtMsg {
+            family,
+            dst_len: self.destination.prefix_len(),
+            table: short_table(table),
+            ..Default::default()
+        };
+        // Mirror iproute2, deletions match on any protocol and scope.
+        if ty == libc::RTM_DELROUTE {
+            hdr.scope = libc::RT_SCOPE_NOWHERE;
+        } else {
+            hdr.protocol = libc::RTPROT_STATIC;
+            hdr.ty = libc::RTN_UNICAST;
+            hdr.scope = match self.gateway {
+                Some(_) => libc::RT_SCOPE_UNIVERSE,
+                None => libc::RT_SCOPE_LINK,
+            };
+        }
+
+        let mut msg = Message::new(ty, flags, &hdr);
+        if hdr.dst_len > 0 {
+            msg.attr(libc::RTA_DST, &dst);
+        }
+        msg.attr_u32(libc::RTA_OIF, index as u32);
+        if let Some(gateway) = self.gateway {
+            msg.attr(libc::RTA_GATEWAY, &encode_addr(&gateway).1);
+        }
+        if let Some(metric) = self.metric {
+            msg.attr_u32(libc::RTA_PRIORITY, metric);
+        }
+        msg.attr_u32(libc::RTA_TABLE, table);
+        msg
+    }
+}
+
+/// A policy routing rule selecting the routing table to look traffic up in.
+///
+/// Rules are not scoped to a link, the link they are managed through only determines the
+/// namespace they reside in, and the interface name matched by [`Rule::iif`] and [`Rule::oif`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rule {
+    /// The priority of the rule, lower values are evaluated first. If `None` the kernel assigns
+    /// a priority just below the lowest existing rule.
+    pub priority: Option<u32>,
+    /// Match traffic originating from the supplied prefix.
+    pub from: Option<IpNet>,
+    /// Match traffic destined to the supplied prefix.
+    pub to: Option<IpNet>,
+    /// Match traffic received on the link.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub iif: bool,
+    /// Match traffic sent out of the link.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub oif: bool,
+    /// The routing table to look matching traffic up in.
+    pub table: u32,
+}
+
+impl Rule {
+    /// Create a new rule looking all traffic up in the supplied routing table. Rules without
+    /// either a [`Rule::from`] or [`Rule::to`] selector apply to IPv4 traffic.
+    pub fn new(table: u32) -> Self {
+        Self {
+            priority: None,
+            from: None,
+            to: None,
+            iif: false,
+            oif: false,
+            table,
+        }
+    }
+
+    fn message(&self, ty: u16, flags: u16, name: &str) -> Message {
+        let family = self
+            .from
+            .or(self.to)
+            .map(|net| encode_addr(&net.addr()).0)
+            .unwrap_or(libc::AF_INET as u8);
+        let hdr = FibRuleHdr {
+            family,
+            dst_len: self.to.map(|net| net.prefix_len()).unwrap_or_default(),
+            src_len: self.from.map(|net| net.prefix_len()).unwrap_or_default(),
+            table: short_table(self.table),
+            action: FR_ACT_TO_TBL,
+            ..Default::default()
+        };
+
+        let mut msg = Message::new(ty, flags, &hdr);
+        if let Some(from) = self.from {
+            msg.attr(FRA_SRC, &encode_addr(&from.addr()).1);
+        }
+        if let Some(to) = self.to {
+            msg.attr(FRA_DST, &encode_addr(&to.addr()).1);
+        }
+        if self.iif {
+            msg.attr_str(FRA_IIFNAME, name);
+        }
+        if self.oif {
+            msg.attr_str(FRA_OIFNAME, name);
+        }
+        if let Some(priority) = self.priority {
+            msg.attr_u32(FRA_PRIORITY, priority);
+        }
+        msg.attr_u32(FRA_TABLE, self.table);
+        msg
+    }
+}
+
+/// Tables which don't fit the legacy 8-bit header field are only conveyed via attribute.
+fn short_table(table: u32) -> u8 {
+    if table < 256 {
+        table as u8
+    } else {
+        libc::RT_TABLE_UNSPEC
+    }
+}
+
+pub(super) fn add_route(link: &Link, route: &Route) -> Result<()> {
+    let mut msg = route.message(
+        libc::RTM_NEWROUTE,
+        netlink::NLM_F_CREATE | netlink::NLM_F_EXCL,
+        link.index()?,
+    );
+    link.request(&mut msg).map(|_| ())
+}
+
+pub(super) fn delete_route(link: &Link, route: &Route) -> Result<()> {
+    let mut msg = route.message(libc::RTM_DELROUTE, 0, link.index()?);
+    link.request(&mut msg).map(|_| ())
+}
+
+pub(super) fn add_rule(link: &Link, rule: &Rule) -> Result<()> {
+    let mut msg = rule.message(
+        libc::RTM_NEWRULE,
+        netlink::NLM_F_CREATE | netlink::NLM_F_EXCL,
+        link.name(),
+    );
+    link.request(&mut msg).map(|_| ())
+}
+
+pub(super) fn delete_rule(link: &Link, rule: &Rule) -> Result<()> {
+    let mut msg = rule.message(libc::RTM_DELRULE, 0, link.name());
+    link.request(&mut msg).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route_message() {
+        let route = Route {
+            gateway: Some("10.0.0.254".parse().unwrap()),
+            table: Some(1000),
+            ..Route::new("10.1.0.0/16".parse().unwrap())
+        };
+        let msg = route.message(libc::RTM_NEWROUTE, 0, 5);
+        let resp = netlink::Response {
+            ty: libc::RTM_NEWROUTE,
+            payload: msg.payload().to_vec(),
+        };
+
+        let hdr: RtMsg = netlink::from_bytes(&resp.payload).unwrap();
+        assert_eq!(libc::AF_INET as u8, hdr.family);
+        assert_eq!(16, hdr.dst_len);
+        assert_eq!(libc::RT_TABLE_UNSPEC, hdr.table);
+        assert_eq!(libc::RT_SCOPE_UNIVERSE, hdr.scope);
+
+        let attrs: Vec<_> = resp.attrs::<RtMsg>().map(|(ty, _)| ty).collect();
+        assert_eq!(
+            vec![
+                libc::RTA_DST,
+                libc::RTA_OIF,
+                libc::RTA_GATEWAY,
+                libc::RTA_TABLE
+            ],
+            attrs
+        );
+    }
+
+    #[test]
+    fn test_rule_message() {
+        let rule = Rule {
+            from: Some("fd00::/64".parse().unwrap()),
+            iif: true,
+            ..Rule::new(100)
+        };
+        let msg = rule.message(libc::RTM_NEWRULE, 0, "rip0");
+        let resp = netlink::Response {
+            ty: libc::RTM_NEWRULE,
+            payload: msg.payload().to_vec(),
+        };
+
+        let hdr: FibRuleHdr = netlink::from_bytes(&resp.payload).unwrap();
+        assert_eq!(libc::AF_INET6 as u8, hdr.family);
+        assert_eq!(64, hdr.src_len);
+        assert_eq!(100, hdr.table);
+
+        let attrs: Vec<_> = resp.attrs::<FibRuleHdr>().collect();
+        assert_eq!(3, attrs.len());
+        assert_eq!((FRA_IIFNAME, &b"rip0\0"[..]), attrs[1]);
+    }
+}
@@ -0,0 +1,255 @@
+// (c) Copyright 2021 Christian Saide
+// SPDX-License-Identifier: MIT
+
+use super::netlink::{self, IfInfoMsg};
+use super::{Error, Link, Result, SYSFS_ROOT};
+
+use nix::libc;
+
+use std::fs;
+use std::path::Path;
+
+const NUM_COUNTERS: usize = 10;
+
+/// The general purpose 64-bit counters the kernel maintains for every network interface.
+///
+/// Use [`LinkStats::delta()`] between two samples to compute throughput and error rates.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LinkStats {
+    /// Total packets received.
+    pub rx_packets: u64,
+    /// Total packets transmitted.
+    pub tx_packets: u64,
+    /// Total bytes received.
+    pub rx_bytes: u64,
+    /// Total bytes transmitted.
+    pub tx_bytes: u64,
+    /// Bad packets received.
+    pub rx_errors: u64,
+    /// Packet transmit problems.
+    pub tx_errors: u64,
+    /// Packets received but dropped, for instance due to lack of buffer space.
+    pub rx_dropped: u64,
+    /// Packets dropped on transmit, for instance when no reader is attached to a queue.
+    pub tx_dropped: u64,
+    /// Multicast packets received.
+    pub multicast: u64,
+    /// Collisions encountered during transmit.
+    pub collisions: u64,
+}
+
+impl LinkStats {
+    /// Compute the change in every counter between the supplied `earlier` sample and this one.
+    /// Counters which went backwards, for instance because the device was recreated in between
+    /// samples, are reported as `0`.
+    pub fn delta(&self, earlier: &LinkStats) -> LinkStats {
+        LinkStats {
+            rx_packets: self.rx_packets.saturating_sub(earlier.rx_packets),
+            tx_packets: self.tx_packets.saturating_sub(earlier.tx_packets),
+            rx_bytes: self.rx_bytes.saturating_sub(earlier.rx_bytes),
+            tx_bytes: self.tx_bytes.saturating_sub(earlier.tx_bytes),
+            rx_errors: self.rx_errors.saturating_sub(earlier.rx_errors),
+            tx_errors: self.tx_errors.saturating_sub(earlier.tx_errors),
+            rx_dropped: self.rx_dropped.saturating_sub(earlier.rx_dropped),
+            tx_dropped: self.tx_dropped.saturating_sub(earlier.tx_dropped),
+            multicast: self.multicast.saturating_sub(earlier.multicast),
+            collisions: self.collisions.saturating_sub(earlier.collisions),
+        }
+    }
+
+    /// Parse the leading counters of a kernel `struct rtnl_link_stats64`.
+    fn from_stats64(data: &[u8]) -> Option<Self> {
+        let raw: [u64; NUM_COUNTERS] = netlink::from_bytes(data)?;
+        Some(Self {
+            rx_packets: raw[0],
+            tx_packets: raw[1],
+            rx_bytes: raw[2],
+            tx_bytes: raw[3],
+            rx_errors: raw[4],
+            tx_errors: raw[5],
+            rx_dropped: raw[6],
+            tx_dropped: raw[7],
+            multicast: raw[8],
+            collisions: raw[9],
+        })
+    }
+
+    /// Read the counters exposed under `<root>/<name>/statistics`.
+    fn from_sysfs(root: &Path, name: &str) -> Result<Self> {
+        let dir = root.join(name).join("statistics");
+        let read = |counter: &str| -> Result<u64> {
+            let path = dir.join(counter);
+            let content = fs::read_to_string(&path).map_err(|source| Error::FS {
+                path: path.display().to_string(),
+                source,
+            })?;
+            content.trim().parse().map_err(|_| Error::FS {
+                path: path.display().to_string(),
+                source: std::io::Error::from(std::io::ErrorKind::InvalidData),
+            })
+        };
+
+        Ok(Self {
+            rx_packets: read("rx_packets")?,
+            tx_packets: read("tx_packets")?,
+            rx_bytes: read("rx_bytes")?,
+            tx_bytes: read("tx_bytes")?,
+            rx_errors: read("rx_errors")?,
+            tx_errors: read("tx_errors")?,
+            rx_dropped: read("rx_dropped")?,
+            tx_dropped: read("tx_dropped")?,
+            multicast: read("multicast")?,
+            collisions: read("collisions")?,
+        })
+    }
+}
+
+/// Retrieve the statistics for the named link via rtnetlink, falling back to sysfs when the
+/// kernel does not report `IFLA_STATS64`.
+pub(super) fn get(link: &Link) -> Result<LinkStats> {
+    match from_netlink(link) {
+        Ok(stats) => Ok(stats),
+        Err(err) => from_sysfs(link, Path::new(SYSFS_ROOT)).map_err(|_| err),
+    }
+}
+
+/// Read the statistics for the link via sysfs, as mounted from the namespace of the link, so
+/// that a device of the same name in the namespace of the caller is never reported instead.
+fn from_sysfs(link: &Link, root: &Path) -> Result<LinkStats> {
+    match link.netns() {
+        Some(netns) => netns.run_with_sysfs(|| LinkStats::from_sysfs(root, link.name())),
+        None => LinkStats::from_sysfs(root, link.name()),
+    }
+}
+
+fn from_netlink(link: &Link) -> Result<LinkStats> {
+    link.info()?
+        .iter()
+        .filter(|resp| resp.ty == libc::RTM_NEWLINK)
+        .find_map(|resp| {
+            resp.attrs::<IfInfoMsg>()
+                .find(|(ty, _)| *ty == libc::IFLA_STATS64)
+                .and_then(|(_, data)| LinkStats::from_stats64(data))
+        })
+        .ok_or_else(|| Error::from(nix::errno::Errno::ENODATA))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::NetNs;
+
+    #[test]
+    fn test_delta() {
+        let earlier = LinkStats {
+            rx_packets: 10,
+            tx_packets: 5,
+            rx_bytes: 1000,
+            tx_bytes: 500,
+            ..Default::default()
+        };
+        let later = LinkStats {
+            rx_packets: 15,
+            tx_packets: 5,
+            rx_bytes: 1600,
+            tx_bytes: 400,
+            rx_dropped: 1,
+            ..Default::default()
+        };
+
+        let delta = later.delta(&earlier);
+        assert_eq!(5, delta.rx_packets);
+        assert_eq!(0, delta.tx_packets);
+        assert_eq!(600, delta.rx_bytes);
+        assert_eq!(0, delta.tx_bytes);
+        assert_eq!(1, delta.rx_dropped);
+    }
+
+    #[test]
+    fn test_from_stats64() {
+        let mut data = Vec::new();
+        (1..=24u64).for_each(|counter| data.extend_from_slice(&counter.to_ne_bytes()));
+
+        let stats = LinkStats::from_stats64(&data);
+        assert!(stats.is_some());
+        let stats = stats.unwrap();
+        assert_eq!(1, stats.rx_packets);
+        assert_eq!(4, stats.tx_bytes);
+        assert_eq!(10, stats.collisions);
+
+        assert!(LinkStats::from_stats64(&data[..16]).is_none());
+    }
+
+    #[test]
+    fn test_from_sysfs() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("rip0/statistics");
+        fs::create_dir_all(&dir).unwrap();
+        let counters = [
+            "rx_packets",
+            "tx_packets",
+            "rx_bytes",
+            "tx_bytes",
+            "rx_errors",
+            "tx_errors",
+            "rx_dropped",
+            "tx_dropped",
+            "multicast",
+            "collisions",
+        ];
+        for (value, counter) in counters.iter().enumerate() {
+            fs::write(dir.join(counter), format!("{}\n", value + 1)).unwrap();
+        }
+
+        let stats = LinkStats::from_sysfs(root.path(), "rip0").unwrap();
+        assert_eq!(1, stats.rx_packets);
+        assert_eq!(4, stats.tx_bytes);
+        assert_eq!(10, stats.collisions);
+
+        assert!(matches!(
+            LinkStats::from_sysfs(root.path(), "rip1"),
+            Err(Error::FS { .. })
+        ));
+        fs::write(dir.join("multicast"), "many\n").unwrap();
+        match LinkStats::from_sysfs(root.path(), "rip0") {
+            Err(Error::FS { path, source }) => {
+                assert!(path.ends_with("multicast"));
+                assert_eq!(std::io::ErrorKind::InvalidData, source.kind());
+            }
+            res => panic!("expected an invalid counter error, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_from_sysfs_netns() {
+        // Creating a namespace requires CAP_SYS_ADMIN, so skip the test without it.
+        let netns = std::thread::spawn(|| {
+            nix::sched::unshare(nix::sched::CloneFlags::CLONE_NEWNET)?;
+            NetNs::current()
+        })
+        .join()
+        .unwrap();
+        let netns = match netns {
+            Ok(netns) => netns,
+            Err(_) => return,
+        };
+
+        let root = Path::new(SYSFS_ROOT);
+        let stats = from_sysfs(&Link::with_netns("lo", netns.clone()), root).unwrap();
+        assert_eq!(0, stats.rx_packets);
+
+        // Devices of the calling namespace aren't visible from within the new namespace.
+        let host = fs::read_dir(root)
+            .unwrap()
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .find(|name| name != "lo");
+        if let Some(host) = host {
+            assert!(from_sysfs(&Link::new(&host), root).is_ok());
+            assert!(matches!(
+                from_sysfs(&Link::with_netns(&host, netns), root),
+                Err(Error::FS { .. })
+            ));
+        }
+    }
+}
@@ -0,0 +1,389 @@
+// (c) Copyright 2021 Christian Saide
+// SPDX-License-Identifier: MIT
+
+use super::{Error, Link, NetNs, Result};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const PROC_ROOT: &str = "/proc/sys/net";
+
+/// The IPv6 link local address generation mode of an interface, mirroring `addr_gen_mode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum AddrGenMode {
+    /// Generate the address from the hardware address, via modified EUI-64.
+    Eui64,
+    /// Do not generate a link local address.
+    None,
+    /// Generate a stable privacy address as per RFC 7217.
+    StablePrivacy,
+    /// Generate a random stable privacy address.
+    Random,
+}
+
+impl AddrGenMode {
+    fn to_raw(self) -> u8 {
+        match self {
+            Self::Eui64 => 0,
+            Self::None => 1,
+            Self::StablePrivacy => 2,
+            Self::Random => 3,
+        }
+    }
+
+    fn from_raw(raw: u8) -> Option<Self> {
+        match raw {
+            0 => Some(Self::Eui64),
+            1 => Some(Self::None),
+            2 => Some(Self::StablePrivacy),
+            3 => Some(Self::Random),
+            _ => None,
+        }
+    }
+}
+
+/// Whether an interface accepts IPv6 router advertisements, mirroring `accept_ra`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum AcceptRa {
+    /// Never accept router advertisements.
+    Never,
+    /// Accept router advertisements only when forwarding is disabled.
+    Host,
+    /// Accept router advertisements even when forwarding is enabled.
+    Always,
+}
+
+impl AcceptRa {
+    fn to_raw(self) -> u8 {
+        match self {
+            Self::Never => 0,
+            Self::Host => 1,
+            Self::Always => 2,
+        }
+    }
+
+    fn from_raw(raw: u8) -> Option<Self> {
+        match raw {
+            0 => Some(Self::Never),
+            1 => Some(Self::Host),
+            2 => Some(Self::Always),
+            _ => None,
+        }
+    }
+}
+
+/// The IPv4 reverse path filtering mode of an interface, mirroring `rp_filter`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum RpFilter {
+    /// No source validation.
+    Off,
+    /// Strict mode as per RFC 3704, packets must arrive on the interface used to reach the source.
+    Strict,
+    /// Loose mode as per RFC 3704, the source must be reachable via any interface.
+    Loose,
+}
+
+impl RpFilter {
+    fn to_raw(self) -> u8 {
+        match self {
+            Self::Off => 0,
+            Self::Strict => 1,
+            Self::Loose => 2,
+        }
+    }
+
+    fn from_raw(raw: u8) -> Option<Self> {
+        match raw {
+            0 => Some(Self::Off),
+            1 => Some(Self::Strict),
+            2 => Some(Self::Loose),
+            _ => None,
+        }
+    }
+}
+
+/// Typed access to the per-interface sysctls under `/proc/sys/net/{ipv4,ipv6}/conf/<name>`.
+///
+/// The original value of every sysctl modified through this handle is recorded, and restored when
+/// the handle is dropped, or when [`Sysctl::restore()`] is called. Call [`Sysctl::persist()`] to
+/// retain the modifications instead.
+///
+/// ```no_run
+/// use riptun::{RpFilter, Tun};
+///
+/// let tun = Tun::new("rip%d", 1).expect("Failed to create device.");
+/// let mut sysctl = tun.sysctl();
+/// sysctl.set_disable_ipv6(true).expect("Failed to disable IPv6.");
+/// sysctl.set_rp_filter(RpFilter::Loose).expect("Failed to set rp_filter.");
+/// ```
+#[derive(Debug)]
+pub struct Sysctl {
+    root: PathBuf,
+    name: String,
+    netns: Option<NetNs>,
+    saved: Vec<(PathBuf, String)>,
+}
+
+impl Sysctl {
+    /// Create a new handle for the sysctls of the supplied [Link], within its namespace.
+    pub fn new(link: &Link) -> Self {
+        Self {
+            root: PathBuf::from(PROC_ROOT),
+            name: String::from(link.name()),
+            netns: link.netns().cloned(),
+            saved: Vec::new(),
+        }
+    }
+
+    /// Replace the root directory the sysctls are resolved against, which defaults to
+    /// `/proc/sys/net`.
+    pub fn with_root<P: AsRef<Path>>(mut self, root: P) -> Self {
+        self.root = root.as_ref().to_path_buf();
+        self
+    }
+
+    /// Return whether IPv6 is disabled on the interface.
+    pub fn disable_ipv6(&self) -> Result<bool> {
+        self.get("ipv6", "disable_ipv6").map(|raw| raw != 0)
+    }
+
+    /// Enable or disable IPv6 on the interface.
+    pub fn set_disable_ipv6(&mut self, on: bool) -> Result<()> {
+        self.set("ipv6", "disable_ipv6", on as u8)
+    }
+
+    /// Return the IPv6 link local address generation mode of the interface.
+    pub fn addr_gen_mode(&self) -> Result<AddrGenMode> {
+        self.get_with("ipv6", "addr_gen_mode", AddrGenMode::from_raw)
+    }
+
+    /// Set the IPv6 link local address generation mode of the interface.
+    pub fn set_addr_gen_mode(&mut self, mode: AddrGenMode) -> Result<()> {
+        self.set("ipv6", "addr_gen_mode", mode.to_raw())
+    }
+
+    /// Return whether the interface accepts IPv6 router advertisements.
+    pub fn accept_ra(&self) -> Result<AcceptRa> {
+        self.get_with("ipv6", "accept_ra", AcceptRa::from_raw)
+    }
+
+    /// Set whether the interface accepts IPv6 router advertisements.
+    pub fn set_accept_ra(&mut self, accept_ra: AcceptRa) -> Result<()> {
+        self.set("ipv6", "accept_ra", accept_ra.to_raw())
+    }
+
+    /// Return the IPv4 reverse path filtering mode of the interface.
+    pub fn rp_filter(&self) -> Result<RpFilter> {
+        self.get_with("ipv4", "rp_filter", RpFilter::from_raw)
+    }
+
+    /// Set the IPv4 reverse path filtering mode of the interface.
+    pub fn set_rp_filter(&mut self, mode: RpFilter) -> Result<()> {
+        self.set("ipv4", "rp_filter", mode.to_raw())
+    }
+
+    /// Return whether IPv4 forwarding is enabled on the interface.
+    pub fn ipv4_forwarding(&self) -> Result<bool> {
+        self.get("ipv4", "forwarding").map(|raw| raw != 0)
+    }
+
+    /// Enable or disable IPv4 forwarding on the interface.
+    pub fn set_ipv4_forwarding(&mut self, on: bool) -> Result<()> {
+        self.set("ipv4", "forwarding", on as u8)
+    }
+
+    /// Return whether IPv6 forwarding is enabled on the interface.
+    pub fn ipv6_forwarding(&self) -> Result<bool> {
+        self.get("ipv6", "forwarding").map(|raw| raw != 0)
+    }
+
+    /// Enable or disable IPv6 forwarding on the interface.
+    pub fn set_ipv6_forwarding(&mut self, on: bool) -> Result<()> {
+        self.set("ipv6", "forwarding", on as u8)
+    }
+
+    /// Restore every sysctl modified through this handle to its original value, in the reverse
+    /// order of modification.
+    ///
+    /// # Errors
+    /// Restoration continues past failures, and the first error encountered is returned.
+    pub fn restore(&mut self) -> Result<()> {
+        let mut result = Ok(());
+        while let Some((path, value)) = self.saved.pop() {
+            let restored = self.run(|| write(&path, &value));
+            if result.is_ok() {
+                result = restored;
+            }
+        }
+        result
+    }
+
+    /// Retain all modifications made through this handle, rather than restoring them on drop.
+    pub fn persist(&mut self) {
+        self.saved.clear();
+    }
+
+    fn path(&self, family: &str, key: &str) -> PathBuf {
+        self.root
+            .join(family)
+            .join("conf")
+            .join(&self.name)
+            .join(key)
+    }
+
+    fn get(&self, family: &str, key: &str) -> Result<u8> {
+        let path = self.path(family, key);
+        let value = self.run(|| read(&path))?;
+        value.parse().map_err(|_| invalid_data(&path))
+    }
+
+    fn get_with<T, F>(&self, family: &str, key: &str, from_raw: F) -> Result<T>
+    where
+        F: FnOnce(u8) -> Option<T>,
+    {
+        let raw = self.get(family, key)?;
+        from_raw(raw).ok_or_else(|| invalid_data(&self.path(family, key)))
+    }
+
+    fn set(&mut self, family: &str, key: &str, value: u8) -> Result<()> {
+        let path = self.path(family, key);
+        let original = self.run(|| {
+            let original = read(&path)?;
+            write(&path, &value.to_string())?;
+            Ok(original)
+        })?;
+
+        if !self.saved.iter().any(|(saved, _)| *saved == path) {
+            self.saved.push((path, original));
+        }
+        Ok(())
+    }
+
+    fn run<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce() -> Result<T> + Send,
+        T: Send,
+    {
+        match &self.netns {
+            Some(netns) => netns.run(f),
+            None => f(),
+        }
+    }
+}
+
+impl Drop for Sysctl {
+    fn drop(&mut self) {
+        // Errors can't be surfaced from drop, call `restore` directly to observe them.
+        let _ = self.restore();
+    }
+}
+
+fn read(path: &Path) -> Result<String> {
+    fs::read_to_string(path)
+        .map(|value| String::from(value.trim()))
+        .map_err(|source| Error::FS {
+            path: path.display().to_string(),
+            source,
+        })
+}
+
+fn write(path: &Path, value: &str) -> Result<()> {
+    fs::write(path, value).map_err(|source| Error::FS {
+        path: path.display().to_string(),
+        source,
+    })
+}
+
+fn invalid_data(path: &Path) -> Error {
+    Error::FS {
+        path: path.display().to_string(),
+        source: std::io::Error::from(std::io::ErrorKind::InvalidData),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fake_root(name: &str) -> tempfile::TempDir {
+        let root = tempfile::tempdir().unwrap();
+        for (family, key, value) in [
+            ("ipv6", "disable_ipv6", "0"),
+            ("ipv6", "addr_gen_mode", "0"),
+            ("ipv6", "accept_ra", "1"),
+            ("ipv6", "forwarding", "0"),
+            ("ipv4", "rp_filter", "1"),
+            ("ipv4", "forwarding", "0"),
+        ] {
+            let dir = root.path().join(family).join("conf").join(name);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join(key), format!("{}\n", value)).unwrap();
+        }
+        root
+    }
+
+    #[test]
+    fn test_get_set() {
+        let root = fake_root("rip0");
+        let mut sysctl = Sysctl::new(&Link::new("rip0")).with_root(root.path());
+
+        assert!(!sysctl.disable_ipv6().unwrap());
+        assert_eq!(AddrGenMode::Eui64, sysctl.addr_gen_mode().unwrap());
+        assert_eq!(AcceptRa::Host, sysctl.accept_ra().unwrap());
+        assert_eq!(RpFilter::Strict, sysctl.rp_filter().unwrap());
+        assert!(!sysctl.ipv4_forwarding().unwrap());
+        assert!(!sysctl.ipv6_forwarding().unwrap());
+
+        sysctl.set_disable_ipv6(true).unwrap();
+        sysctl.set_addr_gen_mode(AddrGenMode::None).unwrap();
+        sysctl.set_accept_ra(AcceptRa::Always).unwrap();
+        sysctl.set_rp_filter(RpFilter::Loose).unwrap();
+        sysctl.set_ipv4_forwarding(true).unwrap();
+        sysctl.set_ipv6_forwarding(true).unwrap();
+
+        assert!(sysctl.disable_ipv6().unwrap());
+        assert_eq!(AddrGenMode::None, sysctl.addr_gen_mode().unwrap());
+        assert_eq!(AcceptRa::Always, sysctl.accept_ra().unwrap());
+        assert_eq!(RpFilter::Loose, sysctl.rp_filter().unwrap());
+        assert!(sysctl.ipv4_forwarding().unwrap());
+        assert!(sysctl.ipv6_forwarding().unwrap());
+
+        assert!(Sysctl::new(&Link::new("rip1"))
+            .with_root(root.path())
+            .disable_ipv6()
+            .is_err());
+    }
+
+    #[test]
+    fn test_restore() {
+        let root = fake_root("rip0");
+        let path = root.path().join("ipv4/conf/rip0/rp_filter");
+
+        let mut sysctl = Sysctl::new(&Link::new("rip0")).with_root(root.path());
+        sysctl.set_rp_filter(RpFilter::Loose).unwrap();
+        sysctl.set_rp_filter(RpFilter::Off).unwrap();
+        drop(sysctl);
+        assert_eq!("1", fs::read_to_string(&path).unwrap().trim());
+
+        let mut sysctl = Sysctl::new(&Link::new("rip0")).with_root(root.path());
+        sysctl.set_rp_filter(RpFilter::Off).unwrap();
+        sysctl.persist();
+        drop(sysctl);
+        assert_eq!("0", fs::read_to_string(&path).unwrap().trim());
+    }
+}
@@ -0,0 +1,364 @@
+// (c) Copyright 2021 Christian Saide
+// SPDX-License-Identifier: MIT
+
+use super::{Load, TokioQueue};
+
+use std::io;
+use std::mem::MaybeUninit;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use bytes::{BufMut, BytesMut};
+use futures_util::{ready, Sink, Stream};
+use tokio::io::ReadBuf;
+use tokio_util::codec::{Decoder, Encoder};
+
+/// The initial capacity of the read buffer, which covers the largest possible packet.
+const INITIAL_RD_CAPACITY: usize = 64 * 1024;
+/// The initial capacity of the write buffer.
+const INITIAL_WR_CAPACITY: usize = 8 * 1024;
+
+pub(crate) mod sealed {
+    use super::{Load, PacketTransport};
+
+    use std::io;
+    use std::task::{Context, Poll};
+
+    pub trait Sealed {
+        /// Attempt to write a single packet, retaining the queue selected by a device for the
+        /// packet, along with its load, in the supplied slot while pending.
+        #[inline]
+        fn poll_send_selected(
+            &self,
+            cx: &mut Context<'_>,
+            _: &mut Option<Load>,
+            datagram: &[u8],
+        ) -> Poll<io::Result<usize>>
+        where
+            Self: PacketTransport,
+        {
+            self.poll_send(cx, datagram)
+        }
+    }
+}
+
+/// A packet oriented transport which can be framed via [TokioFramed], such as a [TokioQueue] or
+/// [`TokioTun`][crate::TokioTun], or a reference to or [Arc] of either. This trait is sealed.
+pub trait PacketTransport: sealed::Sealed {
+    /// Attempt to read a single packet into the supplied buffer, see
+    /// [`TokioQueue::poll_recv()`].
+    fn poll_recv(&self, cx: &mut Context<'_>, datagram: &mut ReadBuf<'_>) -> Poll<io::Result<()>>;
+
+    /// Attempt to write a single packet from the supplied buffer, see
+    /// [`TokioQueue::poll_send()`].
+    fn poll_send(&self, cx: &mut Context<'_>, datagram: &[u8]) -> Poll<io::Result<usize>>;
+}
+
+impl sealed::Sealed for TokioQueue {}
+
+impl PacketTransport for TokioQueue {
+    #[inline]
+    fn poll_recv(&self, cx: &mut Context<'_>, datagram: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        TokioQueue::poll_recv(self, cx, datagram)
+    }
+
+    #[inline]
+    fn poll_send(&self, cx: &mut Context<'_>, datagram: &[u8]) -> Poll<io::Result<usize>> {
+        TokioQueue::poll_send(self, cx, datagram)
+    }
+}
+
+impl<T: PacketTransport + ?Sized> sealed::Sealed for &T {
+    #[inline]
+    fn poll_send_selected(
+        &self,
+        cx: &mut Context<'_>,
+        load: &mut Option<Load>,
+        datagram: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        (**self).poll_send_selected(cx, load, datagram)
+    }
+}
+
+impl<T: PacketTransport + ?Sized> PacketTransport for &T {
+    #[inline]
+    fn poll_recv(&self, cx: &mut Context<'_>, datagram: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        (**self).poll_recv(cx, datagram)
+    }
+
+    #[inline]
+    fn poll_send(&self, cx: &mut Context<'_>, datagram: &[u8]) -> Poll<io::Result<usize>> {
+        (**self).poll_send(cx, datagram)
+    }
+}
+
+impl<T: PacketTransport + ?Sized> sealed::Sealed for Arc<T> {
+    #[inline]
+    fn poll_send_selected(
+        &self,
+        cx: &mut Context<'_>,
+        load: &mut Option<Load>,
+        datagram: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        (**self).poll_send_selected(cx, load, datagram)
+    }
+}
+
+impl<T: PacketTransport + ?Sized> PacketTransport for Arc<T> {
+    #[inline]
+    fn poll_recv(&self, cx: &mut Context<'_>, datagram: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        (**self).poll_recv(cx, datagram)
+    }
+
+    #[inline]
+    fn poll_send(&self, cx: &mut Context<'_>, datagram: &[u8]) -> Poll<io::Result<usize>> {
+        (**self).poll_send(cx, datagram)
+    }
+}
+
+/// A unified [Stream] and [Sink] interface over a [PacketTransport], using a [Decoder] and
+/// [Encoder] to convert packets into frames and back, analogous to `tokio_util`'s `UdpFramed`.
+///
+/// Unlike `Framed` every packet is decoded on its own, so each read yields one or more whole
+/// frames, and every frame is encoded and sent as a single packet. Any bytes of a packet left
+/// over once the decoder returns `None` are reported as an error by
+/// [`Decoder::decode_eof()`], and discarded. The stream ends once the transport is shutdown or
+/// closed. When framing a device, the queue selected by its [`SendPolicy`][crate::SendPolicy]
+/// for a frame is retained until the frame is sent.
+///
+/// ```no_run
+/// use futures_util::{SinkExt, StreamExt};
+/// use riptun::{TokioFramed, TokioTun};
+/// use tokio_util::codec::BytesCodec;
+///
+/// # async fn run() {
+/// let tun = TokioTun::new("rip%d", 4).expect("failed to create device");
+/// let mut framed = TokioFramed::new(&tun, BytesCodec::new());
+/// while let Some(packet) = framed.next().await {
+///     let packet = packet.expect("failed to receive packet");
+///     framed.send(packet.freeze()).await.expect("failed to send packet");
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct TokioFramed<C, T = TokioQueue> {
+    transport: T,
+    codec: C,
+    rd: BytesMut,
+    wr: BytesMut,
+    flushed: bool,
+    load: Option<Load>,
+    is_readable: bool,
+    done: bool,
+}
+
+impl<C, T: PacketTransport> TokioFramed<C, T> {
+    /// Create a new framed adapter over the supplied transport, using the supplied codec to
+    /// decode received packets into frames, and encode frames into packets to send.
+    pub fn new(transport: T, codec: C) -> Self {
+        Self {
+            transport,
+            codec,
+            rd: BytesMut::with_capacity(INITIAL_RD_CAPACITY),
+            wr: BytesMut::with_capacity(INITIAL_WR_CAPACITY),
+            flushed: true,
+            load: None,
+            is_readable: false,
+            done: false,
+        }
+    }
+
+    /// Return a reference to the underlying transport.
+    #[inline]
+    pub fn get_ref(&self) -> &T {
+        &self.transport
+    }
+
+    /// Return a mutable reference to the underlying transport.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    /// Consume the adapter, returning the underlying transport. Any buffered frames are lost.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    /// Return a reference to the codec.
+    #[inline]
+    pub fn codec(&self) -> &C {
+        &self.codec
+    }
+
+    /// Return a mutable reference to the codec.
+    #[inline]
+    pub fn codec_mut(&mut self) -> &mut C {
+        &mut self.codec
+    }
+
+    /// Return a reference to the read buffer.
+    #[inline]
+    pub fn read_buffer(&self) -> &BytesMut {
+        &self.rd
+    }
+
+    /// Return a mutable reference to the read buffer.
+    #[inline]
+    pub fn read_buffer_mut(&mut self) -> &mut BytesMut {
+        &mut self.rd
+    }
+}
+
+impl<C: Decoder + Unpin, T: PacketTransport + Unpin> Stream for TokioFramed<C, T> {
+    type Item = Result<C::Item, C::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        if this.done {
+            return Poll::Ready(None);
+        }
+
+        loop {
+            if this.is_readable {
+                let res = this.codec.decode_eof(&mut this.rd);
+                if let Ok(Some(frame)) = res {
+                    return Poll::Ready(Some(Ok(frame)));
+                }
+                // The packet has either been decoded in its entirety, or is invalid.
+                this.is_readable = false;
+                this.rd.clear();
+                if let Err(err) = res {
+                    return Poll::Ready(Some(Err(err)));
+                }
+            }
+
+            this.rd.reserve(INITIAL_RD_CAPACITY);
+            let read = {
+                let chunk = this.rd.chunk_mut();
+                // The transport only ever writes initialized bytes to the buffer.
+                let chunk = unsafe { &mut *(chunk as *mut _ as *mut [MaybeUninit<u8>]) };
+                let mut buf = ReadBuf::uninit(chunk);
+                match ready!(this.transport.poll_recv(cx, &mut buf)) {
+                    Ok(()) => buf.filled().len(),
+                    Err(err) if err.kind() == io::ErrorKind::NotConnected => {
+                        this.done = true;
+                        return Poll::Ready(None);
+                    }
+                    Err(err) => return Poll::Ready(Some(Err(err.into()))),
+                }
+            };
+            unsafe { this.rd.advance_mut(read) };
+            this.is_readable = true;
+        }
+    }
+}
+
+impl<I, C: Encoder<I> + Unpin, T: PacketTransport + Unpin> Sink<I> for TokioFramed<C, T> {
+    type Error = C::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if !self.flushed {
+            ready!(self.poll_flush(cx))?;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: I) -> Result<(), Self::Error> {
+        let this = &mut *self;
+        this.codec.encode(item, &mut this.wr)?;
+        this.flushed = false;
+        Ok(())
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = &mut *self;
+        if this.flushed {
+            return Poll::Ready(Ok(()));
+        }
+
+        let sent = ready!(this
+            .transport
+            .poll_send_selected(cx, &mut this.load, &this.wr))?;
+        let wrote_all = sent == this.wr.len();
+        this.wr.clear();
+        this.flushed = true;
+        if !wrote_all {
+            let err = io::Error::other("failed to write the entire packet to the transport");
+            return Poll::Ready(Err(err.into()));
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    #[inline]
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.poll_flush(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    use futures_util::task::noop_waker_ref;
+    use futures_util::{FutureExt, SinkExt, StreamExt};
+    use tokio_util::codec::LinesCodec;
+
+    #[derive(Default)]
+    struct MockTransport {
+        inbound: Mutex<VecDeque<&'static [u8]>>,
+        sent: Mutex<Vec<Vec<u8>>>,
+    }
+
+    impl sealed::Sealed for MockTransport {}
+
+    impl PacketTransport for MockTransport {
+        fn poll_recv(
+            &self,
+            _: &mut Context<'_>,
+            datagram: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            match self.inbound.lock().unwrap().pop_front() {
+                Some(packet) => {
+                    datagram.put_slice(packet);
+                    Poll::Ready(Ok(()))
+                }
+                None => Poll::Ready(Err(io::ErrorKind::NotConnected.into())),
+            }
+        }
+
+        fn poll_send(&self, _: &mut Context<'_>, datagram: &[u8]) -> Poll<io::Result<usize>> {
+            self.sent.lock().unwrap().push(datagram.to_vec());
+            Poll::Ready(Ok(datagram.len()))
+        }
+    }
+
+    #[test]
+    fn test_framed() {
+        let transport = MockTransport::default();
+        transport
+            .inbound
+            .lock()
+            .unwrap()
+            .extend([&b"one\ntwo\n"[..], b"three"]);
+        let mut framed = TokioFramed::new(&transport, LinesCodec::new());
+
+        let mut cx = Context::from_waker(noop_waker_ref());
+        let mut frames = Vec::new();
+        while let Poll::Ready(Some(frame)) = framed.poll_next_unpin(&mut cx) {
+            frames.push(frame.unwrap());
+        }
+        assert_eq!(vec!["one", "two", "three"], frames);
+        assert!(matches!(framed.poll_next_unpin(&mut cx), Poll::Ready(None)));
+
+        let mut send = framed.send("reply");
+        assert!(matches!(send.poll_unpin(&mut cx), Poll::Ready(Ok(()))));
+        drop(send);
+        assert_eq!(vec![b"reply\n".to_vec()], *transport.sent.lock().unwrap());
+    }
+}
@@ -1,14 +1,24 @@
 // (c) Copyright 2021 Christian Saide
 // SPDX-License-Identifier: MIT
 
-use super::Queue;
+use super::{Error, Queue};
 
 use std::io;
-use std::os::unix::io::AsRawFd;
+use std::os::unix::io::{AsRawFd, RawFd};
 
 use mio::unix::SourceFd;
 use mio::{event, Interest, Registry, Token};
 
+impl Queue {
+    /// Return the descriptor to register with a [Registry], failing if the queue is closed.
+    fn source_fd(&self) -> io::Result<RawFd> {
+        if self.is_closed() {
+            return Err(Error::QueueClosed.into_io());
+        }
+        Ok(self.as_raw_fd())
+    }
+}
+
 impl event::Source for Queue {
     fn register(
         &mut self,
@@ -16,7 +26,7 @@ impl event::Source for Queue {
         token: Token,
         interests: Interest,
     ) -> io::Result<()> {
-        SourceFd(&self.as_raw_fd()).register(registry, token, interests)
+        SourceFd(&self.source_fd()?).register(registry, token, interests)
     }
 
     fn reregister(
@@ -25,10 +35,10 @@ impl event::Source for Queue {
         token: Token,
         interests: Interest,
     ) -> io::Result<()> {
-        SourceFd(&self.as_raw_fd()).reregister(registry, token, interests)
+        SourceFd(&self.source_fd()?).reregister(registry, token, interests)
     }
 
     fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
-        SourceFd(&self.as_raw_fd()).deregister(registry)
+        SourceFd(&self.source_fd()?).deregister(registry)
     }
 }
@@ -0,0 +1,134 @@
+// (c) Copyright 2021 Christian Saide
+// SPDX-License-Identifier: MIT
+
+use super::Error;
+
+use std::future::Future;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::Context;
+
+use event_listener::Event;
+use futures_util::future::{self, Either};
+use futures_util::pin_mut;
+use futures_util::task::AtomicWaker;
+
+/// The direction of a poll based operation, each of which wakes a single task on shutdown.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Direction {
+    Read,
+    Write,
+}
+
+/// The shutdown state of an async queue, allowing the queue to be shutdown through a shared
+/// reference while readers and writers are pending on it.
+#[derive(Debug, Default)]
+pub(crate) struct Shutdown {
+    closed: AtomicBool,
+    event: Event,
+    read: AtomicWaker,
+    write: AtomicWaker,
+}
+
+impl Shutdown {
+    /// Mark the queue as shutdown, waking all pending operations.
+    pub(crate) fn trigger(&self) {
+        if !self.closed.swap(true, Ordering::SeqCst) {
+            self.event.notify(usize::MAX);
+            self.read.wake();
+            self.write.wake();
+        }
+    }
+
+    /// Return an error if the queue has been shutdown.
+    pub(crate) fn check(&self) -> io::Result<()> {
+        if self.closed.load(Ordering::SeqCst) {
+            Err(Error::QueueClosed.into_io())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Return an error if the queue has been shutdown, otherwise register the current task to be
+    /// woken by a shutdown. Much like the readiness of the queue itself, only the last task to
+    /// poll in each direction is woken.
+    pub(crate) fn poll_check(&self, cx: &Context<'_>, direction: Direction) -> io::Result<()> {
+        // Register prior to checking, so that a concurrent shutdown can't be missed.
+        match direction {
+            Direction::Read => self.read.register(cx.waker()),
+            Direction::Write => self.write.register(cx.waker()),
+        }
+        self.check()
+    }
+
+    /// Drive the supplied operation to completion, unless the queue is shutdown first in which
+    /// case the operation is abandoned and an error returned.
+    pub(crate) async fn guard<T, F>(&self, op: F) -> io::Result<T>
+    where
+        F: Future<Output = io::Result<T>>,
+    {
+        // Listen prior to checking, so that a concurrent shutdown can't be missed.
+        let listener = self.event.listen();
+        self.check()?;
+
+        pin_mut!(op);
+        match future::select(op, listener).await {
+            Either::Left((res, _)) => res,
+            Either::Right(_) => Err(Error::QueueClosed.into_io()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+    use std::task::{Poll, Wake, Waker};
+
+    use futures_util::task::noop_waker_ref;
+
+    #[derive(Default)]
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_wakes_pending() {
+        let shutdown = Shutdown::default();
+        let pending = shutdown.guard(future::pending::<io::Result<()>>());
+        pin_mut!(pending);
+
+        let mut cx = Context::from_waker(noop_waker_ref());
+        assert!(pending.as_mut().poll(&mut cx).is_pending());
+
+        shutdown.trigger();
+        shutdown.trigger();
+        match pending.poll(&mut cx) {
+            Poll::Ready(Err(err)) => assert_eq!(io::ErrorKind::NotConnected, err.kind()),
+            _ => panic!("expected the pending operation to fail"),
+        }
+        assert!(shutdown.check().is_err());
+    }
+    #[test]
+    fn test_wakes_polled() {
+        let shutdown = Shutdown::default();
+        let (read, write) = (Arc::new(CountingWaker::default()), Arc::default());
+        for (waker, direction) in [(&read, Direction::Read), (&write, Direction::Write)] {
+            let waker = Waker::from(Arc::clone(waker));
+            let cx = Context::from_waker(&waker);
+            shutdown.poll_check(&cx, direction).unwrap();
+        }
+
+        shutdown.trigger();
+        assert_eq!(1, read.0.load(Ordering::SeqCst));
+        assert_eq!(1, write.0.load(Ordering::SeqCst));
+        let cx = Context::from_waker(noop_waker_ref());
+        assert!(shutdown.poll_check(&cx, Direction::Read).is_err());
+    }
+}
@@ -1,54 +1,125 @@
 // (c) Copyright 2021 Christian Saide
 // SPDX-License-Identifier: MIT
 
-use super::{IfReq, Opener, Queue, Result};
+use super::{
+    closed_fd, Direction, Error, FromQueue, Load, PacketBatch, PacketSink, PollPacket, Queue,
+    RecvMeta, Result, Shutdown, CLOSED,
+};
+#[cfg(feature = "pool-impl")]
+use super::{PacketPool, PacketStream, PooledPacket};
 
-use std::io;
+use std::io::{self, IoSlice, IoSliceMut};
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
 use async_io::Async;
 use futures_io::{AsyncRead, AsyncWrite};
+use futures_util::ready;
 
 /// An async wrapper around the [Queue] object leveraging the [Async] struct internally
 /// for async functionality.
 ///
 /// This also implements both the [AsyncRead] and [AsyncWrite] enabling simple integration
 /// with both the `async-std` and `smol` ecosystems.
-pub struct AsyncStdQueue(Async<Queue>);
+///
+/// Once shutdown or closed all operations fail with [Error::QueueClosed], converted into an
+/// [io::Error] of kind [`NotConnected`][std::io::ErrorKind::NotConnected] where necessary.
+pub struct AsyncStdQueue {
+    inner: Option<Async<Queue>>,
+    shutdown: Shutdown,
+}
 
 impl AsyncStdQueue {
-    /// Open a new async Queue based on the supplied [IfReq], exposing async capability for the
-    /// async-std/smol ecosystems.
-    pub(crate) fn open(req: &IfReq) -> Result<Self> {
-        let queue = Queue::open(req)?;
+    /// Wrap the supplied [Queue], exposing async capability for the async-std/smol ecosystems.
+    pub(crate) fn new(queue: Queue) -> Result<Self> {
         let async_fd = Async::new(queue)?;
-        Ok(Self(async_fd))
+        Ok(Self {
+            inner: Some(async_fd),
+            shutdown: Shutdown::default(),
+        })
     }
 
-    /// Close the internal queue destroying this instance completely.
+    /// Shutdown the queue, waking all pending operations with an error and failing any
+    /// subsequent ones. Unlike [`AsyncStdQueue::close()`] this only requires a shared reference,
+    /// so it can be used to stop tasks blocked reading from or writing to a shared queue. The
+    /// underlying file descriptor remains open until the queue is closed or dropped.
     #[inline]
+    pub fn shutdown(&self) {
+        self.shutdown.trigger();
+    }
+
+    /// Close the internal queue destroying this instance completely. The queue is shutdown, and
+    /// deregistered from the reactor, prior to closing the underlying file descriptor. Closing an
+    /// already closed queue is a no-op.
     pub fn close(&mut self) -> Result<()> {
-        self.0.get_mut().close()
+        self.shutdown.trigger();
+        // Make sure the placeholder exposed via `get_ref()` is available before letting go of
+        // the queue, see `Queue::close()`.
+        if self.inner.is_some() {
+            closed_fd()?;
+        }
+        match self.inner.take() {
+            Some(async_fd) => async_fd.into_inner()?.close(),
+            None => Ok(()),
+        }
+    }
+
+    fn inner(&self) -> io::Result<&Async<Queue>> {
+        self.shutdown.check()?;
+        self.inner
+            .as_ref()
+            .ok_or_else(|| Error::QueueClosed.into_io())
+    }
+
+    /// Attempt an operation on the inner [Async] until it no longer returns
+    /// [`WouldBlock`][std::io::ErrorKind::WouldBlock], registering the current task for wakeup if
+    /// the queue isn't ready in the supplied direction, along with on shutdown.
+    fn poll_io<T, F>(
+        &self,
+        cx: &mut Context<'_>,
+        direction: Direction,
+        mut op: F,
+    ) -> Poll<io::Result<T>>
+    where
+        F: FnMut(&Queue) -> io::Result<T>,
+    {
+        self.shutdown.poll_check(cx, direction)?;
+        let inner = self
+            .inner
+            .as_ref()
+            .ok_or_else(|| Error::QueueClosed.into_io())?;
+        loop {
+            match op(inner.get_ref()) {
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+                res => return Poll::Ready(res),
+            }
+            match direction {
+                Direction::Read => ready!(inner.poll_readable(cx))?,
+                Direction::Write => ready!(inner.poll_writable(cx))?,
+            }
+        }
     }
 
     /// Wrapper around the [Async] struct's [`Async::readable()`] call.
     #[inline]
     pub async fn readable(&self) -> io::Result<()> {
-        self.0.readable().await
+        let inner = self.inner()?;
+        self.shutdown.guard(inner.readable()).await
     }
 
     /// Wrapper around the [Async] struct's [`Async::writable()`] call.
     #[inline]
     pub async fn writable(&self) -> io::Result<()> {
-        self.0.writable().await
+        let inner = self.inner()?;
+        self.shutdown.guard(inner.writable()).await
     }
 
     /// Return a reference to the internal [Queue]. This is generally used, when it's necessary
-    /// to interact with the underlying [`Queue::recv()`] or [`Queue::send()`] methods.
+    /// to interact with the underlying [`Queue::recv()`] or [`Queue::send()`] methods. Once this
+    /// queue is closed the returned [Queue] is closed as well.
     #[inline]
     pub fn get_ref(&self) -> &Queue {
-        self.0.get_ref()
+        self.inner.as_ref().map(Async::get_ref).unwrap_or(&CLOSED)
     }
 
     /// Asynchrounously read a datagram off the underlying queue. Looping over [`Queue::recv()`] calls
@@ -60,7 +131,47 @@ impl AsyncStdQueue {
     /// On any error it should be assumed that no usable data was read into the buffer.
     #[inline]
     pub async fn recv(&self, datagram: &mut [u8]) -> io::Result<usize> {
-        self.0.read_with(|queue| queue.recv(datagram)).await
+        let inner = self.inner()?;
+        self.shutdown
+            .guard(inner.read_with(|queue| queue.recv(datagram)))
+            .await
+    }
+
+    /// Asynchronously read a single packet into a buffer taken from the supplied [PacketPool],
+    /// see [`AsyncStdQueue::recv()`] and [`Queue::recv_pooled()`] for more details.
+    ///
+    /// # Errors
+    /// On any error the buffer is returned to the pool.
+    #[cfg(feature = "pool-impl")]
+    pub async fn recv_pooled(&self, pool: &PacketPool) -> io::Result<PooledPacket> {
+        let mut packet = pool.get();
+        let read = self.recv(packet.spare()).await?;
+        packet.commit(read);
+        Ok(packet)
+    }
+
+    /// Create a [PacketStream] of the packets received by this queue, each received into a
+    /// buffer taken from the supplied [PacketPool] as per [`AsyncStdQueue::recv_pooled()`].
+    #[cfg(feature = "pool-impl")]
+    pub fn stream(&self, pool: PacketPool) -> PacketStream<'_> {
+        PacketStream::new(self, pool)
+    }
+
+    /// Create a [PacketSink] sending each packet via this queue as per [`AsyncStdQueue::send()`].
+    pub fn sink<'a, T>(&'a self) -> PacketSink<'a, T>
+    where
+        T: AsRef<[u8]>,
+    {
+        PacketSink::new(self)
+    }
+
+    /// Asynchronously read a packet off the underlying queue, analogous to
+    /// [`AsyncStdQueue::recv()`], returning its [RecvMeta] reporting the supplied queue index.
+    pub(crate) async fn recv_meta(&self, idx: usize, datagram: &mut [u8]) -> io::Result<RecvMeta> {
+        let inner = self.inner()?;
+        self.shutdown
+            .guard(inner.read_with(|queue| queue.recv_meta(idx, datagram)))
+            .await
     }
 
     /// Asynchrounously write a datagram to the underlying queue. Looping over [`Queue::send()`] calls
@@ -73,7 +184,97 @@ impl AsyncStdQueue {
     /// On any error it should be assumed that the buffer was partially sent.
     #[inline]
     pub async fn send(&self, datagram: &[u8]) -> io::Result<usize> {
-        self.0.write_with(|queue| queue.send(datagram)).await
+        let inner = self.inner()?;
+        self.shutdown
+            .guard(inner.write_with(|queue| queue.send(datagram)))
+            .await
+    }
+
+    /// Asynchronously read a single packet off the underlying queue, scattering it across the
+    /// supplied buffers in order, see [`Queue::recv_vectored()`] for more details. Upon success the
+    /// number of bytes read is returned.
+    ///
+    /// # Errors
+    /// On any error it should be assumed that no usable data was read into the buffers.
+    #[inline]
+    pub async fn recv_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        let inner = self.inner()?;
+        self.shutdown
+            .guard(inner.read_with(|queue| queue.recv_vectored(bufs)))
+            .await
+    }
+
+    /// Asynchronously write a single packet gathered from the supplied buffers to the underlying
+    /// queue, see [`Queue::send_vectored()`] for more details. Upon success the number of bytes
+    /// sent is returned.
+    ///
+    /// # Errors
+    /// On any error it should be assumed that the buffers were partially sent.
+    #[inline]
+    pub async fn send_vectored(&self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        let inner = self.inner()?;
+        self.shutdown
+            .guard(inner.write_with(|queue| queue.send_vectored(bufs)))
+            .await
+    }
+
+    /// Asynchronously read as many packets as are available off the underlying queue into the
+    /// supplied batch, replacing its previous contents, see [`Queue::recv_batch()`] for more
+    /// details. This waits for readiness once, then drains packets until either the batch is full
+    /// or the queue has no more packets waiting. Upon success the number of packets read is
+    /// returned, which is at least `1` for a non-empty batch.
+    ///
+    /// # Errors
+    /// Errors are returned even if packets were already read into the batch, as with
+    /// [`Queue::recv_batch()`], on any error it should be assumed that no usable data was read.
+    pub async fn recv_batch(&self, batch: &mut PacketBatch) -> io::Result<usize> {
+        let inner = self.inner()?;
+        self.shutdown
+            .guard(inner.read_with(|queue| queue.recv_batch_int(batch, false)))
+            .await
+    }
+
+    /// Asynchronously write every packet of the supplied batch to the underlying queue, in order,
+    /// waiting for readiness only when the queue would block. Upon success the number of packets
+    /// sent is returned, which is the length of the batch.
+    ///
+    /// # Errors
+    /// On any error it should be assumed that part of the batch was sent.
+    pub async fn send_batch(&self, batch: &PacketBatch) -> io::Result<usize> {
+        let inner = self.inner()?;
+        self.shutdown
+            .guard(async {
+                let mut next = 0;
+                while next < batch.len() {
+                    next = inner
+                        .write_with(|queue| queue.send_batch_int(batch, next))
+                        .await?;
+                }
+                Ok(next)
+            })
+            .await
+    }
+
+    /// Attempt to read a single packet into the supplied buffer, registering the current task
+    /// for wakeup if the queue isn't ready for reading. Upon success the number of bytes read is
+    /// returned.
+    ///
+    /// A pending call is woken by a shutdown, although only the last task to poll for reading is
+    /// woken, as with the readiness of the queue itself.
+    #[inline]
+    pub fn poll_recv(&self, cx: &mut Context<'_>, datagram: &mut [u8]) -> Poll<io::Result<usize>> {
+        self.poll_io(cx, Direction::Read, |queue| queue.recv(datagram))
+    }
+
+    /// Attempt to write a single packet from the supplied buffer, registering the current task
+    /// for wakeup if the queue isn't ready for writing. Upon success the number of bytes sent is
+    /// returned.
+    ///
+    /// A pending call is woken by a shutdown, although only the last task to poll for writing is
+    /// woken, as with the readiness of the queue itself.
+    #[inline]
+    pub fn poll_send(&self, cx: &mut Context<'_>, datagram: &[u8]) -> Poll<io::Result<usize>> {
+        self.poll_io(cx, Direction::Write, |queue| queue.send(datagram))
     }
 }
 
@@ -84,7 +285,16 @@ impl AsyncWrite for AsyncStdQueue {
         cx: &mut Context<'_>,
         buf: &[u8],
     ) -> Poll<io::Result<usize>> {
-        Pin::new(&mut self.get_mut().0).poll_write(cx, buf)
+        self.poll_send(cx, buf)
+    }
+
+    #[inline]
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_io(cx, Direction::Write, |queue| queue.send_vectored(bufs))
     }
 
     #[inline]
@@ -94,8 +304,8 @@ impl AsyncWrite for AsyncStdQueue {
     }
 
     #[inline]
-    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        Pin::new(&mut self.get_mut().0).poll_close(cx)
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(self.get_mut().close().map_err(Error::into_io))
     }
 }
 
@@ -106,13 +316,39 @@ impl AsyncRead for AsyncStdQueue {
         cx: &mut Context<'_>,
         buf: &mut [u8],
     ) -> std::task::Poll<io::Result<usize>> {
-        Pin::new(&mut self.get_mut().0).poll_read(cx, buf)
+        self.poll_recv(cx, buf)
+    }
+
+    #[inline]
+    fn poll_read_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_io(cx, Direction::Read, |queue| queue.recv_vectored(bufs))
+    }
+}
+
+impl PollPacket for AsyncStdQueue {
+    #[inline]
+    fn poll_recv_packet(&self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        self.poll_recv(cx, buf)
+    }
+
+    #[inline]
+    fn poll_send_packet(
+        &self,
+        cx: &mut Context<'_>,
+        _: &mut Option<Load>,
+        packet: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_send(cx, packet)
     }
 }
 
-impl Opener for AsyncStdQueue {
+impl FromQueue for AsyncStdQueue {
     #[inline]
-    fn open(req: &IfReq) -> Result<Self> {
-        Self::open(req)
+    fn from_queue(queue: Queue) -> Result<Self> {
+        Self::new(queue)
     }
 }
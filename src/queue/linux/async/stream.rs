@@ -0,0 +1,257 @@
+// (c) Copyright 2021 Christian Saide
+// SPDX-License-Identifier: MIT
+
+#[cfg(feature = "pool-impl")]
+use super::{PacketPool, PooledPacket};
+use crate::tun::Load;
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+#[cfg(feature = "pool-impl")]
+use futures_util::Stream;
+use futures_util::{ready, Sink};
+
+/// Poll based packet I/O, implemented by the async queues and devices to drive a [PacketStream]
+/// or [PacketSink] without allocating a future per packet.
+pub(crate) trait PollPacket {
+    /// Attempt to read a single packet into the supplied buffer, registering the current task
+    /// for wakeup if no packet is available. Upon success the number of bytes read is returned.
+    #[cfg_attr(not(feature = "pool-impl"), allow(dead_code))]
+    fn poll_recv_packet(&self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>>;
+
+    /// Attempt to write the supplied packet, registering the current task for wakeup if it can't
+    /// be sent yet. Upon success the number of bytes sent is returned.
+    ///
+    /// Devices retain the queue selected for the packet in the supplied [Load] while pending, so
+    /// that it keeps counting against the load of the queue until sent.
+    fn poll_send_packet(
+        &self,
+        cx: &mut Context<'_>,
+        load: &mut Option<Load>,
+        packet: &[u8],
+    ) -> Poll<io::Result<usize>>;
+}
+
+/// A [Stream] of the packets received by an async queue or device, created via calls such as
+/// [`TokioQueue::stream()`][crate::TokioQueue::stream()].
+///
+/// Each packet is received into a buffer taken from the [PacketPool] supplied on creation, which
+/// is returned to the pool once the packet is dropped, so a steady state stream doesn't allocate
+/// packet buffers. The stream ends once the underlying queues are shutdown or closed.
+#[cfg(feature = "pool-impl")]
+pub struct PacketStream<'a> {
+    io: &'a (dyn PollPacket + Sync),
+    pool: PacketPool,
+    packet: Option<PooledPacket>,
+    done: bool,
+}
+
+#[cfg(feature = "pool-impl")]
+impl<'a> PacketStream<'a> {
+    pub(crate) fn new(io: &'a (dyn PollPacket + Sync), pool: PacketPool) -> Self {
+        Self {
+            io,
+            pool,
+            packet: None,
+            done: false,
+        }
+    }
+
+    /// Return a reference to the pool packets are received into.
+    #[inline]
+    pub fn pool(&self) -> &PacketPool {
+        &self.pool
+    }
+}
+
+#[cfg(feature = "pool-impl")]
+impl Stream for PacketStream<'_> {
+    type Item = io::Result<PooledPacket>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        if this.done {
+            return Poll::Ready(None);
+        }
+
+        // Hold on to the buffer while waiting, so it is only taken from the pool once per packet.
+        let pool = &this.pool;
+        let packet = this.packet.get_or_insert_with(|| pool.get());
+        let res = ready!(this.io.poll_recv_packet(cx, packet.spare()));
+        let mut packet = this.packet.take().unwrap();
+        match res {
+            Ok(read) => {
+                packet.commit(read);
+                Poll::Ready(Some(Ok(packet)))
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotConnected => {
+                this.done = true;
+                Poll::Ready(None)
+            }
+            Err(err) => Poll::Ready(Some(Err(err))),
+        }
+    }
+}
+
+/// A [Sink] of the packets to send via an async queue or device, created via calls such as
+/// [`TokioQueue::sink()`][crate::TokioQueue::sink()].
+///
+/// Packets are accepted as any buffer type, such as a `PooledPacket` received from a
+/// `PacketStream`, or a `Bytes`, which is dropped once sent. A single packet is
+/// sent at a time, so flushing the sink only waits on the packet currently being sent.
+pub struct PacketSink<'a, T> {
+    io: &'a (dyn PollPacket + Sync),
+    pending: Option<T>,
+    load: Option<Load>,
+}
+
+impl<'a, T> PacketSink<'a, T> {
+    pub(crate) fn new(io: &'a (dyn PollPacket + Sync)) -> Self {
+        Self {
+            io,
+            pending: None,
+            load: None,
+        }
+    }
+}
+
+impl<T: AsRef<[u8]>> PacketSink<'_, T> {
+    fn poll_pending(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if let Some(pending) = self.pending.as_ref() {
+            let res = ready!(self
+                .io
+                .poll_send_packet(cx, &mut self.load, pending.as_ref()));
+            self.pending = None;
+            res?;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+// The pending packet is never pinned.
+impl<T> Unpin for PacketSink<'_, T> {}
+
+impl<T: AsRef<[u8]>> Sink<T> for PacketSink<'_, T> {
+    type Error = io::Error;
+
+    #[inline]
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_pending(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, packet: T) -> io::Result<()> {
+        self.pending = Some(packet);
+        Ok(())
+    }
+
+    #[inline]
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_pending(cx)
+    }
+
+    #[inline]
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_pending(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    use futures_util::task::noop_waker_ref;
+    use futures_util::{FutureExt, SinkExt};
+
+    #[derive(Default)]
+    struct MockIo {
+        inbound: Mutex<VecDeque<Poll<io::Result<&'static [u8]>>>>,
+        sent: Mutex<Vec<Vec<u8>>>,
+    }
+
+    impl PollPacket for MockIo {
+        fn poll_recv_packet(&self, _: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+            let next = self.inbound.lock().unwrap().pop_front();
+            match next.unwrap_or(Poll::Ready(Err(io::ErrorKind::NotConnected.into()))) {
+                Poll::Ready(Ok(packet)) => {
+                    buf[..packet.len()].copy_from_slice(packet);
+                    Poll::Ready(Ok(packet.len()))
+                }
+                Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+                Poll::Pending => Poll::Pending,
+            }
+        }
+
+        fn poll_send_packet(
+            &self,
+            _: &mut Context<'_>,
+            _: &mut Option<Load>,
+            packet: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.sent.lock().unwrap().push(packet.to_vec());
+            match packet {
+                b"fail" => Poll::Ready(Err(io::ErrorKind::BrokenPipe.into())),
+                _ => Poll::Ready(Ok(packet.len())),
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "pool-impl")]
+    fn test_stream() {
+        use futures_util::StreamExt;
+
+        let io = MockIo::default();
+        io.inbound
+            .lock()
+            .unwrap()
+            .extend([Poll::Pending, Poll::Ready(Ok(&b"ab"[..]))]);
+        let mut stream = PacketStream::new(&io, PacketPool::new(1, 4, 0));
+
+        // The buffer is held on to while waiting for the packet.
+        let mut cx = Context::from_waker(noop_waker_ref());
+        assert!(stream.poll_next_unpin(&mut cx).is_pending());
+        assert_eq!(0, stream.pool().available());
+        let packet = match stream.poll_next_unpin(&mut cx) {
+            Poll::Ready(Some(Ok(packet))) => packet,
+            _ => panic!("expected a packet"),
+        };
+        assert_eq!(b"ab", &packet[..]);
+        assert_eq!(0, stream.pool().available());
+        drop(packet);
+        assert_eq!(1, stream.pool().available());
+        assert!(matches!(stream.poll_next_unpin(&mut cx), Poll::Ready(None)));
+        assert!(matches!(stream.poll_next_unpin(&mut cx), Poll::Ready(None)));
+        assert_eq!(1, stream.pool().available());
+    }
+
+    #[test]
+    fn test_sink() {
+        let io = MockIo::default();
+        let mut sink = PacketSink::new(&io);
+
+        let mut cx = Context::from_waker(noop_waker_ref());
+        let mut send = sink.send(&b"one"[..]);
+        assert!(matches!(send.poll_unpin(&mut cx), Poll::Ready(Ok(()))));
+        drop(send);
+        let mut send = sink.feed(&b"fail"[..]);
+        assert!(matches!(send.poll_unpin(&mut cx), Poll::Ready(Ok(()))));
+        drop(send);
+        match sink.poll_flush_unpin(&mut cx) {
+            Poll::Ready(Err(err)) => assert_eq!(io::ErrorKind::BrokenPipe, err.kind()),
+            _ => panic!("expected the failed send to be reported"),
+        }
+        assert!(matches!(
+            sink.poll_flush_unpin(&mut cx),
+            Poll::Ready(Ok(()))
+        ));
+        assert_eq!(
+            vec![b"one".to_vec(), b"fail".to_vec()],
+            *io.sent.lock().unwrap()
+        );
+    }
+}
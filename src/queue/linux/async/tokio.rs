@@ -3,7 +3,7 @@
 
 use super::*;
 
-use std::io;
+use std::io::{self, IoSlice, IoSliceMut};
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
@@ -18,41 +18,86 @@ use tokio::io::{AsyncRead, AsyncWrite};
 ///
 /// This also implements both the [AsyncRead] and [AsyncWrite] enabling simple integration with the
 /// greater ecosystem.
-pub struct TokioQueue(AsyncFd<Queue>);
+///
+/// Once shutdown or closed all operations fail with [Error::QueueClosed], converted into an
+/// [io::Error] of kind [`NotConnected`][std::io::ErrorKind::NotConnected] where necessary.
+pub struct TokioQueue {
+    inner: Option<AsyncFd<Queue>>,
+    shutdown: Shutdown,
+}
 
 impl TokioQueue {
-    /// Open a new async Queue based on the supplied [IfReq], exposing async capability for the
-    /// tokio ecosystem.
-    pub(crate) fn open(req: &IfReq) -> Result<Self> {
-        let queue = Queue::open(req)?;
+    /// Wrap the supplied [Queue], exposing async capability for the tokio ecosystem.
+    pub(crate) fn new(queue: Queue) -> Result<Self> {
         queue.set_non_blocking(true)?;
-        let async_fd = AsyncFd::new(queue)?;
-        Ok(Self(async_fd))
+        let async_fd = crate::async_fd::new(queue)?;
+        Ok(Self {
+            inner: Some(async_fd),
+            shutdown: Shutdown::default(),
+        })
     }
 
-    /// Close the internal queue destroying this instance completely.
+    /// Shutdown the queue, waking all pending operations with an error and failing any
+    /// subsequent ones. Unlike [`TokioQueue::close()`] this only requires a shared reference, so it
+    /// can be used to stop tasks blocked reading from or writing to a shared queue. The underlying
+    /// file descriptor remains open until the queue is closed or dropped.
     #[inline]
+    pub fn shutdown(&self) {
+        self.shutdown.trigger();
+    }
+
+    /// Close the internal queue destroying this instance completely. The queue is shutdown, and
+    /// deregistered from the `tokio` reactor, prior to closing the underlying file descriptor.
+    /// Closing an already closed queue is a no-op.
     pub fn close(&mut self) -> Result<()> {
-        self.0.get_mut().close()
+        self.shutdown.trigger();
+        // Make sure the placeholder exposed via `get_ref()` is available before letting go of
+        // the queue, see `Queue::close()`.
+        if self.inner.is_some() {
+            closed_fd()?;
+        }
+        match self.inner.take() {
+            Some(async_fd) => async_fd.into_inner().close(),
+            None => Ok(()),
+        }
+    }
+
+    fn inner(&self) -> io::Result<&AsyncFd<Queue>> {
+        self.shutdown.check()?;
+        self.inner
+            .as_ref()
+            .ok_or_else(|| Error::QueueClosed.into_io())
+    }
+
+    /// Retrieve the inner [AsyncFd] for a poll based operation, registering the current task to
+    /// be woken by a shutdown.
+    fn poll_inner(&self, cx: &Context<'_>, direction: Direction) -> io::Result<&AsyncFd<Queue>> {
+        self.shutdown.poll_check(cx, direction)?;
+        self.inner
+            .as_ref()
+            .ok_or_else(|| Error::QueueClosed.into_io())
     }
 
     /// Wrapper around the internal [AsyncFd] structs [`AsyncFd::readable()`] call.
     #[inline]
     pub async fn readable(&self) -> io::Result<AsyncFdReadyGuard<'_, Queue>> {
-        self.0.readable().await
+        let inner = self.inner()?;
+        self.shutdown.guard(inner.readable()).await
     }
 
     /// Wrapper around the internal [AsyncFd] structs [`AsyncFd::writable()`] call.
     #[inline]
     pub async fn writable(&self) -> io::Result<AsyncFdReadyGuard<'_, Queue>> {
-        self.0.writable().await
+        let inner = self.inner()?;
+        self.shutdown.guard(inner.writable()).await
     }
 
     /// Return a reference to the internal [Queue]. This is generally used, when it's necessary
-    /// to interact with the underlying [`Queue::recv()`] or [`Queue::send()`] methods.
+    /// to interact with the underlying [`Queue::recv()`] or [`Queue::send()`] methods. Once this
+    /// queue is closed the returned [Queue] is closed as well.
     #[inline]
     pub fn get_ref(&self) -> &Queue {
-        self.0.get_ref()
+        self.inner.as_ref().map(AsyncFd::get_ref).unwrap_or(&CLOSED)
     }
 
     /// Asynchrounously read a datagram off the underlying queue. Looping over [`Queue::recv()`] calls
@@ -64,13 +109,153 @@ impl TokioQueue {
     /// # Errors
     /// On any error it should be assumed that no usable data was read into the buffer.
     pub async fn recv(&self, datagram: &mut [u8]) -> io::Result<usize> {
-        loop {
-            let mut guard = self.0.readable().await?;
-            match guard.try_io(|queue| queue.get_ref().recv(datagram)) {
-                Ok(res) => return res,
-                Err(_) => continue,
-            };
-        }
+        let inner = self.inner()?;
+        self.shutdown
+            .guard(async {
+                loop {
+                    let mut guard = inner.readable().await?;
+                    match guard.try_io(|queue| queue.get_ref().recv(datagram)) {
+                        Ok(res) => return res,
+                        Err(_) => continue,
+                    };
+                }
+            })
+            .await
+    }
+
+    /// Asynchronously read a single packet into a buffer taken from the supplied [PacketPool],
+    /// see [`TokioQueue::recv()`] and [`Queue::recv_pooled()`] for more details.
+    ///
+    /// # Errors
+    /// On any error the buffer is returned to the pool.
+    #[cfg(feature = "pool-impl")]
+    pub async fn recv_pooled(&self, pool: &PacketPool) -> io::Result<PooledPacket> {
+        let mut packet = pool.get();
+        let read = self.recv(packet.spare()).await?;
+        packet.commit(read);
+        Ok(packet)
+    }
+
+    /// Create a [PacketStream] of the packets received by this queue, each received into a
+    /// buffer taken from the supplied [PacketPool] as per [`TokioQueue::recv_pooled()`].
+    #[cfg(feature = "pool-impl")]
+    pub fn stream(&self, pool: PacketPool) -> PacketStream<'_> {
+        PacketStream::new(self, pool)
+    }
+
+    /// Create a [PacketSink] sending each packet via this queue as per [`TokioQueue::send()`].
+    pub fn sink<'a, T>(&'a self) -> PacketSink<'a, T>
+    where
+        T: AsRef<[u8]>,
+    {
+        PacketSink::new(self)
+    }
+
+    /// Asynchronously read a packet off the underlying queue, analogous to
+    /// [`TokioQueue::recv()`], returning its [RecvMeta] reporting the supplied queue index.
+    pub(crate) async fn recv_meta(&self, idx: usize, datagram: &mut [u8]) -> io::Result<RecvMeta> {
+        let inner = self.inner()?;
+        self.shutdown
+            .guard(async {
+                loop {
+                    let mut guard = inner.readable().await?;
+                    match guard.try_io(|queue| queue.get_ref().recv_meta(idx, datagram)) {
+                        Ok(res) => return res,
+                        Err(_) => continue,
+                    };
+                }
+            })
+            .await
+    }
+
+    /// Asynchronously read a single packet off the underlying queue, scattering it across the
+    /// supplied buffers in order, see [`Queue::recv_vectored()`] for more details. Upon success the
+    /// number of bytes read is returned.
+    ///
+    /// # Errors
+    /// On any error it should be assumed that no usable data was read into the buffers.
+    pub async fn recv_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        let inner = self.inner()?;
+        self.shutdown
+            .guard(async {
+                loop {
+                    let mut guard = inner.readable().await?;
+                    match guard.try_io(|queue| queue.get_ref().recv_vectored(bufs)) {
+                        Ok(res) => return res,
+                        Err(_) => continue,
+                    };
+                }
+            })
+            .await
+    }
+
+    /// Asynchronously write a single packet gathered from the supplied buffers to the underlying
+    /// queue, see [`Queue::send_vectored()`] for more details. Upon success the number of bytes
+    /// sent is returned.
+    ///
+    /// # Errors
+    /// On any error it should be assumed that the buffers were partially sent.
+    pub async fn send_vectored(&self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        let inner = self.inner()?;
+        self.shutdown
+            .guard(async {
+                loop {
+                    let mut guard = inner.writable().await?;
+                    match guard.try_io(|queue| queue.get_ref().send_vectored(bufs)) {
+                        Ok(res) => return res,
+                        Err(_) => continue,
+                    };
+                }
+            })
+            .await
+    }
+
+    /// Asynchronously read as many packets as are available off the underlying queue into the
+    /// supplied batch, replacing its previous contents, see [`Queue::recv_batch()`] for more
+    /// details. This waits for readiness once, then drains packets until either the batch is full
+    /// or the queue has no more packets waiting. Upon success the number of packets read is
+    /// returned, which is at least `1` for a non-empty batch.
+    ///
+    /// # Errors
+    /// Errors are returned even if packets were already read into the batch, as with
+    /// [`Queue::recv_batch()`], on any error it should be assumed that no usable data was read.
+    pub async fn recv_batch(&self, batch: &mut PacketBatch) -> io::Result<usize> {
+        let inner = self.inner()?;
+        self.shutdown
+            .guard(async {
+                loop {
+                    let mut guard = inner.readable().await?;
+                    match guard.try_io(|queue| queue.get_ref().recv_batch_int(batch, false)) {
+                        Ok(res) => return res,
+                        Err(_) => continue,
+                    };
+                }
+            })
+            .await
+    }
+
+    /// Asynchronously write every packet of the supplied batch to the underlying queue, in order,
+    /// waiting for readiness only when the queue would block. Upon success the number of packets
+    /// sent is returned, which is the length of the batch.
+    ///
+    /// # Errors
+    /// On any error it should be assumed that part of the batch was sent.
+    pub async fn send_batch(&self, batch: &PacketBatch) -> io::Result<usize> {
+        let inner = self.inner()?;
+        self.shutdown
+            .guard(async {
+                let mut next = 0;
+                while next < batch.len() {
+                    let mut guard = inner.writable().await?;
+                    if let Ok(res) =
+                        guard.try_io(|queue| queue.get_ref().send_batch_int(batch, next))
+                    {
+                        next = res?;
+                    }
+                }
+                Ok(next)
+            })
+            .await
     }
 
     /// Asynchrounously write a datagram to the underlying queue. Looping over [`Queue::send()`] calls
@@ -82,10 +267,61 @@ impl TokioQueue {
     /// # Errors
     /// On any error it should be assumed that the buffer was partially sent.
     pub async fn send(&self, datagram: &[u8]) -> io::Result<usize> {
+        let inner = self.inner()?;
+        self.shutdown
+            .guard(async {
+                loop {
+                    let mut guard = inner.writable().await?;
+                    match guard.try_io(|queue| queue.get_ref().send(datagram)) {
+                        Ok(res) => return res,
+                        Err(_) => continue,
+                    };
+                }
+            })
+            .await
+    }
+
+    /// Attempt to read a single packet into the supplied buffer, registering the current task
+    /// for wakeup if the queue isn't ready for reading. Upon success the packet is appended to
+    /// the filled portion of the buffer.
+    ///
+    /// A pending call is woken by a shutdown, although only the last task to poll for reading is
+    /// woken, as with the readiness of the queue itself.
+    pub fn poll_recv(
+        &self,
+        cx: &mut Context<'_>,
+        datagram: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let inner = self.poll_inner(cx, Direction::Read)?;
         loop {
-            let mut guard = self.0.writable().await?;
+            let mut guard = ready!(inner.poll_read_ready(cx))?;
+            let unfilled = unsafe { datagram.unfilled_mut() };
+            match guard.try_io(|queue| queue.get_ref().recv_uninit(unfilled)) {
+                Ok(res) => match res {
+                    Ok(read) => {
+                        unsafe { datagram.assume_init(read) };
+                        datagram.advance(read);
+                        return Poll::Ready(Ok(()));
+                    }
+                    Err(err) => return Poll::Ready(Err(err)),
+                },
+                Err(_) => continue,
+            }
+        }
+    }
+
+    /// Attempt to write a single packet from the supplied buffer, registering the current task
+    /// for wakeup if the queue isn't ready for writing. Upon success the number of bytes sent is
+    /// returned.
+    ///
+    /// A pending call is woken by a shutdown, although only the last task to poll for writing is
+    /// woken, as with the readiness of the queue itself.
+    pub fn poll_send(&self, cx: &mut Context<'_>, datagram: &[u8]) -> Poll<io::Result<usize>> {
+        let inner = self.poll_inner(cx, Direction::Write)?;
+        loop {
+            let mut guard = ready!(inner.poll_write_ready(cx))?;
             match guard.try_io(|queue| queue.get_ref().send(datagram)) {
-                Ok(res) => return res,
+                Ok(res) => return Poll::Ready(res),
                 Err(_) => continue,
             };
         }
@@ -93,58 +329,77 @@ impl TokioQueue {
 }
 
 impl AsyncWrite for TokioQueue {
+    #[inline]
     fn poll_write(
         self: Pin<&mut Self>,
         cx: &mut Context<'_>,
         datagram: &[u8],
     ) -> Poll<io::Result<usize>> {
+        self.poll_send(cx, datagram)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let inner = self.poll_inner(cx, Direction::Write)?;
         loop {
-            let mut guard = ready!(self.0.poll_write_ready(cx))?;
-            match guard.try_io(|queue| queue.get_ref().send(datagram)) {
+            let mut guard = ready!(inner.poll_write_ready(cx))?;
+            match guard.try_io(|queue| queue.get_ref().send_vectored(bufs)) {
                 Ok(res) => return Poll::Ready(res),
                 Err(_) => continue,
             };
         }
     }
 
+    #[inline]
+    fn is_write_vectored(&self) -> bool {
+        true
+    }
+
     fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
         // Flushing is a no-op on a char device.
         Poll::Ready(Ok(()))
     }
 
     fn poll_shutdown(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
-        self.0.get_mut().close().map_err(|err| err.into_io())?;
-        Poll::Ready(Ok(()))
+        Poll::Ready(self.close().map_err(Error::into_io))
     }
 }
 
 impl AsyncRead for TokioQueue {
+    #[inline]
     fn poll_read(
         self: Pin<&mut Self>,
         cx: &mut Context<'_>,
         datagram: &mut ReadBuf<'_>,
     ) -> Poll<io::Result<()>> {
-        loop {
-            let mut guard = ready!(self.0.poll_read_ready(cx))?;
-            let unfilled = unsafe { datagram.unfilled_mut() };
-            match guard.try_io(|queue| queue.get_ref().recv_uninit(unfilled)) {
-                Ok(res) => match res {
-                    Ok(read) => {
-                        unsafe { datagram.assume_init(read) };
-                        datagram.advance(read);
-                        return Poll::Ready(Ok(()));
-                    }
-                    Err(err) => return Poll::Ready(Err(err)),
-                },
-                Err(_) => continue,
-            }
-        }
+        self.poll_recv(cx, datagram)
+    }
+}
+
+impl PollPacket for TokioQueue {
+    fn poll_recv_packet(&self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        let mut buf = ReadBuf::new(buf);
+        ready!(self.poll_recv(cx, &mut buf))?;
+        Poll::Ready(Ok(buf.filled().len()))
+    }
+
+    #[inline]
+    fn poll_send_packet(
+        &self,
+        cx: &mut Context<'_>,
+        _: &mut Option<Load>,
+        packet: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_send(cx, packet)
     }
 }
 
-impl Opener for TokioQueue {
+impl FromQueue for TokioQueue {
     #[inline]
-    fn open(req: &IfReq) -> Result<Self> {
-        Self::open(req)
+    fn from_queue(queue: Queue) -> Result<Self> {
+        Self::new(queue)
     }
 }
@@ -0,0 +1,89 @@
+// (c) Copyright 2021 Christian Saide
+// SPDX-License-Identifier: MIT
+
+use super::*;
+
+use std::fs::File as StdFile;
+use std::io;
+use std::os::unix::io::{FromRawFd, IntoRawFd};
+
+use tokio_uring::buf::{IoBuf, IoBufMut};
+use tokio_uring::fs::File;
+use tokio_uring::BufResult;
+
+/// An async wrapper around the [Queue] object driven by the `io_uring` instance of the current
+/// `tokio-uring` runtime, which must be used from within [`tokio_uring::start()`].
+///
+/// Following the `tokio-uring` ownership model every operation takes ownership of the supplied
+/// buffer, returning it alongside the result once the kernel has released it.
+///
+/// ```no_run
+/// use riptun::{Tun, TokioUringQueue};
+///
+/// tokio_uring::start(async {
+///     let tun = Tun::new("rip%d", 1).expect("failed to create device");
+///     let queue = tun.into_iter().next().unwrap();
+///     let queue = TokioUringQueue::new(queue).expect("failed to wrap queue");
+///
+///     let mut buf = vec![0; 1500];
+///     loop {
+///         let (res, packet) = queue.recv(buf).await;
+///         let read = res.expect("failed to receive packet");
+///         println!("Packet: {:?}", &packet[..read]);
+///         buf = packet;
+///     }
+/// });
+/// ```
+pub struct TokioUringQueue {
+    file: File,
+}
+
+impl TokioUringQueue {
+    /// Wrap the supplied [Queue], exposing async capability via the `tokio-uring` runtime.
+    pub fn new(queue: Queue) -> Result<Self> {
+        if queue.is_closed() {
+            return Err(Error::QueueClosed);
+        }
+        // The ring completes operations asynchronously on our behalf, so a non-blocking queue
+        // would only produce spurious `EAGAIN` completions.
+        queue.set_non_blocking(false)?;
+        let file = unsafe { StdFile::from_raw_fd(queue.into_raw_fd()) };
+        Ok(Self {
+            file: File::from_std(file),
+        })
+    }
+
+    /// Receive a single packet into the supplied buffer, returning the number of bytes read
+    /// along with the buffer.
+    #[inline]
+    pub async fn recv<T: IoBufMut>(&self, buf: T) -> BufResult<usize, T> {
+        self.file.read_at(buf, 0).await
+    }
+
+    /// Send the packet held by the supplied buffer, returning the number of bytes written along
+    /// with the buffer.
+    #[inline]
+    pub async fn send<T: IoBuf>(&self, buf: T) -> BufResult<usize, T> {
+        self.file.write_at(buf, 0).await
+    }
+
+    /// Receive a single packet scattered across the supplied buffers in order, returning the
+    /// total number of bytes read along with the buffers.
+    #[inline]
+    pub async fn recv_vectored<T: IoBufMut>(&self, bufs: Vec<T>) -> BufResult<usize, Vec<T>> {
+        self.file.readv_at(bufs, 0).await
+    }
+
+    /// Send a single packet gathered from the supplied buffers in order, returning the total
+    /// number of bytes written along with the buffers.
+    #[inline]
+    pub async fn send_vectored<T: IoBuf>(&self, bufs: Vec<T>) -> BufResult<usize, Vec<T>> {
+        self.file.writev_at(bufs, 0).await
+    }
+
+    /// Close the queue, waiting for the underlying file descriptor to be released.
+    #[inline]
+    pub async fn close(self) -> io::Result<()> {
+        self.file.close().await
+    }
+}
@@ -0,0 +1,141 @@
+// (c) Copyright 2021 Christian Saide
+// SPDX-License-Identifier: MIT
+
+/// A reusable arena holding a batch of packets, used to receive or send many packets per
+/// readiness notification via [`Queue::recv_batch()`][crate::Queue::recv_batch()] and
+/// [`Queue::send_batch()`][crate::Queue::send_batch()].
+///
+/// The arena is allocated once up front as a single contiguous buffer, with a fixed size slot
+/// for each packet, so the batch can be refilled indefinitely without further allocation.
+///
+/// ```
+/// use riptun::PacketBatch;
+///
+/// let mut batch = PacketBatch::new(32, 1500);
+/// assert!(batch.push(b"first"));
+/// assert!(batch.push(b"second"));
+/// assert_eq!(vec![&b"first"[..], &b"second"[..]], batch.iter().collect::<Vec<_>>());
+/// ```
+#[derive(Debug, Clone)]
+pub struct PacketBatch {
+    buf: Vec<u8>,
+    lens: Vec<usize>,
+    capacity: usize,
+    packet_size: usize,
+}
+
+impl PacketBatch {
+    /// Create a new empty batch holding at most `capacity` packets of at most `packet_size` bytes
+    /// each. Received packets larger than the packet size are truncated, so it should account
+    /// for the MTU of the device, along with any enabled headers.
+    pub fn new(capacity: usize, packet_size: usize) -> Self {
+        Self {
+            buf: vec![0; capacity * packet_size],
+            lens: Vec::with_capacity(capacity),
+            capacity,
+            packet_size,
+        }
+    }
+
+    /// Return the maximum number of packets this batch holds.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Return the maximum size of each packet in this batch.
+    #[inline]
+    pub fn packet_size(&self) -> usize {
+        self.packet_size
+    }
+
+    /// Return the number of packets in this batch.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.lens.len()
+    }
+
+    /// Return whether this batch holds no packets.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.lens.is_empty()
+    }
+
+    /// Return whether this batch holds as many packets as it can.
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.lens.len() == self.capacity
+    }
+
+    /// Remove all packets from this batch, retaining the arena for reuse.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.lens.clear();
+    }
+
+    /// Append a copy of the supplied packet to this batch. If the batch is full, or the packet
+    /// exceeds the packet size of the batch, this returns `false` leaving the batch unchanged.
+    pub fn push(&mut self, packet: &[u8]) -> bool {
+        if self.is_full() || packet.len() > self.packet_size {
+            return false;
+        }
+        self.spare()[..packet.len()].copy_from_slice(packet);
+        self.commit(packet.len());
+        true
+    }
+
+    /// Retrieve the packet at the supplied index, if in bounds.
+    pub fn get(&self, idx: usize) -> Option<&[u8]> {
+        let len = *self.lens.get(idx)?;
+        let start = idx * self.packet_size;
+        Some(&self.buf[start..start + len])
+    }
+
+    /// Retrieve a mutable reference to the packet at the supplied index, if in bounds.
+    pub fn get_mut(&mut self, idx: usize) -> Option<&mut [u8]> {
+        let len = *self.lens.get(idx)?;
+        let start = idx * self.packet_size;
+        Some(&mut self.buf[start..start + len])
+    }
+
+    /// Iterate over the packets in this batch, in the order they were received or pushed.
+    pub fn iter(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        (0..self.len()).filter_map(move |idx| self.get(idx))
+    }
+
+    /// Return the slot of the next packet, which must only be called if the batch isn't full.
+    pub(super) fn spare(&mut self) -> &mut [u8] {
+        let start = self.len() * self.packet_size;
+        &mut self.buf[start..start + self.packet_size]
+    }
+
+    /// Append the packet of the supplied length written to the slot returned by `spare()`.
+    pub(super) fn commit(&mut self, len: usize) {
+        self.lens.push(len.min(self.packet_size));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch() {
+        let mut batch = PacketBatch::new(2, 4);
+        assert!(batch.is_empty());
+        assert!(!batch.push(b"large"));
+        assert!(batch.push(b"one"));
+        assert!(batch.push(b"two!"));
+        assert!(batch.is_full());
+        assert!(!batch.push(b"3"));
+
+        batch.get_mut(0).unwrap()[0] = b'O';
+        assert_eq!(Some(&b"One"[..]), batch.get(0));
+        assert_eq!(None, batch.get(2));
+
+        batch.clear();
+        assert_eq!(0, batch.len());
+        batch.commit(9000);
+        assert_eq!(Some(4), batch.get(0).map(<[u8]>::len));
+    }
+}
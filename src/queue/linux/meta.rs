@@ -0,0 +1,184 @@
+// (c) Copyright 2021 Christian Saide
+// SPDX-License-Identifier: MIT
+
+use std::convert::TryInto;
+
+/// Set by the kernel in [`PacketInfo::flags`] when a packet did not fit the supplied buffer.
+const TUN_PKT_STRIP: u16 = 0x0001;
+
+/// The optional headers the kernel prepends to every packet read off a queue, and expects
+/// prepended to every packet written to it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Headers {
+    pub(crate) packet_info: bool,
+    pub(crate) vnet_hdr: bool,
+}
+
+impl Headers {
+    /// No headers, the default for all devices.
+    pub(crate) const NONE: Self = Self {
+        packet_info: false,
+        vnet_hdr: false,
+    };
+
+    /// Return the combined length of the enabled headers.
+    pub(crate) fn len(self) -> usize {
+        let mut len = 0;
+        if self.packet_info {
+            len += PacketInfo::LEN;
+        }
+        if self.vnet_hdr {
+            len += VnetHdr::LEN;
+        }
+        len
+    }
+}
+
+/// The packet information header (`struct tun_pi`) prepended to packets when enabled via
+/// [`TunBuilder::packet_info()`][crate::TunBuilder::packet_info()].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PacketInfo {
+    /// The packet flags, of which the kernel only sets `TUN_PKT_STRIP` to signal truncation.
+    pub flags: u16,
+    /// The ethernet protocol of the packet, for instance `0x0800` for IPv4, in host byte order.
+    pub proto: u16,
+}
+
+impl PacketInfo {
+    /// The length of the header on the wire.
+    pub const LEN: usize = 4;
+
+    fn parse(data: &[u8; Self::LEN]) -> Self {
+        Self {
+            flags: u16::from_ne_bytes([data[0], data[1]]),
+            proto: u16::from_be_bytes([data[2], data[3]]),
+        }
+    }
+
+    /// Return whether the kernel reported the packet as truncated.
+    #[inline]
+    pub fn is_truncated(&self) -> bool {
+        self.flags & TUN_PKT_STRIP != 0
+    }
+}
+
+/// The virtio net header (`struct virtio_net_hdr`) prepended to packets when enabled via
+/// [`TunBuilder::vnet_hdr()`][crate::TunBuilder::vnet_hdr()], describing checksum and
+/// segmentation offload state. Fields are in host byte order.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct VnetHdr {
+    /// The header flags, such as `VIRTIO_NET_HDR_F_NEEDS_CSUM`.
+    pub flags: u8,
+    /// The segmentation offload type, such as `VIRTIO_NET_HDR_GSO_TCPV4`.
+    pub gso_type: u8,
+    /// The length of the protocol headers of the packet.
+    pub hdr_len: u16,
+    /// The size of each segment when segmentation offload is in use.
+    pub gso_size: u16,
+    /// The offset the checksum calculation starts at.
+    pub csum_start: u16,
+    /// The offset from `csum_start` the checksum is stored at.
+    pub csum_offset: u16,
+}
+
+impl VnetHdr {
+    /// The length of the header on the wire.
+    pub const LEN: usize = 10;
+
+    fn parse(data: &[u8; Self::LEN]) -> Self {
+        let u16_at = |idx: usize| u16::from_ne_bytes([data[idx], data[idx + 1]]);
+        Self {
+            flags: data[0],
+            gso_type: data[1],
+            hdr_len: u16_at(2),
+            gso_size: u16_at(4),
+            csum_start: u16_at(6),
+            csum_offset: u16_at(8),
+        }
+    }
+}
+
+/// The metadata of a packet read off a device, see [`Tun::recv_meta()`][crate::Tun::recv_meta()].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RecvMeta {
+    /// The index of the queue the packet was read off, which can be used to send replies via
+    /// the same queue to retain flow affinity.
+    pub queue: usize,
+    /// The number of bytes of the packet written to the supplied buffer, excluding any headers.
+    pub len: usize,
+    /// Whether the packet was larger than the supplied buffer, in which case only the first
+    /// `len` bytes were retained.
+    pub truncated: bool,
+    /// The packet information header, if enabled for the device.
+    pub packet_info: Option<PacketInfo>,
+    /// The virtio net header, if enabled for the device.
+    pub vnet_hdr: Option<VnetHdr>,
+}
+
+impl RecvMeta {
+    /// Build the metadata of a read of `read` bytes, whose headers were read into `hdrs` and
+    /// whose payload was read into a buffer of `capacity` bytes.
+    pub(crate) fn parse(
+        queue: usize,
+        headers: Headers,
+        hdrs: &[u8; PacketInfo::LEN + VnetHdr::LEN],
+        read: usize,
+        capacity: usize,
+    ) -> Self {
+        let mut offset = 0;
+        let mut header = |enabled: bool, len: usize| {
+            let data = &hdrs[offset..offset + len];
+            offset += len;
+            enabled.then_some(data)
+        };
+        let packet_info = header(headers.packet_info, PacketInfo::LEN)
+            .map(|data| PacketInfo::parse(data.try_into().unwrap()));
+        let vnet_hdr = header(headers.vnet_hdr, VnetHdr::LEN)
+            .map(|data| VnetHdr::parse(data.try_into().unwrap()));
+
+        // The kernel reports the full length of truncated packets, while other descriptors fill
+        // the trailing overflow byte, so either way the read exceeds the supplied buffer.
+        let payload = read.saturating_sub(headers.len());
+        Self {
+            queue,
+            len: payload.min(capacity),
+            truncated: payload > capacity || packet_info.is_some_and(|pi| pi.is_truncated()),
+            packet_info,
+            vnet_hdr,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let mut hdrs = [0u8; PacketInfo::LEN + VnetHdr::LEN];
+        hdrs[2..4].copy_from_slice(&0x86ddu16.to_be_bytes());
+        hdrs[5] = 4;
+        hdrs[8..10].copy_from_slice(&1400u16.to_ne_bytes());
+
+        let headers = Headers {
+            packet_info: true,
+            vnet_hdr: true,
+        };
+        let meta = RecvMeta::parse(3, headers, &hdrs, headers.len() + 100, 1500);
+        assert_eq!(3, meta.queue);
+        assert_eq!(100, meta.len);
+        assert!(!meta.truncated);
+        assert_eq!(0x86dd, meta.packet_info.unwrap().proto);
+        let vnet_hdr = meta.vnet_hdr.unwrap();
+        assert_eq!(4, vnet_hdr.gso_type);
+        assert_eq!(1400, vnet_hdr.gso_size);
+
+        hdrs[0..2].copy_from_slice(&TUN_PKT_STRIP.to_ne_bytes());
+        let meta = RecvMeta::parse(0, headers, &hdrs, headers.len() + 100, 1500);
+        assert!(meta.truncated);
+
+        let meta = RecvMeta::parse(1, Headers::NONE, &[0; 14], 1501, 1500);
+        assert_eq!((1500, true), (meta.len, meta.truncated));
+        assert_eq!(None, meta.packet_info);
+    }
+}
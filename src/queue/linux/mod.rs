@@ -5,27 +5,62 @@ use super::{Error, Result};
 
 use cfg_if::cfg_if;
 
+mod batch;
+mod meta;
 mod req;
 mod sync;
 
+pub use batch::PacketBatch;
+pub(crate) use meta::Headers;
+pub use meta::{PacketInfo, RecvMeta, VnetHdr};
 use req::IfReq;
+pub use req::Mode;
 pub use sync::Queue;
+#[cfg(any(feature = "async-std-impl", feature = "tokio-impl"))]
+use sync::{closed_fd, CLOSED};
 
-pub(crate) fn new_queues<T>(name: &str, num_queues: usize) -> Result<(Vec<T>, String)>
-where
-    T: Opener,
-{
-    let req = IfReq::new(name)?;
+pub(crate) fn new_queues(
+    name: &str,
+    mode: Mode,
+    headers: Headers,
+    num_queues: usize,
+) -> Result<(Vec<Queue>, String)> {
+    let req = IfReq::new(name, mode)?.with_headers(headers);
     let mut queues = Vec::with_capacity(num_queues);
     for _ in 0..num_queues {
-        let queue = T::open(&req)?;
+        let queue = Queue::open(&req)?;
         queues.push(queue);
     }
     Ok((queues, req.name()))
 }
 
-pub(crate) trait Opener: Sized {
-    fn open(req: &IfReq) -> Result<Self>;
+cfg_if! {
+    if #[cfg(feature = "pool-impl")] {
+        mod pool;
+        pub use pool::{PacketPool, PooledPacket};
+    }
+}
+
+/// Conversion from an opened blocking [Queue] into one of the queue flavours. This is performed
+/// on the calling thread, so that async queues register with the reactor of the caller.
+pub(crate) trait FromQueue: Sized {
+    fn from_queue(queue: Queue) -> Result<Self>;
+}
+
+cfg_if! {
+    if #[cfg(any(feature = "async-std-impl", feature = "tokio-impl"))] {
+        #[path = "async/shutdown.rs"]
+        mod shutdown;
+        pub(crate) use shutdown::{Direction, Shutdown};
+
+        #[path = "async/stream.rs"]
+        mod stream;
+        pub use stream::PacketSink;
+        pub(crate) use stream::PollPacket;
+        use crate::tun::Load;
+        #[cfg(feature = "pool-impl")]
+        pub use stream::PacketStream;
+    }
 }
 
 cfg_if! {
@@ -44,6 +79,30 @@ cfg_if! {
     }
 }
 
+cfg_if! {
+    if #[cfg(feature = "tokio-codec-impl")] {
+        #[path = "async/codec.rs"]
+        mod async_codec;
+        pub(crate) use self::async_codec::sealed;
+        pub use self::async_codec::{PacketTransport, TokioFramed};
+    }
+}
+
+cfg_if! {
+    if #[cfg(feature = "io-uring-impl")] {
+        mod uring;
+        pub use uring::UringQueue;
+    }
+}
+
+cfg_if! {
+    if #[cfg(feature = "tokio-uring-impl")] {
+        #[path = "async/tokio_uring.rs"]
+        mod async_tokio_uring;
+        pub use self::async_tokio_uring::TokioUringQueue;
+    }
+}
+
 cfg_if! {
     if #[cfg(feature = "mio-impl")] {
         #[path = "async/mio.rs"]
@@ -0,0 +1,348 @@
+// (c) Copyright 2021 Christian Saide
+// SPDX-License-Identifier: MIT
+
+use std::collections::VecDeque;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+
+use bytes::{Bytes, BytesMut};
+
+/// A pool of reusable packet buffers, allocated up front, used to receive packets via
+/// `recv_pooled()` calls such as [`Queue::recv_pooled()`][crate::Queue::recv_pooled()].
+///
+/// Each buffer reserves headroom ahead of the packet, so that encapsulation headers can be
+/// prepended in place via [`PooledPacket::prepend()`], and the packet then converted into a
+/// [BytesMut] or [Bytes] to be framed and forwarded without copying it. Buffers are returned to
+/// the pool once their [PooledPacket] is dropped. Buffers converted into a [BytesMut] or [Bytes]
+/// are reclaimed by the pool once every handle to the packet has been dropped, in the meantime
+/// the pool allocates a replacement on demand.
+///
+/// The pool is cheap to clone, with every clone sharing the same buffers.
+///
+/// ```
+/// use riptun::PacketPool;
+///
+/// let pool = PacketPool::new(64, 1500, 8);
+/// let mut packet = pool.get();
+/// assert_eq!(1500, packet.len());
+/// packet.truncate(4);
+/// assert!(packet.prepend(b"head"));
+/// assert_eq!(b"head\0\0\0\0", &packet.freeze()[..]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct PacketPool {
+    shared: Arc<Shared>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    buffers: Mutex<Buffers>,
+    capacity: usize,
+    packet_size: usize,
+    headroom: usize,
+}
+
+#[derive(Debug, Default)]
+struct Buffers {
+    /// Buffers ready to be taken from the pool.
+    free: Vec<BytesMut>,
+    /// Empty handles to the buffers of detached packets, which can be reclaimed once the
+    /// packets have been dropped, oldest first.
+    detached: VecDeque<BytesMut>,
+}
+
+impl Buffers {
+    #[inline]
+    fn len(&self) -> usize {
+        self.free.len() + self.detached.len()
+    }
+}
+
+impl Shared {
+    #[inline]
+    fn slot_size(&self) -> usize {
+        self.headroom + self.packet_size
+    }
+
+    /// Take a free buffer, or reclaim the oldest detached buffer if its packet has been dropped.
+    fn take(&self) -> Option<BytesMut> {
+        let mut buffers = self.buffers.lock().unwrap();
+        if let Some(buf) = buffers.free.pop() {
+            return Some(buf);
+        }
+
+        let mut buf = buffers.detached.pop_front()?;
+        if !buf.try_reclaim(self.slot_size()) {
+            // Check the next detached buffer on the following call, so a single long lived
+            // packet can't prevent the others from being reclaimed.
+            buffers.detached.push_back(buf);
+            return None;
+        }
+        // Every byte of the buffer was initialized when it was allocated, and the handle starts
+        // at the beginning of the buffer.
+        unsafe { buf.set_len(self.slot_size()) };
+        Some(buf)
+    }
+}
+
+impl PacketPool {
+    /// Create a new pool retaining at most `capacity` buffers, each holding a packet of at most
+    /// `packet_size` bytes preceded by `headroom` bytes. Received packets larger than the packet
+    /// size are truncated, so it should account for the MTU of the device, along with any
+    /// enabled headers.
+    pub fn new(capacity: usize, packet_size: usize, headroom: usize) -> Self {
+        // Each buffer is a separate allocation, so that it can be reclaimed once detached
+        // independently of the others.
+        let slot_size = headroom + packet_size;
+        let free = (0..capacity).map(|_| BytesMut::zeroed(slot_size)).collect();
+        Self {
+            shared: Arc::new(Shared {
+                buffers: Mutex::new(Buffers {
+                    free,
+                    detached: VecDeque::new(),
+                }),
+                capacity,
+                packet_size,
+                headroom,
+            }),
+        }
+    }
+
+    /// Return the maximum number of buffers retained by this pool.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.shared.capacity
+    }
+
+    /// Return the maximum size of each packet in this pool.
+    #[inline]
+    pub fn packet_size(&self) -> usize {
+        self.shared.packet_size
+    }
+
+    /// Return the headroom reserved ahead of each packet in this pool.
+    #[inline]
+    pub fn headroom(&self) -> usize {
+        self.shared.headroom
+    }
+
+    /// Return the number of buffers currently available in this pool, excluding detached buffers
+    /// pending reclamation.
+    pub fn available(&self) -> usize {
+        self.shared.buffers.lock().unwrap().free.len()
+    }
+
+    /// Take a buffer from this pool, allocating a new one if none are available. The returned
+    /// packet spans the full packet size, with all of its headroom available.
+    pub fn get(&self) -> PooledPacket {
+        let buf = self
+            .shared
+            .take()
+            .unwrap_or_else(|| BytesMut::zeroed(self.shared.slot_size()));
+        PooledPacket {
+            buf: Some(buf),
+            start: self.shared.headroom,
+            end: self.shared.slot_size(),
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+/// A packet buffer taken from a [PacketPool], which is returned to the pool once dropped. This
+/// dereferences to the packet itself, excluding any remaining headroom.
+#[derive(Debug)]
+pub struct PooledPacket {
+    buf: Option<BytesMut>,
+    start: usize,
+    end: usize,
+    shared: Arc<Shared>,
+}
+
+impl PooledPacket {
+    /// Return the remaining headroom ahead of the packet.
+    #[inline]
+    pub fn headroom(&self) -> usize {
+        self.start
+    }
+
+    /// Prepend a copy of the supplied header to the packet, consuming headroom. If the header
+    /// exceeds the remaining headroom, this returns `false` leaving the packet unchanged.
+    pub fn prepend(&mut self, header: &[u8]) -> bool {
+        if header.len() > self.start {
+            return false;
+        }
+        self.start -= header.len();
+        self[..header.len()].copy_from_slice(header);
+        true
+    }
+
+    /// Remove the first `cnt` bytes of the packet, for instance to strip its packet information
+    /// header, adding them to the headroom.
+    ///
+    /// # Panics
+    /// This panics if `cnt` exceeds the length of the packet.
+    pub fn advance(&mut self, cnt: usize) {
+        assert!(
+            cnt <= self.len(),
+            "cannot advance past the end of the packet"
+        );
+        self.start += cnt;
+    }
+
+    /// Shorten the packet to the supplied length, which has no effect if the packet is already
+    /// shorter.
+    pub fn truncate(&mut self, len: usize) {
+        self.end = self.end.min(self.start + len);
+    }
+
+    /// Detach the buffer from its pool, returning the packet as a [BytesMut] without copying it.
+    /// The pool reclaims the buffer once the returned packet, and anything split from or frozen
+    /// from it, has been dropped.
+    pub fn into_bytes_mut(mut self) -> BytesMut {
+        let mut buf = self.buf.take().unwrap();
+        // Retain an empty handle to the start of the buffer, through which it can be reclaimed.
+        let handle = buf.split_to(0);
+        let mut buffers = self.shared.buffers.lock().unwrap();
+        if buffers.len() < self.shared.capacity {
+            buffers.detached.push_back(handle);
+        }
+        drop(buffers);
+
+        buf.truncate(self.end);
+        let _ = buf.split_to(self.start);
+        buf
+    }
+
+    /// Detach the buffer from its pool, returning the packet as an immutable [Bytes] without
+    /// copying it. The pool reclaims the buffer once every clone of the packet has been dropped.
+    #[inline]
+    pub fn freeze(self) -> Bytes {
+        self.into_bytes_mut().freeze()
+    }
+
+    /// Return the full buffer following the headroom, which must only be called on a packet
+    /// freshly taken from its pool.
+    pub(super) fn spare(&mut self) -> &mut [u8] {
+        let headroom = self.shared.headroom;
+        &mut self.buf.as_mut().unwrap()[headroom..]
+    }
+
+    /// Set the packet to the first `len` bytes written to the buffer returned by `spare()`.
+    pub(super) fn commit(&mut self, len: usize) {
+        self.start = self.shared.headroom;
+        self.end = self.start + len.min(self.shared.packet_size);
+    }
+}
+
+impl Deref for PooledPacket {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        &self.buf.as_ref().unwrap()[self.start..self.end]
+    }
+}
+
+impl DerefMut for PooledPacket {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.buf.as_mut().unwrap()[self.start..self.end]
+    }
+}
+
+impl AsRef<[u8]> for PooledPacket {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl AsMut<[u8]> for PooledPacket {
+    #[inline]
+    fn as_mut(&mut self) -> &mut [u8] {
+        self
+    }
+}
+
+impl From<PooledPacket> for BytesMut {
+    #[inline]
+    fn from(packet: PooledPacket) -> Self {
+        packet.into_bytes_mut()
+    }
+}
+
+impl From<PooledPacket> for Bytes {
+    #[inline]
+    fn from(packet: PooledPacket) -> Self {
+        packet.freeze()
+    }
+}
+
+impl Drop for PooledPacket {
+    fn drop(&mut self) {
+        if let Some(buf) = self.buf.take() {
+            let mut buffers = self.shared.buffers.lock().unwrap();
+            if buffers.len() < self.shared.capacity {
+                buffers.free.push(buf);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pool() {
+        let pool = PacketPool::new(2, 8, 4);
+        let mut packet = pool.get();
+        assert_eq!(1, pool.available());
+        packet.spare().copy_from_slice(b"abcdefgh");
+        packet.commit(9000);
+        assert_eq!(b"abcdefgh", &packet[..]);
+
+        packet.commit(3);
+        assert_eq!(b"abc", &packet[..]);
+        assert!(!packet.prepend(b"12345"));
+        assert!(packet.prepend(b"12"));
+        assert_eq!((2, &b"12abc"[..]), (packet.headroom(), &packet[..]));
+        packet.advance(1);
+        packet.truncate(3);
+        assert_eq!(b"2ab", &packet[..]);
+        drop(packet);
+        assert_eq!(2, pool.available());
+
+        // Detached buffers aren't returned, with replacements allocated on demand until the
+        // detached packets have been dropped.
+        let packets = (0..3).map(|_| pool.get()).collect::<Vec<_>>();
+        assert_eq!(0, pool.available());
+        let mut bytes = packets
+            .into_iter()
+            .map(PooledPacket::freeze)
+            .collect::<Vec<_>>();
+        assert_eq!(0, pool.available());
+        assert_eq!(8, bytes[2].len());
+
+        let ptr = |packet: &[u8]| packet.as_ptr();
+        let replacement = pool.get();
+        assert!(bytes.iter().all(|bytes| ptr(bytes) != ptr(&replacement)));
+        // The pool already retains a detached buffer per slot, so the replacement is dropped.
+        drop(replacement);
+        assert_eq!(0, pool.available());
+
+        // Once released, detached buffers are reclaimed in place of allocating new ones.
+        let mut released = bytes
+            .drain(..2)
+            .map(|bytes| ptr(&bytes))
+            .collect::<Vec<_>>();
+        let packets = (0..3).map(|_| pool.get()).collect::<Vec<_>>();
+        let mut reclaimed = vec![ptr(&packets[0]), ptr(&packets[1])];
+        released.sort();
+        reclaimed.sort();
+        assert_eq!(released, reclaimed);
+        assert_eq!(8, packets[2].len());
+        drop(packets);
+        assert_eq!(2, pool.available());
+    }
+}
@@ -3,13 +3,40 @@
 
 use nix::libc;
 
-use super::{Error, Result};
+use super::{Error, Headers, Result};
 
 const IF_NAME_SIZE: usize = libc::IFNAMSIZ;
 const IFF_TUN: u16 = libc::IFF_TUN as u16;
+const IFF_TAP: u16 = libc::IFF_TAP as u16;
 const IFF_NO_PI: u16 = libc::IFF_NO_PI as u16;
 const IFF_MULTI_QUEUE: u16 = libc::IFF_MULTI_QUEUE as u16;
-const IFF_FLAGS: u16 = IFF_TUN | IFF_NO_PI | IFF_MULTI_QUEUE;
+const IFF_VNET_HDR: u16 = libc::IFF_VNET_HDR as u16;
+const IFF_FLAGS: u16 = IFF_NO_PI | IFF_MULTI_QUEUE;
+
+/// The type of virtual device to create, determining at which layer packets are
+/// exchanged with the kernel.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum Mode {
+    /// A layer 3 device exchanging raw IP packets.
+    #[default]
+    Tun,
+    /// A layer 2 device exchanging full ethernet frames.
+    Tap,
+}
+
+impl Mode {
+    fn flags(self) -> u16 {
+        match self {
+            Self::Tun => IFF_TUN,
+            Self::Tap => IFF_TAP,
+        }
+    }
+}
 
 #[derive(Debug)]
 #[repr(C)]
@@ -19,7 +46,7 @@ pub struct IfReq {
 }
 
 impl IfReq {
-    pub fn new(name_str: &str) -> Result<Self> {
+    pub fn new(name_str: &str, mode: Mode) -> Result<Self> {
         if name_str.is_empty() || !name_str.is_ascii() {
             return Err(Error::InvalidName {
                 max_size: IF_NAME_SIZE,
@@ -37,10 +64,28 @@ impl IfReq {
 
         Ok(Self {
             name,
-            flags: IFF_FLAGS,
+            flags: IFF_FLAGS | mode.flags(),
         })
     }
 
+    /// Enable the supplied optional packet headers on the queues opened using this request.
+    pub fn with_headers(mut self, headers: Headers) -> Self {
+        if headers.packet_info {
+            self.flags &= !IFF_NO_PI;
+        }
+        if headers.vnet_hdr {
+            self.flags |= IFF_VNET_HDR;
+        }
+        self
+    }
+
+    pub fn headers(&self) -> Headers {
+        Headers {
+            packet_info: self.flags & IFF_NO_PI == 0,
+            vnet_hdr: self.flags & IFF_VNET_HDR != 0,
+        }
+    }
+
     pub fn name(&self) -> String {
         self.name
             .iter()
@@ -55,8 +100,9 @@ mod tests {
     use super::*;
 
     #[test]
+    #[allow(clippy::assertions_on_constants)]
     fn test_empy_name() {
-        let req = IfReq::new("");
+        let req = IfReq::new("", Mode::Tun);
         assert!(req.is_err());
         match req.unwrap_err() {
             Error::InvalidName { .. } => assert!(true),
@@ -65,8 +111,9 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::assertions_on_constants)]
     fn test_utf_name() {
-        let req = IfReq::new("😀");
+        let req = IfReq::new("😀", Mode::Tun);
         assert!(req.is_err());
         match req.unwrap_err() {
             Error::InvalidName { .. } => assert!(true),
@@ -78,19 +125,43 @@ mod tests {
     fn test_long_name() {
         let input = "aaaaaaaaaaaaaaaaaaaaaaaa";
         let expected = "aaaaaaaaaaaaaaaa";
-        let req = IfReq::new(input);
+        let req = IfReq::new(input, Mode::Tun);
         assert!(req.is_ok());
         let req = req.unwrap();
-        assert_eq!(IFF_FLAGS, req.flags);
+        assert_eq!(IFF_FLAGS | IFF_TUN, req.flags);
         assert_eq!(expected, req.name());
     }
 
     #[test]
     fn test_happy_path() {
-        let req = IfReq::new("rip%d");
+        let req = IfReq::new("rip%d", Mode::Tun);
         assert!(req.is_ok());
         let req = req.unwrap();
-        assert_eq!(IFF_FLAGS, req.flags);
+        assert_eq!(IFF_FLAGS | IFF_TUN, req.flags);
         assert_eq!("rip%d", req.name());
     }
+
+    #[test]
+    fn test_tap_mode() {
+        let req = IfReq::new("tap%d", Mode::Tap);
+        assert!(req.is_ok());
+        let req = req.unwrap();
+        assert_eq!(IFF_FLAGS | IFF_TAP, req.flags);
+        assert_eq!(0, req.flags & IFF_TUN);
+        assert_eq!("tap%d", req.name());
+    }
+
+    #[test]
+    fn test_headers() {
+        let req = IfReq::new("rip%d", Mode::Tun).unwrap();
+        assert_eq!(Headers::NONE, req.headers());
+
+        let headers = Headers {
+            packet_info: true,
+            vnet_hdr: true,
+        };
+        let req = req.with_headers(headers);
+        assert_eq!(IFF_MULTI_QUEUE | IFF_VNET_HDR | IFF_TUN, req.flags);
+        assert_eq!(headers, req.headers());
+    }
 }
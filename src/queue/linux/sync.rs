@@ -1,16 +1,23 @@
 // (c) Copyright 2021 Christian Saide
 // SPDX-License-Identifier: MIT
 
-use super::{Error, IfReq, Opener, Result};
+use super::{Error, FromQueue, Headers, IfReq, PacketBatch, PacketInfo, RecvMeta, Result, VnetHdr};
+#[cfg(feature = "pool-impl")]
+use super::{PacketPool, PooledPacket};
 
-use nix::{fcntl::OFlag, libc};
+use nix::poll::{poll, PollFd, PollFlags};
+use nix::{errno::Errno, fcntl::OFlag, libc};
 
-use std::io::{self, Read, Write};
+use std::io::{self, IoSlice, IoSliceMut, Read, Write};
 use std::mem::MaybeUninit;
-use std::os::unix::prelude::{AsRawFd, RawFd};
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
+use std::sync::OnceLock;
 
 const PATH: &[u8] = b"/dev/net/tun\0";
 
+/// The maximum number of buffers accepted by `readv` and `writev`.
+const IOV_MAX: usize = libc::UIO_MAXIOV as usize;
+
 nix::ioctl_write_int!(create_queue, b'T', 202);
 
 #[cfg(target_pointer_width = "64")]
@@ -20,43 +27,156 @@ type PointerWidth = u32;
 #[cfg(target_pointer_width = "16")]
 type PointerWidth = u16;
 
-/// A raw TUN/TAP queue wrapping all I/O for both sync and async operations.
-#[derive(Clone)]
-pub struct Queue(RawFd);
+/// A raw TUN/TAP queue wrapping all I/O for both sync and async operations. The queue owns its
+/// underlying file descriptor, which is closed when the queue is dropped, if it wasn't already
+/// closed via [`Queue::close()`]. Use [`Queue::try_clone()`] to obtain a second handle to the same
+/// queue.
+///
+/// Once closed all operations on the queue fail with [`Error::QueueClosed`], while the raw
+/// descriptor accessors expose a shared placeholder descriptor, on which all I/O fails with
+/// `EBADF`.
+pub struct Queue {
+    fd: Option<OwnedFd>,
+    headers: Headers,
+}
+
+/// A permanently closed queue, exposed by the async queues once they have been closed.
+#[cfg(any(feature = "async-std-impl", feature = "tokio-impl"))]
+pub(super) static CLOSED: Queue = Queue {
+    fd: None,
+    headers: Headers::NONE,
+};
+
+/// Reject more buffers than a single `readv` or `writev` call accepts, rather than silently
+/// dropping the trailing buffers of the packet.
+fn check_iov_len(len: usize) -> io::Result<()> {
+    if len > IOV_MAX {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "more than IOV_MAX buffers supplied",
+        ));
+    }
+    Ok(())
+}
+
+/// Return the placeholder descriptor exposed by closed queues, an `O_PATH` descriptor on which all
+/// I/O fails with `EBADF`, and which epoll refuses to register. Unlike `-1`, which `poll` silently
+/// ignores, a caller waiting on the placeholder fails rather than blocking forever.
+///
+/// The placeholder is opened on first use, which fails if the process has run out of file
+/// descriptors, so queues open it before closing their own descriptor. Every closed queue can
+/// therefore rely on it being available.
+pub(super) fn closed_fd() -> Result<BorrowedFd<'static>> {
+    static CLOSED_FD: OnceLock<OwnedFd> = OnceLock::new();
+    if let Some(fd) = CLOSED_FD.get() {
+        return Ok(fd.as_fd());
+    }
+
+    let fd = unsafe {
+        libc::open(
+            b"/\0".as_ptr() as *const libc::c_char,
+            libc::O_PATH | libc::O_CLOEXEC,
+        )
+    };
+    if fd < 0 {
+        return Err(Error::errno());
+    }
+    // Another thread may have raced us, in which case our descriptor is simply dropped.
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+    Ok(CLOSED_FD.get_or_init(|| fd).as_fd())
+}
 
 impl Queue {
     /// Open a new queue using the supplied [IfReq], exposing a synchronous blocking queue.
     pub(crate) fn open(req: &IfReq) -> Result<Self> {
         let fd = unsafe { libc::open(PATH.as_ptr() as *const libc::c_char, libc::O_RDWR) };
         if fd < 0 {
-            return Err(Error::FS {
-                path: unsafe { String::from_utf8_unchecked(PATH.to_vec()) },
-                source: io::Error::last_os_error(),
+            let source = io::Error::last_os_error();
+            return Err(match source.raw_os_error() {
+                Some(libc::ENOENT | libc::ENODEV | libc::ENXIO) => Error::NoTunModule,
+                Some(libc::EACCES | libc::EPERM) => Error::PermissionDenied {
+                    op: "open /dev/net/tun",
+                    device: req.name(),
+                },
+                _ => Error::FS {
+                    path: unsafe { String::from_utf8_unchecked(PATH.to_vec()) },
+                    source,
+                },
             });
         }
 
-        let ret = unsafe { create_queue(fd, req as *const IfReq as PointerWidth)? };
+        // Take ownership immediately, so the descriptor is closed if attaching the queue fails.
+        let mut queue = unsafe { Self::from_raw_fd(fd) };
+        queue.headers = req.headers();
+        let ret =
+            unsafe { create_queue(fd, req as *const IfReq as PointerWidth) }.map_err(|source| {
+                let device = req.name();
+                // The kernel rejects attaching to an existing device of a different kind or mode
+                // with EINVAL, which is otherwise reported for invalid flags or names.
+                if source == Errno::EINVAL && nix::net::if_::if_nametoindex(device.as_str()).is_ok()
+                {
+                    return Error::DeviceMismatch { device };
+                }
+                Error::from(source).context("TUNSETIFF", &device)
+            })?;
         if ret >= 1 {
-            return Err(Error::from(ret as i32));
+            // The ioctl only ever returns zero on success, so treat anything else as an errno.
+            return Err(Error::from(Errno::from_i32(ret)).context("TUNSETIFF", &req.name()));
         }
-        Ok(Self(fd))
+        Ok(queue)
     }
 
-    /// Close the internal queue destroying this instance completely.
+    /// Close the internal queue destroying this instance completely. Closing an already closed
+    /// queue is a no-op.
+    ///
+    /// # Errors
+    /// If the placeholder descriptor exposed by closed queues can't be opened, as the process has
+    /// run out of file descriptors, the queue is left open and the error returned.
     pub fn close(&mut self) -> Result<()> {
-        let ret = unsafe { libc::close(self.0) };
+        if self.fd.is_some() {
+            closed_fd()?;
+        }
+        let fd = match self.fd.take() {
+            Some(fd) => fd.into_raw_fd(),
+            None => return Ok(()),
+        };
+        // Close explicitly rather than dropping the OwnedFd, so that errors can be surfaced.
+        let ret = unsafe { libc::close(fd) };
         if ret < 0 {
             Err(Error::errno())
         } else {
-            self.0 = -1;
             Ok(())
         }
     }
 
+    /// Return whether this queue has been closed.
+    #[inline]
+    pub fn is_closed(&self) -> bool {
+        self.fd.is_none()
+    }
+
+    /// Create a new handle to this queue by duplicating the underlying file descriptor. Both
+    /// handles refer to the same queue, and share its state such as non-blocking mode, however
+    /// each can be closed independently of the other.
+    pub fn try_clone(&self) -> Result<Self> {
+        let fd = self.fd.as_ref().ok_or(Error::QueueClosed)?;
+        Ok(Self {
+            fd: Some(fd.try_clone()?),
+            headers: self.headers,
+        })
+    }
+
+    fn fd(&self) -> Result<RawFd> {
+        self.fd
+            .as_ref()
+            .map(|fd| fd.as_raw_fd())
+            .ok_or(Error::QueueClosed)
+    }
+
     /// Either enable or disable non-blocking mode on the underlying file descriptor.
     pub fn set_non_blocking(&self, on: bool) -> Result<()> {
-        let flags =
-            nix::fcntl::fcntl(self.0, nix::fcntl::FcntlArg::F_GETFL).map_err(Error::from)?;
+        let fd = self.fd()?;
+        let flags = nix::fcntl::fcntl(fd, nix::fcntl::FcntlArg::F_GETFL).map_err(Error::from)?;
 
         let mut flags = OFlag::from_bits(flags).unwrap_or(OFlag::O_RDWR);
         if on && !flags.contains(OFlag::O_NONBLOCK) {
@@ -67,7 +187,7 @@ impl Queue {
             return Ok(());
         }
 
-        nix::fcntl::fcntl(self.0, nix::fcntl::FcntlArg::F_SETFL(flags))
+        nix::fcntl::fcntl(fd, nix::fcntl::FcntlArg::F_SETFL(flags))
             .map(|_| ())
             .map_err(Error::from)
     }
@@ -82,10 +202,11 @@ impl Queue {
     /// # Errors
     /// On any error it should be assumed that the buffer was partially sent.
     pub fn send(&self, datagram: &[u8]) -> io::Result<usize> {
+        let fd = self.fd().map_err(Error::into_io)?;
         let count = datagram.len();
         let written = unsafe {
             let ptr = datagram.as_ptr();
-            libc::write(self.0, ptr as *const libc::c_void, count)
+            libc::write(fd, ptr as *const libc::c_void, count)
         };
 
         if written < 0 {
@@ -108,6 +229,74 @@ impl Queue {
         unsafe { self.recv_int(datagram.as_mut_ptr(), datagram.len()) }
     }
 
+    /// Read a single packet into a buffer taken from the supplied [PacketPool], see [`Queue::recv()`] for more
+    /// details. The packet is read following the headroom of the buffer, so that it can be encapsulated and
+    /// forwarded without further copies.
+    ///
+    /// # Errors
+    /// On any error the buffer is returned to the pool.
+    #[cfg(feature = "pool-impl")]
+    pub fn recv_pooled(&self, pool: &PacketPool) -> io::Result<PooledPacket> {
+        let mut packet = pool.get();
+        let read = self.recv(packet.spare())?;
+        packet.commit(read);
+        Ok(packet)
+    }
+
+    /// Write a single packet gathered from the supplied buffers to the underlying file descriptor, for instance a
+    /// header followed by the payload, without first copying them into a contiguous buffer. This call wraps the raw
+    /// [`libc::writev()`] call returning the number of bytes written, see [`Queue::send()`] for more details.
+    ///
+    /// # Errors
+    /// On any error it should be assumed that the buffers were partially sent. Supplying more than
+    /// `IOV_MAX` buffers fails with [`InvalidInput`][io::ErrorKind::InvalidInput], as the packet
+    /// can't be written in a single call.
+    pub fn send_vectored(&self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        let fd = self.fd().map_err(Error::into_io)?;
+        check_iov_len(bufs.len())?;
+        // IoSlice is guaranteed to be ABI compatible with iovec.
+        let written = unsafe {
+            libc::writev(
+                fd,
+                bufs.as_ptr() as *const libc::iovec,
+                bufs.len() as libc::c_int,
+            )
+        };
+
+        if written < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(written as usize)
+        }
+    }
+
+    /// Read a single packet off the underlying file descriptor, scattering it across the supplied buffers in order,
+    /// for instance to split a header from the payload. This call wraps the raw [`libc::readv()`] call returning the
+    /// number of bytes read, see [`Queue::recv()`] for more details.
+    ///
+    /// # Errors
+    /// On any error it should be assumed that no usable data was read into the buffers. Supplying
+    /// more than `IOV_MAX` buffers fails with [`InvalidInput`][io::ErrorKind::InvalidInput], as
+    /// the packet can't be read in a single call.
+    pub fn recv_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        let fd = self.fd().map_err(Error::into_io)?;
+        check_iov_len(bufs.len())?;
+        // IoSliceMut is guaranteed to be ABI compatible with iovec.
+        let read = unsafe {
+            libc::readv(
+                fd,
+                bufs.as_mut_ptr() as *mut libc::iovec,
+                bufs.len() as libc::c_int,
+            )
+        };
+
+        if read < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(read as usize)
+        }
+    }
+
     /// Read data from the underlying file descriptor into the supplied datagram, reading data from the hosts networking
     /// stack, using uninitialized memory. This call is analogous to the [`Queue::recv()`] function but allows for using
     /// uninitialized memory buffers.
@@ -122,8 +311,131 @@ impl Queue {
         unsafe { self.recv_int(datagram.as_mut_ptr(), datagram.len()) }
     }
 
+    /// Read as many packets as are available off the underlying file descriptor into the supplied
+    /// batch, replacing its previous contents, until either the batch is full or the queue has no
+    /// more packets waiting. Upon success the number of packets read is returned. Packets larger
+    /// than the packet size of the batch are truncated.
+    ///
+    /// In blocking mode this blocks only until the first packet arrives. In non-blocking mode
+    /// this returns [`WouldBlock`][std::io::ErrorKind::WouldBlock] if no packet is waiting.
+    ///
+    /// # Errors
+    /// Any error other than [`WouldBlock`][std::io::ErrorKind::WouldBlock] or
+    /// [`Interrupted`][std::io::ErrorKind::Interrupted] is returned, even if packets were already
+    /// read into the batch. Those packets are left in the batch, but are not reported.
+    pub fn recv_batch(&self, batch: &mut PacketBatch) -> io::Result<usize> {
+        let fd = self.fd().map_err(Error::into_io)?;
+        let flags = nix::fcntl::fcntl(fd, nix::fcntl::FcntlArg::F_GETFL)
+            .map_err(|err| Error::from(err).into_io())?;
+        let blocking = !OFlag::from_bits_truncate(flags).contains(OFlag::O_NONBLOCK);
+        self.recv_batch_int(batch, blocking)
+    }
+
+    pub(crate) fn recv_batch_int(
+        &self,
+        batch: &mut PacketBatch,
+        blocking: bool,
+    ) -> io::Result<usize> {
+        batch.clear();
+        while !batch.is_full() {
+            // Only wait for the first packet, afterwards just drain what is already waiting.
+            if blocking && !batch.is_empty() && !self.ready_now(PollFlags::POLLIN)? {
+                break;
+            }
+            match self.recv(batch.spare()) {
+                Ok(read) => batch.commit(read),
+                // Running out of packets, or a signal, merely ends the batch once it has any.
+                Err(err)
+                    if !batch.is_empty()
+                        && matches!(
+                            err.kind(),
+                            io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted
+                        ) =>
+                {
+                    break
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(batch.len())
+    }
+
+    /// Write the packets of the supplied batch to the underlying file descriptor, in order, until
+    /// either all were sent or, in non-blocking mode, the queue would block. Upon success the
+    /// number of packets sent is returned.
+    ///
+    /// In non-blocking mode this returns [`WouldBlock`][std::io::ErrorKind::WouldBlock] if not
+    /// even the first packet could be sent.
+    ///
+    /// # Errors
+    /// On any error it should be assumed that part of the batch was sent.
+    #[inline]
+    pub fn send_batch(&self, batch: &PacketBatch) -> io::Result<usize> {
+        self.send_batch_int(batch, 0)
+    }
+
+    /// Write the packets of the supplied batch starting at the supplied index, returning the
+    /// index following the last packet sent.
+    pub(crate) fn send_batch_int(&self, batch: &PacketBatch, start: usize) -> io::Result<usize> {
+        let mut next = start;
+        while let Some(packet) = batch.get(next) {
+            match self.send(packet) {
+                Ok(_) => next += 1,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock && next > start => break,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(next)
+    }
+
+    /// Return whether the underlying file descriptor is ready for the supplied events, without
+    /// blocking.
+    fn ready_now(&self, events: PollFlags) -> io::Result<bool> {
+        let mut fds = [PollFd::new(self.fd().map_err(Error::into_io)?, events)];
+        loop {
+            match poll(&mut fds, 0) {
+                Ok(ready) => return Ok(ready > 0),
+                Err(Errno::EINTR) => continue,
+                Err(err) => return Err(Error::from(err).into_io()),
+            }
+        }
+    }
+
+    /// Read a packet off the underlying file descriptor, splitting any enabled headers from the
+    /// payload, which is read into the supplied datagram. The supplied queue index is reported in
+    /// the returned [RecvMeta].
+    pub(crate) fn recv_meta(&self, queue: usize, datagram: &mut [u8]) -> io::Result<RecvMeta> {
+        let mut hdrs = [0u8; PacketInfo::LEN + VnetHdr::LEN];
+        // A trailing byte beyond the datagram detects truncation by descriptors which, unlike
+        // TUN queues, report only the number of bytes actually read.
+        let mut overflow = [0u8; 1];
+        let capacity = datagram.len();
+
+        let read = {
+            let (packet_info, vnet_hdr) = hdrs.split_at_mut(PacketInfo::LEN);
+            let mut bufs = [
+                IoSliceMut::new(&mut []),
+                IoSliceMut::new(&mut []),
+                IoSliceMut::new(datagram),
+                IoSliceMut::new(&mut overflow),
+            ];
+            let mut first = 2;
+            if self.headers.vnet_hdr {
+                first -= 1;
+                bufs[first] = IoSliceMut::new(vnet_hdr);
+            }
+            if self.headers.packet_info {
+                first -= 1;
+                bufs[first] = IoSliceMut::new(packet_info);
+            }
+            self.recv_vectored(&mut bufs[first..])?
+        };
+        Ok(RecvMeta::parse(queue, self.headers, &hdrs, read, capacity))
+    }
+
     unsafe fn recv_int<T>(&self, ptr: *mut T, count: usize) -> io::Result<usize> {
-        let read = libc::read(self.0, ptr as *mut libc::c_void, count);
+        let fd = self.fd().map_err(Error::into_io)?;
+        let read = libc::read(fd, ptr as *mut libc::c_void, count);
         if read < 0 {
             Err(io::Error::last_os_error())
         } else {
@@ -133,8 +445,57 @@ impl Queue {
 }
 
 impl AsRawFd for Queue {
+    /// Return the underlying file descriptor, or the placeholder descriptor if the queue has been
+    /// closed. Check [`Queue::is_closed()`] first to tell the two apart. If the placeholder isn't
+    /// available `-1` is returned instead.
+    #[inline]
     fn as_raw_fd(&self) -> RawFd {
-        self.0
+        match &self.fd {
+            Some(fd) => fd.as_raw_fd(),
+            None => closed_fd().map_or(-1, |fd| fd.as_raw_fd()),
+        }
+    }
+}
+
+impl AsFd for Queue {
+    /// Borrow the underlying file descriptor, or the placeholder descriptor if the queue has been
+    /// closed. Check [`Queue::is_closed()`] first to tell the two apart.
+    #[inline]
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        match &self.fd {
+            Some(fd) => fd.as_fd(),
+            // Queues only close once the placeholder is open, see `Queue::close()`.
+            None => {
+                closed_fd().unwrap_or_else(|_| unreachable!("closed queue without placeholder"))
+            }
+        }
+    }
+}
+
+impl IntoRawFd for Queue {
+    /// Consume this queue, passing ownership of the underlying file descriptor to the caller. If
+    /// the queue has been closed this returns a duplicate of the placeholder descriptor instead,
+    /// or `-1` if it can't be duplicated.
+    fn into_raw_fd(mut self) -> RawFd {
+        match self.fd.take() {
+            Some(fd) => fd.into_raw_fd(),
+            None => closed_fd()
+                .ok()
+                .and_then(|fd| fd.try_clone_to_owned().ok())
+                .map_or(-1, IntoRawFd::into_raw_fd),
+        }
+    }
+}
+
+impl FromRawFd for Queue {
+    /// Create a queue taking ownership of the supplied file descriptor, which must be an open TUN
+    /// or TAP queue, for instance one previously returned from [`IntoRawFd::into_raw_fd()`]. The
+    /// queue is assumed to exchange packets without any optional headers.
+    unsafe fn from_raw_fd(fd: RawFd) -> Self {
+        Self {
+            fd: Some(OwnedFd::from_raw_fd(fd)),
+            headers: Headers::NONE,
+        }
     }
 }
 
@@ -143,6 +504,11 @@ impl Read for Queue {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         self.recv(buf)
     }
+
+    #[inline]
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> std::io::Result<usize> {
+        self.recv_vectored(bufs)
+    }
 }
 
 impl Write for Queue {
@@ -151,6 +517,11 @@ impl Write for Queue {
         self.send(buf)
     }
 
+    #[inline]
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> std::io::Result<usize> {
+        self.send_vectored(bufs)
+    }
+
     #[inline]
     fn flush(&mut self) -> std::io::Result<()> {
         // TUN queues are character devices under the hood no flushing needed.
@@ -158,9 +529,144 @@ impl Write for Queue {
     }
 }
 
-impl Opener for Queue {
+impl FromQueue for Queue {
     #[inline]
-    fn open(req: &IfReq) -> Result<Self> {
-        Self::open(req)
+    fn from_queue(queue: Queue) -> Result<Self> {
+        Ok(queue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ownership() {
+        let (read, write) = nix::unistd::pipe().unwrap();
+        let read = unsafe { Queue::from_raw_fd(read) };
+        let write = unsafe { Queue::from_raw_fd(write) };
+
+        let mut clone = write.try_clone().unwrap();
+        assert_ne!(write.as_raw_fd(), clone.as_raw_fd());
+        clone.close().unwrap();
+        clone.close().unwrap();
+        assert!(clone.is_closed());
+        assert!(matches!(clone.try_clone(), Err(Error::QueueClosed)));
+        assert_eq!(
+            io::ErrorKind::NotConnected,
+            clone.send(b"data").unwrap_err().kind()
+        );
+
+        assert_eq!(4, write.send(b"data").unwrap());
+        let mut buf = [0; 4];
+        assert_eq!(4, read.recv(&mut buf).unwrap());
+
+        // The placeholder exposed by a closed queue rejects all I/O.
+        assert_eq!(
+            Err(Errno::EBADF),
+            nix::unistd::write(clone.as_raw_fd(), b"data")
+        );
+        let fd = clone.into_raw_fd();
+        assert_eq!(Err(Errno::EBADF), nix::unistd::write(fd, b"data"));
+        drop(unsafe { OwnedFd::from_raw_fd(fd) });
+
+        // Ownership of the write end passes through the raw descriptor, which is closed once the
+        // queue taking it back is dropped, so the read end observes EOF.
+        let fd = write.into_raw_fd();
+        assert_eq!(Ok(4), nix::unistd::write(fd, b"data"));
+        drop(unsafe { Queue::from_raw_fd(fd) });
+        assert_eq!(4, read.recv(&mut buf).unwrap());
+        assert_eq!(0, read.recv(&mut buf).unwrap());
+
+        // Dropping the read end closes it, so writes to the pipe fail.
+        let (other, write) = nix::unistd::pipe().unwrap();
+        let write = unsafe { Queue::from_raw_fd(write) };
+        drop(unsafe { Queue::from_raw_fd(other) });
+        assert_eq!(
+            Some(libc::EPIPE),
+            write.send(b"data").unwrap_err().raw_os_error()
+        );
+    }
+
+    #[test]
+    fn test_batch() {
+        use nix::sys::socket::{socketpair, AddressFamily, SockFlag, SockType};
+
+        let (local, peer) = socketpair(
+            AddressFamily::Unix,
+            SockType::Datagram,
+            None,
+            SockFlag::empty(),
+        )
+        .unwrap();
+        let (local, peer) = unsafe { (Queue::from_raw_fd(local), Queue::from_raw_fd(peer)) };
+
+        let mut batch = PacketBatch::new(4, 8);
+        for packet in [&b"one"[..], b"two", b"three"] {
+            assert!(batch.push(packet));
+        }
+        assert_eq!(3, peer.send_batch(&batch).unwrap());
+
+        // A blocking queue returns the packets already waiting, rather than blocking for more.
+        let mut received = PacketBatch::new(4, 8);
+        assert_eq!(3, local.recv_batch(&mut received).unwrap());
+        assert_eq!(
+            batch.iter().collect::<Vec<_>>(),
+            received.iter().collect::<Vec<_>>()
+        );
+
+        local.set_non_blocking(true).unwrap();
+        let err = local.recv_batch(&mut received).unwrap_err();
+        assert_eq!(io::ErrorKind::WouldBlock, err.kind());
+        assert!(received.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "pool-impl")]
+    fn test_recv_pooled() {
+        use nix::sys::socket::{socketpair, AddressFamily, SockFlag, SockType};
+
+        let (local, peer) = socketpair(
+            AddressFamily::Unix,
+            SockType::Datagram,
+            None,
+            SockFlag::empty(),
+        )
+        .unwrap();
+        let (local, peer) = unsafe { (Queue::from_raw_fd(local), Queue::from_raw_fd(peer)) };
+
+        let pool = PacketPool::new(1, 8, 4);
+        peer.send(b"packet").unwrap();
+        let mut packet = local.recv_pooled(&pool).unwrap();
+        assert_eq!(b"packet", &packet[..]);
+        assert!(packet.prepend(b"hdr:"));
+        assert_eq!(&b"hdr:packet"[..], &packet.into_bytes_mut()[..]);
+
+        local.set_non_blocking(true).unwrap();
+        let pool = PacketPool::new(1, 8, 4);
+        assert!(local.recv_pooled(&pool).is_err());
+        assert_eq!(1, pool.available());
+    }
+
+    #[test]
+    fn test_vectored() {
+        let (read, write) = nix::unistd::pipe().unwrap();
+        let (mut read, mut write) =
+            unsafe { (Queue::from_raw_fd(read), Queue::from_raw_fd(write)) };
+
+        let bufs = [IoSlice::new(b"head"), IoSlice::new(b"payload")];
+        assert_eq!(11, write.write_vectored(&bufs).unwrap());
+        assert_eq!(4, write.send_vectored(&[IoSlice::new(b"tail")]).unwrap());
+
+        let (mut head, mut payload) = ([0u8; 4], [0u8; 11]);
+        let mut bufs = [IoSliceMut::new(&mut head), IoSliceMut::new(&mut payload)];
+        assert_eq!(15, read.read_vectored(&mut bufs).unwrap());
+        assert_eq!(b"head", &head);
+        assert_eq!(b"payloadtail", &payload);
+
+        // Trailing buffers beyond IOV_MAX are rejected rather than silently dropped.
+        let bufs = vec![IoSlice::new(b"x"); IOV_MAX + 1];
+        let err = write.send_vectored(&bufs).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
     }
 }
@@ -0,0 +1,421 @@
+// (c) Copyright 2021 Christian Saide
+// SPDX-License-Identifier: MIT
+
+use super::{Error, Queue, Result};
+
+use std::collections::VecDeque;
+use std::io;
+use std::os::unix::io::AsRawFd;
+
+use io_uring::{opcode, squeue, types, IoUring};
+use nix::libc;
+
+/// The user data of the cancellations submitted while dropping the queue.
+const CANCEL: u64 = u64::MAX;
+
+/// A runtime free [Queue] driven by an `io_uring` instance, which amortizes the cost of a syscall
+/// across every packet received and sent since the previous submission.
+///
+/// All packet buffers are carved out of a single arena registered with the kernel up front, so
+/// that reads and writes skip mapping user memory per operation. Every read slot is kept armed
+/// with a pending read, which is re-armed as soon as its packet has been delivered, mimicking
+/// a multishot read that the TUN driver doesn't natively support.
+///
+/// Sends are copied into a free write slot and only submitted once the queue is flushed, a
+/// receive is performed, or no write slot remains, so errors encountered by a send are reported
+/// by a subsequent call.
+///
+/// ```no_run
+/// use riptun::{Tun, UringQueue};
+///
+/// let tun = Tun::new("rip%d", 1).expect("failed to create device");
+/// let queue = tun.into_iter().next().unwrap();
+/// let mut queue = UringQueue::new(queue).expect("failed to setup io_uring");
+/// let mut replies = Vec::new();
+/// queue
+///     .recv(|packet| replies.push(packet.to_vec()))
+///     .expect("failed to receive packets");
+/// for reply in replies {
+///     queue.send(&reply).expect("failed to send packet");
+/// }
+/// queue.flush().expect("failed to flush packets");
+/// ```
+pub struct UringQueue {
+    ring: IoUring,
+    queue: Queue,
+    arena: Vec<u8>,
+    depth: usize,
+    buffer_size: usize,
+    inflight: usize,
+    armed: usize,
+    ready: VecDeque<(usize, usize)>,
+    free: Vec<usize>,
+    error: Option<io::Error>,
+    failed: Option<i32>,
+}
+
+impl UringQueue {
+    /// The default number of reads kept in flight, and of sends buffered.
+    pub const DEFAULT_DEPTH: usize = 64;
+    /// The default size of each buffer slot, which covers the default MTU along with any enabled
+    /// headers.
+    pub const DEFAULT_BUFFER_SIZE: usize = 2048;
+
+    /// Drive the supplied [Queue] with a new `io_uring` instance, using [`UringQueue::DEFAULT_DEPTH`]
+    /// slots of [`UringQueue::DEFAULT_BUFFER_SIZE`] bytes for each of reads and writes.
+    #[inline]
+    pub fn new(queue: Queue) -> Result<Self> {
+        Self::with_capacity(queue, Self::DEFAULT_DEPTH, Self::DEFAULT_BUFFER_SIZE)
+    }
+
+    /// Drive the supplied [Queue] with a new `io_uring` instance, keeping `depth` reads in flight
+    /// and buffering up to `depth` sends, each in a slot of `buffer_size` bytes. Received packets
+    /// larger than the buffer size are truncated.
+    pub fn with_capacity(queue: Queue, depth: usize, buffer_size: usize) -> Result<Self> {
+        if queue.is_closed() {
+            return Err(Error::QueueClosed);
+        }
+        let depth = depth.max(1);
+        let buffer_size = buffer_size.clamp(1, u32::MAX as usize);
+        // The ring completes operations asynchronously on our behalf, so a non-blocking queue
+        // would only produce spurious `EAGAIN` completions.
+        queue.set_non_blocking(false)?;
+
+        // Each slot has at most a single operation in flight, so the submission queue can never
+        // overflow, along with the completion queue which is twice its size.
+        let entries = (2 * depth).next_power_of_two() as u32;
+        let ring = IoUring::new(entries)?;
+        let mut arena = vec![0u8; 2 * depth * buffer_size];
+        let iovec = libc::iovec {
+            iov_base: arena.as_mut_ptr().cast(),
+            iov_len: arena.len(),
+        };
+        // The arena is never resized, and outlives every operation referencing it.
+        unsafe { ring.submitter().register_buffers(&[iovec])? };
+
+        let mut uring = Self {
+            ring,
+            queue,
+            arena,
+            depth,
+            buffer_size,
+            inflight: 0,
+            armed: 0,
+            ready: VecDeque::with_capacity(depth),
+            free: (depth..2 * depth).rev().collect(),
+            error: None,
+            failed: None,
+        };
+        for slot in 0..depth {
+            uring.arm(slot);
+        }
+        uring.ring.submit()?;
+        Ok(uring)
+    }
+
+    /// Return the size of each buffer slot, the largest packet this queue can receive or send.
+    #[inline]
+    pub fn buffer_size(&self) -> usize {
+        self.buffer_size
+    }
+
+    /// Return a reference to the underlying [Queue].
+    #[inline]
+    pub fn get_ref(&self) -> &Queue {
+        &self.queue
+    }
+
+    /// Receive every packet read since the previous call, passing each to the supplied closure
+    /// in the order it was read, and returning the number of packets received. This blocks
+    /// until at least one packet is available, while also submitting any buffered sends.
+    ///
+    /// # Errors
+    /// Once reads fail persistently, for instance because the device was removed, every read
+    /// slot is left disarmed and all subsequent calls fail with the same error.
+    pub fn recv<F: FnMut(&[u8])>(&mut self, f: F) -> io::Result<usize> {
+        while self.ready.is_empty() {
+            self.take_error()?;
+            self.check_armed()?;
+            self.ring.submit_and_wait(1)?;
+            self.reap();
+        }
+        self.deliver(f)
+    }
+
+    /// Receive every packet read since the previous call, as per [`UringQueue::recv()`], without
+    /// blocking. If no packets are available this returns `0`.
+    pub fn try_recv<F: FnMut(&[u8])>(&mut self, f: F) -> io::Result<usize> {
+        self.ring.submit()?;
+        self.reap();
+        if self.ready.is_empty() {
+            self.take_error()?;
+            self.check_armed()?;
+            return Ok(0);
+        }
+        self.deliver(f)
+    }
+
+    /// Buffer a copy of the supplied packet to be sent by the next submission, blocking only if
+    /// every write slot is in use. Packets larger than the buffer size are rejected with an
+    /// [`InvalidInput`][io::ErrorKind::InvalidInput] error.
+    pub fn send(&mut self, packet: &[u8]) -> io::Result<()> {
+        if packet.len() > self.buffer_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "packet exceeds the buffer size of the queue",
+            ));
+        }
+        self.take_error()?;
+        while self.free.is_empty() {
+            self.ring.submit_and_wait(1)?;
+            self.reap();
+            self.take_error()?;
+        }
+
+        let slot = self.free.pop().unwrap();
+        let start = slot * self.buffer_size;
+        self.arena[start..start + packet.len()].copy_from_slice(packet);
+        let entry = opcode::WriteFixed::new(
+            types::Fd(self.queue.as_raw_fd()),
+            self.arena[start..].as_ptr(),
+            packet.len() as u32,
+            0,
+        )
+        .build()
+        .user_data(slot as u64);
+        self.push(&entry);
+        Ok(())
+    }
+
+    /// Submit every buffered send, blocking until all have completed, and returning the first
+    /// error encountered by any of them.
+    pub fn flush(&mut self) -> io::Result<()> {
+        while self.free.len() < self.depth {
+            self.ring.submit_and_wait(1)?;
+            self.reap();
+        }
+        self.take_error()
+    }
+
+    /// Close the queue, waiting for any buffered sends to complete prior to closing the
+    /// underlying file descriptor.
+    pub fn close(mut self) -> Result<()> {
+        self.flush()?;
+        self.cancel();
+        self.queue.close()
+    }
+
+    fn deliver<F: FnMut(&[u8])>(&mut self, mut f: F) -> io::Result<usize> {
+        let delivered = self.ready.len();
+        while let Some((slot, len)) = self.ready.pop_front() {
+            let start = slot * self.buffer_size;
+            f(&self.arena[start..start + len]);
+            self.arm(slot);
+        }
+        self.ring.submit()?;
+        Ok(delivered)
+    }
+
+    /// Queue a read into the supplied slot, to be submitted alongside the next submission.
+    fn arm(&mut self, slot: usize) {
+        let start = slot * self.buffer_size;
+        let entry = opcode::ReadFixed::new(
+            types::Fd(self.queue.as_raw_fd()),
+            self.arena[start..].as_mut_ptr(),
+            self.buffer_size as u32,
+            0,
+        )
+        .build()
+        .user_data(slot as u64);
+        self.push(&entry);
+        self.armed += 1;
+    }
+
+    fn push(&mut self, entry: &squeue::Entry) {
+        // Every buffer referenced by the entry lives within the registered arena, and the
+        // submission queue has room for an entry per slot.
+        unsafe { self.ring.submission().push(entry) }.expect("submission queue overflow");
+        self.inflight += 1;
+    }
+
+    /// Process every completion, recording received packets and released write slots.
+    fn reap(&mut self) {
+        let mut retry = Vec::new();
+        for cqe in self.ring.completion() {
+            self.inflight -= 1;
+            let slot = match cqe.user_data() {
+                CANCEL => continue,
+                slot => slot as usize,
+            };
+            let res = cqe.result();
+            if slot >= self.depth {
+                self.free.push(slot);
+                if res < 0 {
+                    self.error.get_or_insert(io::Error::from_raw_os_error(-res));
+                }
+                continue;
+            }
+
+            self.armed -= 1;
+            if res >= 0 {
+                // The kernel reports the full length of truncated packets.
+                let len = (res as usize).min(self.buffer_size);
+                self.ready.push_back((slot, len));
+                continue;
+            }
+            match -res {
+                libc::EAGAIN | libc::EINTR => retry.push(slot),
+                // The queue is unusable, for instance because the device has been removed, so
+                // leave the slot disarmed rather than spinning on the same failure.
+                errno @ (libc::EBADF | libc::EBADFD | libc::EIO | libc::ENODEV | libc::ENXIO) => {
+                    self.failed = Some(errno);
+                    self.error
+                        .get_or_insert(io::Error::from_raw_os_error(errno));
+                }
+                // Report any other failure once, while keeping the slot armed.
+                errno => {
+                    self.error
+                        .get_or_insert(io::Error::from_raw_os_error(errno));
+                    retry.push(slot);
+                }
+            }
+        }
+        for slot in retry {
+            self.arm(slot);
+        }
+    }
+
+    fn take_error(&mut self) -> io::Result<()> {
+        self.error.take().map_or(Ok(()), Err)
+    }
+
+    /// Fail with the persistent read error once no read remains armed, as waiting for a
+    /// completion would then block forever.
+    fn check_armed(&self) -> io::Result<()> {
+        match self.failed {
+            Some(errno) if self.armed == 0 => Err(io::Error::from_raw_os_error(errno)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Cancel every pending read and wait for all operations to complete, so the kernel no
+    /// longer references the arena.
+    fn cancel(&mut self) {
+        if self.inflight == 0 {
+            return;
+        }
+        // Any pending sends were submitted alongside reads, so there is room for a cancellation
+        // per read slot.
+        for slot in 0..self.depth {
+            let entry = opcode::AsyncCancel::new(slot as u64)
+                .build()
+                .user_data(CANCEL);
+            self.push(&entry);
+        }
+        while self.inflight > 0 {
+            if self.ring.submit_and_wait(1).is_err() {
+                // Without a way to wait for the outstanding operations the arena must be leaked
+                // rather than freed from under the kernel.
+                std::mem::forget(std::mem::take(&mut self.arena));
+                return;
+            }
+            self.inflight -= self.ring.completion().count();
+        }
+    }
+}
+
+impl Drop for UringQueue {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::UdpSocket;
+    use std::os::unix::io::{FromRawFd, IntoRawFd};
+    use std::os::unix::net::UnixDatagram;
+
+    fn try_uring(queue: Queue) -> Option<UringQueue> {
+        match UringQueue::with_capacity(queue, 4, 16) {
+            Ok(uring) => Some(uring),
+            // io_uring may be disabled by the kernel or sandbox the tests are run within.
+            Err(Error::IO { .. }) => None,
+            Err(err) => panic!("unexpected error: {}", err),
+        }
+    }
+
+    #[test]
+    fn test_uring() {
+        let (local, remote) = UnixDatagram::pair().unwrap();
+        let queue = unsafe { Queue::from_raw_fd(local.into_raw_fd()) };
+        let mut uring = match try_uring(queue) {
+            Some(uring) => uring,
+            None => return,
+        };
+
+        assert_eq!(0, uring.try_recv(|_| ()).unwrap());
+        remote.send(b"first").unwrap();
+        remote.send(b"second").unwrap();
+        let mut packets = Vec::new();
+        while packets.len() < 2 {
+            uring.recv(|packet| packets.push(packet.to_vec())).unwrap();
+        }
+        assert_eq!(vec![b"first".to_vec(), b"second".to_vec()], packets);
+
+        assert!(uring.send(&[0; 17]).is_err());
+        for idx in 0..6u8 {
+            uring.send(&[idx; 3]).unwrap();
+        }
+        uring.flush().unwrap();
+        let mut buf = [0u8; 16];
+        for idx in 0..6u8 {
+            assert_eq!(3, remote.recv(&mut buf).unwrap());
+            assert_eq!([idx; 3], buf[..3]);
+        }
+        uring.close().unwrap();
+    }
+    #[test]
+    fn test_uring_read_errors() {
+        // Sending to a port without a listener queues a connection refused error on the socket,
+        // which fails a single read.
+        let peer = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = peer.local_addr().unwrap();
+        drop(peer);
+        let local = UdpSocket::bind("127.0.0.1:0").unwrap();
+        local.connect(addr).unwrap();
+        local.send(b"refused").unwrap();
+
+        let queue = unsafe { Queue::from_raw_fd(local.try_clone().unwrap().into_raw_fd()) };
+        let mut uring = match try_uring(queue) {
+            Some(uring) => uring,
+            None => return,
+        };
+        let err = uring.recv(|_| ()).unwrap_err();
+        assert_eq!(io::ErrorKind::ConnectionRefused, err.kind());
+
+        // The failed read is re-armed, so every slot keeps receiving packets.
+        let peer = UdpSocket::bind(addr).unwrap();
+        peer.connect(local.local_addr().unwrap()).unwrap();
+        let mut packets = Vec::new();
+        for idx in 0..8u8 {
+            peer.send(&[idx]).unwrap();
+            uring.recv(|packet| packets.push(packet[0])).unwrap();
+        }
+        assert_eq!((0..8).collect::<Vec<_>>(), packets);
+        drop(uring);
+
+        // Reads from a write only descriptor fail persistently, so once every slot has failed
+        // all calls fail rather than waiting for reads which are never submitted.
+        let (read, write) = nix::unistd::pipe().unwrap();
+        let _read = unsafe { Queue::from_raw_fd(read) };
+        let mut uring = try_uring(unsafe { Queue::from_raw_fd(write) }).unwrap();
+        for _ in 0..3 {
+            let err = uring.recv(|_| ()).unwrap_err();
+            assert_eq!(Some(libc::EBADF), err.raw_os_error());
+        }
+        assert!(uring.try_recv(|_| ()).is_err());
+    }
+}
@@ -0,0 +1,130 @@
+// (c) Copyright 2021 Christian Saide
+// SPDX-License-Identifier: MIT
+
+use super::{Error, Result};
+
+use std::io;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+
+use nix::errno::Errno;
+use nix::sys::epoll::{
+    epoll_create1, epoll_ctl, epoll_wait, EpollCreateFlags, EpollEvent, EpollFlags, EpollOp,
+};
+
+/// A persistent set of queues tracking which of them are ready for a single direction of I/O,
+/// backed by a dedicated level triggered epoll instance. The epoll instance is itself readable
+/// whenever any queue in the set is ready, so registering it with an async reactor replaces
+/// waiting on every queue individually.
+///
+/// Ready queues are handed out round-robin, so that no single queue can starve the others. This
+/// relies on the kernel moving each level triggered entry to the back of the ready list once it
+/// has been reported, so retrieving a single event per call is both fair and independent of the
+/// number of ready queues.
+pub(crate) struct ReadySet {
+    epoll: OwnedFd,
+    num_queues: usize,
+}
+
+impl ReadySet {
+    /// Create a new set tracking the supplied queue descriptors for readability (`EPOLLIN`) or
+    /// writability (`EPOLLOUT`), identifying each queue by its position. Closed queues are
+    /// supplied as `None`.
+    pub(crate) fn new<I>(fds: I, flags: EpollFlags) -> Result<Self>
+    where
+        I: IntoIterator<Item = Option<RawFd>>,
+    {
+        let epoll = epoll_create1(EpollCreateFlags::EPOLL_CLOEXEC)?;
+        let epoll = unsafe { OwnedFd::from_raw_fd(epoll) };
+
+        let mut num_queues = 0;
+        for (idx, fd) in fds.into_iter().enumerate() {
+            // Closed queues are never ready, so there is no need to track them.
+            let fd = match fd {
+                Some(fd) => fd,
+                None => continue,
+            };
+            num_queues += 1;
+            let mut event = EpollEvent::new(flags, idx as u64);
+            epoll_ctl(epoll.as_raw_fd(), EpollOp::EpollCtlAdd, fd, &mut event)?;
+        }
+
+        Ok(Self { epoll, num_queues })
+    }
+
+    /// Return whether the set tracks no queues, in which case it can never become ready.
+    #[inline]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.num_queues == 0
+    }
+
+    /// Return the index of the next ready queue in round-robin order.
+    ///
+    /// # Errors
+    /// If no queue is ready this returns [`WouldBlock`][std::io::ErrorKind::WouldBlock].
+    pub(crate) fn next(&self) -> io::Result<usize> {
+        if self.is_empty() {
+            return Err(Error::QueueClosed.into_io());
+        }
+
+        let mut events = [EpollEvent::empty()];
+        loop {
+            match epoll_wait(self.epoll.as_raw_fd(), &mut events, 0) {
+                Ok(0) => return Err(io::Error::from(io::ErrorKind::WouldBlock)),
+                Ok(_) => return Ok(events[0].data() as usize),
+                Err(Errno::EINTR) => continue,
+                Err(err) => return Err(Error::from(err).into_io()),
+            }
+        }
+    }
+}
+
+impl AsRawFd for ReadySet {
+    fn as_raw_fd(&self) -> RawFd {
+        self.epoll.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use nix::sys::socket::{socketpair, AddressFamily, SockFlag, SockType};
+    use nix::unistd::write;
+
+    #[test]
+    fn test_round_robin() {
+        let pairs = (0..3)
+            .map(|_| {
+                let (local, peer) = socketpair(
+                    AddressFamily::Unix,
+                    SockType::Datagram,
+                    None,
+                    SockFlag::SOCK_CLOEXEC,
+                )
+                .unwrap();
+                unsafe { (OwnedFd::from_raw_fd(local), OwnedFd::from_raw_fd(peer)) }
+            })
+            .collect::<Vec<_>>();
+        let set = ReadySet::new(
+            pairs.iter().map(|(local, _)| Some(local.as_raw_fd())),
+            EpollFlags::EPOLLIN,
+        )
+        .unwrap();
+        assert_eq!(io::ErrorKind::WouldBlock, set.next().unwrap_err().kind());
+
+        // Level triggered readiness persists until the packets are read, so each ready queue is
+        // handed out in turn.
+        for idx in [0, 2] {
+            write(pairs[idx].1.as_raw_fd(), b"packet").unwrap();
+        }
+        let served = (0..4).map(|_| set.next().unwrap()).collect::<Vec<_>>();
+        assert_eq!(vec![0, 2, 0, 2], served);
+
+        let set = ReadySet::new(std::iter::empty(), EpollFlags::EPOLLIN).unwrap();
+        assert_eq!(io::ErrorKind::NotConnected, set.next().unwrap_err().kind());
+
+        // A set of only closed queues can never become ready.
+        let set = ReadySet::new([None, None], EpollFlags::EPOLLIN).unwrap();
+        assert_eq!(io::ErrorKind::NotConnected, set.next().unwrap_err().kind());
+    }
+}
@@ -5,15 +5,26 @@ use super::*;
 
 use std::io;
 use std::ops::{Index, IndexMut, RangeBounds};
+use std::os::unix::io::AsRawFd;
 use std::slice::{Iter, IterMut, SliceIndex};
+use std::sync::OnceLock;
+use std::task::{Context, Poll};
 use std::vec::{Drain, IntoIter};
 
-use futures_util::future::select_all;
+use async_io::Async;
+use futures_util::ready;
+use nix::sys::epoll::EpollFlags;
 
 /// An asynchronous virtual TUN device based on the `async-std`/`smol` ecosystems.
 pub struct AsyncStdTun {
     queues: Vec<AsyncStdQueue>,
-    name: String,
+    link: Link,
+    teardown: Teardown,
+    cleanup_on_drop: bool,
+    selector: Selector,
+    shutdown: Shutdown,
+    readable: OnceLock<Async<ReadySet>>,
+    writable: OnceLock<Async<ReadySet>>,
 }
 
 impl AsyncStdTun {
@@ -22,18 +33,174 @@ impl AsyncStdTun {
     /// to denote a OS determined incrementing ID to assign this device. To get the real device
     /// name call [`TokioTun::name()`].
     pub fn new(name: &str, num_queues: usize) -> Result<Self> {
-        if num_queues < 1 {
-            return Err(Error::InvalidNumQueues);
+        Self::with_mode(name, Mode::Tun, num_queues)
+    }
+
+    /// Create a new multi-queue device operating in the specified [Mode], using the supplied name
+    /// and number of queues. This is analogous to [`AsyncStdTun::new()`], but allows for creating layer 2
+    /// [`Mode::Tap`] devices.
+    pub fn with_mode(name: &str, mode: Mode, num_queues: usize) -> Result<Self> {
+        TunBuilder::new(name)
+            .mode(mode)
+            .queues(num_queues)
+            .build_async_std()
+    }
+
+    pub(crate) fn from_parts(queues: Vec<AsyncStdQueue>, link: Link, selector: Selector) -> Self {
+        Self {
+            queues,
+            link,
+            teardown: Teardown::default(),
+            cleanup_on_drop: false,
+            selector,
+            shutdown: Shutdown::default(),
+            readable: OnceLock::new(),
+            writable: OnceLock::new(),
         }
+    }
 
-        let (queues, name) = new_queues(name, num_queues)?;
-        Ok(Self { queues, name })
+    /// Record configuration applied to this device, which is undone on [`Self::close()`].
+    pub(crate) fn set_teardown(&mut self, teardown: Teardown) {
+        self.teardown = teardown;
+    }
+
+    /// Enable or disable undoing the configuration applied to this device by
+    /// [`DeviceConfig::build()`][crate::DeviceConfig::build()] when this handle is dropped
+    /// without being closed, for instance while unwinding from a panic. This is disabled by
+    /// default, leaving the configuration in place. Any errors encountered while undoing the
+    /// configuration on drop are ignored, use [`Self::close()`] to observe them.
+    ///
+    /// Only the steps applied by the [DeviceConfig][crate::DeviceConfig] are undone, changes made
+    /// afterwards through [`Self::link()`] are not tracked and are left in place.
+    ///
+    /// Dropping the handle always closes the queues it still owns, regardless of this setting.
+    #[inline]
+    pub fn set_cleanup_on_drop(&mut self, on: bool) {
+        self.cleanup_on_drop = on;
+    }
+
+    /// Return the [SendPolicy] used to select the queue packets are sent via by
+    /// [`AsyncStdTun::send()`].
+    #[inline]
+    pub fn send_policy(&self) -> SendPolicy {
+        self.selector.policy()
+    }
+
+    /// Set the [SendPolicy] used to select the queue packets are sent via by [`AsyncStdTun::send()`].
+    #[inline]
+    pub fn set_send_policy(&mut self, policy: SendPolicy) {
+        self.selector.set_policy(policy);
     }
 
     /// Return the OS determined name of this device.
     #[inline]
     pub fn name(&self) -> &str {
-        self.name.as_str()
+        self.link.name()
+    }
+
+    /// Return a reference to the [Link] backing this device, exposing link level configuration.
+    #[inline]
+    pub fn link(&self) -> &Link {
+        &self.link
+    }
+
+    /// Retrieve the kernel assigned interface index of this device, see [`Link::ifindex()`] for more
+    /// details.
+    #[inline]
+    pub fn ifindex(&self) -> Result<u32> {
+        self.link.ifindex()
+    }
+
+    /// Rename this device, see [`Link::rename()`] for more details. Subsequent calls to
+    /// `name()` return the new name.
+    #[inline]
+    pub fn rename(&mut self, name: &str) -> Result<()> {
+        self.link.rename(name)
+    }
+
+    /// Set the alias (`ifalias`) description of this device, see [`Link::set_alias()`] for more
+    /// details.
+    #[inline]
+    pub fn set_alias(&self, alias: &str) -> Result<()> {
+        self.link.set_alias(alias)
+    }
+
+    /// Move this device into the supplied network namespace, see [`Link::move_to_netns()`] for
+    /// more details. The queues of this device remain valid and usable across the move.
+    #[inline]
+    pub fn move_to_netns(&mut self, netns: &NetNs) -> Result<()> {
+        self.link.move_to_netns(netns)
+    }
+
+    /// Retrieve the hardware (MAC) address of this device, see [`Link::mac_address()`] for more
+    /// details.
+    #[inline]
+    pub fn mac_address(&self) -> Result<MacAddr> {
+        self.link.mac_address()
+    }
+
+    /// Assign the supplied hardware (MAC) address to this device, see [`Link::set_mac_address()`]
+    /// for more details.
+    #[inline]
+    pub fn set_mac_address(&self, addr: MacAddr) -> Result<()> {
+        self.link.set_mac_address(addr)
+    }
+
+    /// Attach this device to the supplied master link, such as a bridge, see [`Link::set_master()`]
+    /// for more details.
+    #[inline]
+    pub fn set_master(&self, master: &Link) -> Result<()> {
+        self.link.set_master(master)
+    }
+
+    /// Release this device from its current master link, see [`Link::clear_master()`] for more
+    /// details.
+    #[inline]
+    pub fn clear_master(&self) -> Result<()> {
+        self.link.clear_master()
+    }
+
+    /// Create a handle exposing the RPS and XPS CPU masks of the queues of this device, see
+    /// [QueueAffinity] for more details. Queue indexes only match those used to access the queues
+    /// of this device until any of its queues are drained or closed.
+    #[inline]
+    pub fn queue_affinity(&self) -> QueueAffinity {
+        self.link.queue_affinity()
+    }
+
+    /// Retrieve the root queueing discipline of this device, see [`Link::qdisc()`] for more
+    /// details.
+    #[inline]
+    pub fn qdisc(&self) -> Result<Qdisc> {
+        self.link.qdisc()
+    }
+
+    /// Replace the root queueing discipline of this device, see [`Link::set_qdisc()`] for more
+    /// details.
+    #[inline]
+    pub fn set_qdisc(&self, qdisc: &Qdisc) -> Result<()> {
+        self.link.set_qdisc(qdisc)
+    }
+
+    /// Create a handle exposing typed access to the per-interface sysctls of this device, see
+    /// [Sysctl] for more details.
+    #[inline]
+    pub fn sysctl(&self) -> Sysctl {
+        self.link.sysctl()
+    }
+
+    /// Retrieve the kernel's interface statistics for this device, see [`Link::stats()`] for more
+    /// details.
+    #[inline]
+    pub fn stats(&self) -> Result<LinkStats> {
+        self.link.stats()
+    }
+
+    /// Create an asynchronous [AsyncStdMonitor] reporting changes to this device as a
+    /// [Stream][futures_util::Stream] of [LinkEvent][crate::LinkEvent]s, see [`Link::monitor()`]
+    /// for more details.
+    pub fn monitor(&self) -> Result<AsyncStdMonitor> {
+        AsyncStdMonitor::new(self.link.monitor()?)
     }
 
     /// Retrieve an immutable reference to the specified [AsyncStdQueue] if the suplied [SliceIndex]
@@ -53,69 +220,86 @@ impl AsyncStdTun {
     where
         I: SliceIndex<[AsyncStdQueue], Output = AsyncStdQueue>,
     {
+        self.invalidate();
         self.queues.get_mut(index)
     }
 
-    /// Close the device destroying all internal queues.
-    /// NOTE: If `drain` is called its on the caller to cleanup the queues.
+    /// Shutdown all internal queues, waking every task pending on them with an error, see
+    /// [`AsyncStdQueue::shutdown()`] for more details. This is useful to stop tasks servicing a shared
+    /// device prior to closing it.
+    pub fn shutdown(&self) {
+        self.shutdown.trigger();
+        self.queues.iter().for_each(AsyncStdQueue::shutdown);
+    }
+
+    /// Close the device destroying all internal queues, and undo the configuration applied to it
+    /// by [DeviceConfig][crate::DeviceConfig], changes made through [`Self::link()`] are left in
+    /// place. Queues previously removed via `drain` are closed when they are dropped.
     pub fn close(&mut self) -> Result<()> {
+        let teardown = self.teardown.run(&self.link);
         for mut queue in self.drain(..) {
             queue.close()?;
         }
-        Ok(())
+        teardown
     }
 
     /// Drain the internal queues, passing ownership of the queue and its lifecycle
     /// to the caller. This is useful in certain scenarios where extreme control over
     /// threading and I/O operations is desired.
     #[inline]
-    pub fn drain<R>(&mut self, range: R) -> Drain<AsyncStdQueue>
+    pub fn drain<R>(&mut self, range: R) -> Drain<'_, AsyncStdQueue>
     where
         R: RangeBounds<usize>,
     {
+        self.invalidate();
         self.queues.drain(range)
     }
 
     /// Iterate over immutable instances internal [AsyncStdQueue] instances.
     #[inline]
-    pub fn iter(&self) -> Iter<AsyncStdQueue> {
+    pub fn iter(&self) -> Iter<'_, AsyncStdQueue> {
         self.queues.iter()
     }
 
     /// Iterate over mutable instances of the internal [AsyncStdQueue] instances.
     #[inline]
-    pub fn iter_mut(&mut self) -> IterMut<AsyncStdQueue> {
+    pub fn iter_mut(&mut self) -> IterMut<'_, AsyncStdQueue> {
+        self.invalidate();
         self.queues.iter_mut()
     }
 
-    /// Send a packet asynchronously to an available queue. This method handles collecting
-    /// all of the [`AsyncStdQueue::writable()`] futures. Then leverages [`select_all`][futures_util::future::select_all]
-    /// to await the first available queue to send the datagram via.
-    pub async fn send(&self, datagram: &[u8]) -> io::Result<usize> {
-        loop {
-            // First collect all queue writable futures, pinning them as needed.
-            let futures = self.iter().map(|queue| Box::pin(queue.writable()));
-
-            // Select the first available queue to write to.
-            let (result, idx, _) = select_all(futures).await;
-
-            // Check to see if we errored, if so short circuit.
-            if let Err(e) = result {
-                return Err(e);
-            }
-
-            // Using the index returned from the above `select_all` call, retrieve
-            // the queue in question, and attempt to send the datagram. Ensuring that
-            // if the write fails due to EWOULDBLOCK/EAGAIN that the process is retried.
-            let queue = self
-                .get(idx)
-                .ok_or_else(|| Error::InvalidQueue(idx).into_io())?;
+    /// Select the open queue to send the supplied packet via, according to the [SendPolicy] of
+    /// this device, see [`Selector::select()`].
+    fn select(&self, datagram: &[u8]) -> Option<Load> {
+        self.selector.select(datagram, self.queues.len(), |idx| {
+            !self.queues[idx].get_ref().is_closed()
+        })
+    }
 
-            match queue.get_ref().send(datagram) {
-                Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
-                result => return result,
-            }
+    /// Send a packet asynchronously via the queue selected by the [SendPolicy] of this device.
+    /// Upon success the number of bytes sent is returned.
+    ///
+    /// By default any ready queue is used. Readiness of all queues is tracked by a single
+    /// persistent epoll instance registered with the reactor, so the cost of each call is
+    /// independent of the number of queues, and ready queues are selected round-robin.
+    pub async fn send(&self, datagram: &[u8]) -> io::Result<usize> {
+        if let Some(load) = self.select(datagram) {
+            return self.queues[load.queue].send(datagram).await;
         }
+
+        let ready = self.ready_set(&self.writable, EpollFlags::EPOLLOUT)?;
+        self.shutdown
+            .guard(async {
+                loop {
+                    let queue = ready.read_with(ReadySet::next).await?;
+                    match self.queues[queue].get_ref().send(datagram) {
+                        // Another task raced us to the queue, so wait for readiness again.
+                        Err(err) if err.kind() == io::ErrorKind::WouldBlock => continue,
+                        result => return result,
+                    }
+                }
+            })
+            .await
     }
 
     /// Send a packet asynchronously via the specified TUN queue, see the [`AsyncStdQueue::send()`]
@@ -135,33 +319,177 @@ impl AsyncStdTun {
             .await
     }
 
-    /// Receive a packet asynchronously from an available queue. This method handles collecting
-    /// all of the [`AsyncStdQueue::readable()`] futures. Then leverages [`select_all`] to await the
-    /// first available queue to send the datagram via.
+    /// Receive a packet asynchronously from any queue with data available. Upon success the
+    /// number of bytes read is returned.
+    ///
+    /// Readiness of all queues is tracked by a single persistent epoll instance registered with
+    /// the reactor, so the cost of each call is independent of the number of queues, and ready
+    /// queues are selected round-robin so a busy queue can't starve the others.
     pub async fn recv(&self, datagram: &mut [u8]) -> io::Result<usize> {
-        loop {
-            // First collect all queue readable futures, pinning them as needed.
-            let futures = self.iter().map(|queue| Box::pin(queue.readable()));
+        self.recv_with(|queue, _| queue.recv(datagram)).await
+    }
+
+    /// Receive a packet asynchronously from any queue with data available, analogous to
+    /// [`AsyncStdTun::recv()`], returning the [RecvMeta] of the packet. This includes the index of
+    /// the queue the packet was read off, so that replies can be sent via the same queue, and the
+    /// parsed packet headers if enabled via [`TunBuilder::packet_info()`] or
+    /// [`TunBuilder::vnet_hdr()`]. Only the packet itself is read into the supplied datagram.
+    pub async fn recv_meta(&self, datagram: &mut [u8]) -> io::Result<RecvMeta> {
+        self.recv_with(|queue, idx| queue.recv_meta(idx, datagram))
+            .await
+    }
+
+    /// Receive a packet asynchronously from any queue with data available, as per
+    /// [`AsyncStdTun::recv()`], into a buffer taken from the supplied [PacketPool]. The packet is read
+    /// following the headroom of the buffer, so that it can be encapsulated and forwarded
+    /// without further copies.
+    #[cfg(feature = "pool-impl")]
+    pub async fn recv_pooled(&self, pool: &PacketPool) -> io::Result<PooledPacket> {
+        self.recv_with(|queue, _| queue.recv_pooled(pool)).await
+    }
 
-            // Select the first available queue with data to read.
-            let (result, idx, _) = select_all(futures).await;
+    /// Create a [PacketStream] of the packets received by any queue of this device, each
+    /// received into a buffer taken from the supplied [PacketPool] as per
+    /// [`AsyncStdTun::recv_pooled()`].
+    #[cfg(feature = "pool-impl")]
+    pub fn stream(&self, pool: PacketPool) -> PacketStream<'_> {
+        PacketStream::new(self, pool)
+    }
+
+    /// Create a [PacketSink] sending each packet via this device as per [`AsyncStdTun::send()`], so
+    /// that the queue each packet is sent via is determined by the [SendPolicy] of the device.
+    pub fn sink<'a, T>(&'a self) -> PacketSink<'a, T>
+    where
+        T: AsRef<[u8]>,
+    {
+        PacketSink::new(self)
+    }
+
+    /// Attempt to read a single packet from any queue with data available into the supplied
+    /// buffer, registering the current task for wakeup if no queue is ready for reading, see
+    /// [`AsyncStdQueue::poll_recv()`] for more details.
+    pub fn poll_recv(&self, cx: &mut Context<'_>, datagram: &mut [u8]) -> Poll<io::Result<usize>> {
+        self.shutdown.poll_check(cx, Direction::Read)?;
+        let ready = self.ready_set(&self.readable, EpollFlags::EPOLLIN)?;
+        self.poll_ready_set(cx, ready, |queue| queue.recv(datagram))
+    }
+
+    /// Attempt to write a single packet via the queue selected by the [SendPolicy] of this
+    /// device, registering the current task for wakeup if no suitable queue is ready for
+    /// writing, see [`AsyncStdQueue::poll_send()`] for more details.
+    ///
+    /// No state is retained between calls, so the queue is selected anew on each poll, and a
+    /// pending call doesn't count against the load of its queue under
+    /// [`SendPolicy::LeastLoaded`]. Sends via [`AsyncStdTun::sink()`] retain the selected queue until
+    /// the packet is sent.
+    #[inline]
+    pub fn poll_send(&self, cx: &mut Context<'_>, datagram: &[u8]) -> Poll<io::Result<usize>> {
+        self.poll_send_with(cx, &mut None, datagram)
+    }
+
+    /// Attempt to write a single packet as per [`AsyncStdTun::poll_send()`], retaining the queue
+    /// selected by the [SendPolicy], along with its load, in the supplied slot while pending.
+    pub(crate) fn poll_send_with(
+        &self,
+        cx: &mut Context<'_>,
+        load: &mut Option<Load>,
+        datagram: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if load.is_none() {
+            *load = self.select(datagram);
+        }
+        if let Some(queue) = load.as_ref().map(|load| load.queue) {
+            // The queue may have been removed from the device while pending.
+            let res = match self.queues.get(queue) {
+                Some(queue) => ready!(queue.poll_send(cx, datagram)),
+                None => Err(Error::InvalidQueue(queue).into_io()),
+            };
+            *load = None;
+            return Poll::Ready(res);
+        }
+
+        self.shutdown.poll_check(cx, Direction::Write)?;
+        let ready = self.ready_set(&self.writable, EpollFlags::EPOLLOUT)?;
+        self.poll_ready_set(cx, ready, |queue| queue.send(datagram))
+    }
 
-            // Check to see if we errored, if so short circuit.
-            if let Err(e) = result {
-                return Err(e);
+    /// Attempt an operation on the next ready queue of the supplied readiness set, until it no
+    /// longer returns [`WouldBlock`][std::io::ErrorKind::WouldBlock].
+    fn poll_ready_set<T, F>(
+        &self,
+        cx: &mut Context<'_>,
+        ready: &Async<ReadySet>,
+        mut op: F,
+    ) -> Poll<io::Result<T>>
+    where
+        F: FnMut(&Queue) -> io::Result<T>,
+    {
+        loop {
+            let queue = match ready.get_ref().next() {
+                Ok(queue) => queue,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    ready!(ready.poll_readable(cx))?;
+                    continue;
+                }
+                Err(err) => return Poll::Ready(Err(err)),
+            };
+            match op(self.queues[queue].get_ref()) {
+                // Another task raced us to the queue, so wait for readiness again.
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => continue,
+                result => return Poll::Ready(result),
             }
+        }
+    }
+
+    async fn recv_with<T, F>(&self, mut op: F) -> io::Result<T>
+    where
+        F: FnMut(&Queue, usize) -> io::Result<T>,
+    {
+        let ready = self.ready_set(&self.readable, EpollFlags::EPOLLIN)?;
+        self.shutdown
+            .guard(async {
+                loop {
+                    let queue = ready.read_with(ReadySet::next).await?;
+                    match op(self.queues[queue].get_ref(), queue) {
+                        // Another task raced us to the queue, so wait for readiness again.
+                        Err(err) if err.kind() == io::ErrorKind::WouldBlock => continue,
+                        result => return result,
+                    }
+                }
+            })
+            .await
+    }
 
-            // Using the index returned from the above `select_all` call, retrieve
-            // the queue in question, and attempt to read the datagram. Ensuring that
-            // if the read fails due to EWOULDBLOCK/EAGAIN that the process is retried.
-            let queue = self
-                .get(idx)
-                .ok_or_else(|| Error::InvalidQueue(idx).into_io())?;
-            match queue.get_ref().recv(datagram) {
-                Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
-                result => return result,
+    /// Retrieve the persistent readiness set of the queues, creating it and registering it with
+    /// the reactor on first use.
+    fn ready_set<'a>(
+        &self,
+        set: &'a OnceLock<Async<ReadySet>>,
+        flags: EpollFlags,
+    ) -> io::Result<&'a Async<ReadySet>> {
+        let ready = match set.get() {
+            Some(ready) => ready,
+            None => {
+                let fds = self.queues.iter().map(|queue| {
+                    let queue = queue.get_ref();
+                    (!queue.is_closed()).then(|| queue.as_raw_fd())
+                });
+                let ready = ReadySet::new(fds, flags).map_err(Error::into_io)?;
+                let ready = Async::new(ready)?;
+                set.get_or_init(|| ready)
             }
+        };
+        // An empty set never becomes ready, so waiting on it would block forever.
+        if ready.get_ref().is_empty() {
+            return Err(Error::QueueClosed.into_io());
         }
+        Ok(ready)
+    }
+
+    /// Discard the readiness sets, as the queues they track may be about to change.
+    fn invalidate(&mut self) {
+        self.readable.take();
+        self.writable.take();
     }
 
     /// Receive a packet asynchronously from the specified TUN queue, see the [`AsyncStdQueue::recv()`]
@@ -180,15 +508,60 @@ impl AsyncStdTun {
             .recv(datagram)
             .await
     }
+
+    /// Receive a packet asynchronously from the specified TUN queue, analogous to
+    /// [`AsyncStdTun::recv_via()`], returning the [RecvMeta] of the packet, see
+    /// [`AsyncStdTun::recv_meta()`] for more details.
+    ///
+    /// # Errors
+    /// General I/O errors are possible, along with a [Error::InvalidQueue] if the specified
+    /// queue is out of range for this device.
+    pub async fn recv_meta_via(&self, queue: usize, datagram: &mut [u8]) -> io::Result<RecvMeta> {
+        self.get(queue)
+            .ok_or_else(|| Error::InvalidQueue(queue).into_io())?
+            .recv_meta(queue, datagram)
+            .await
+    }
+}
+
+impl PollPacket for AsyncStdTun {
+    #[inline]
+    fn poll_recv_packet(&self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        self.poll_recv(cx, buf)
+    }
+
+    #[inline]
+    fn poll_send_packet(
+        &self,
+        cx: &mut Context<'_>,
+        load: &mut Option<Load>,
+        packet: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_send_with(cx, load, packet)
+    }
 }
 
 impl IntoIterator for AsyncStdTun {
     type Item = AsyncStdQueue;
     type IntoIter = IntoIter<AsyncStdQueue>;
 
+    /// Consume the device, passing ownership of its queues to the caller. The configuration
+    /// applied to the device by riptun is left in place, regardless of
+    /// [`AsyncStdTun::set_cleanup_on_drop()`].
     #[inline]
-    fn into_iter(self) -> Self::IntoIter {
-        self.queues.into_iter()
+    fn into_iter(mut self) -> Self::IntoIter {
+        self.teardown.forget();
+        std::mem::take(&mut self.queues).into_iter()
+    }
+}
+
+impl Drop for AsyncStdTun {
+    fn drop(&mut self) {
+        if self.cleanup_on_drop {
+            let _ = self.teardown.run(&self.link);
+        } else {
+            self.teardown.forget();
+        }
     }
 }
 
@@ -204,6 +577,7 @@ impl Index<usize> for AsyncStdTun {
 impl IndexMut<usize> for AsyncStdTun {
     #[inline]
     fn index_mut(&mut self, index: usize) -> &mut AsyncStdQueue {
+        self.invalidate();
         self.queues.index_mut(index)
     }
 }
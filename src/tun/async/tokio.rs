@@ -5,15 +5,27 @@ use super::*;
 
 use std::io;
 use std::ops::{Index, IndexMut, RangeBounds};
+use std::os::unix::io::AsRawFd;
 use std::slice::{Iter, IterMut, SliceIndex};
+use std::sync::OnceLock;
+use std::task::{Context, Poll};
 use std::vec::{Drain, IntoIter};
 
-use futures_util::future::select_all;
+use futures_util::ready;
+use nix::sys::epoll::EpollFlags;
+use tokio::io::unix::AsyncFd;
+use tokio::io::ReadBuf;
 
 /// An asynchronous virtual TUN device based on the `tokio` ecosystem.
 pub struct TokioTun {
     queues: Vec<TokioQueue>,
-    name: String,
+    link: Link,
+    teardown: Teardown,
+    cleanup_on_drop: bool,
+    selector: Selector,
+    shutdown: Shutdown,
+    readable: OnceLock<AsyncFd<ReadySet>>,
+    writable: OnceLock<AsyncFd<ReadySet>>,
 }
 
 impl TokioTun {
@@ -22,18 +34,174 @@ impl TokioTun {
     /// denote a OS determined incrementing ID to assign this device. To get the real device
     /// name call [`TokioTun::name()`].
     pub fn new(name: &str, num_queues: usize) -> Result<Self> {
-        if num_queues < 1 {
-            return Err(Error::InvalidNumQueues);
+        Self::with_mode(name, Mode::Tun, num_queues)
+    }
+
+    /// Create a new multi-queue device operating in the specified [Mode], using the supplied name
+    /// and number of queues. This is analogous to [`TokioTun::new()`], but allows for creating layer 2
+    /// [`Mode::Tap`] devices.
+    pub fn with_mode(name: &str, mode: Mode, num_queues: usize) -> Result<Self> {
+        TunBuilder::new(name)
+            .mode(mode)
+            .queues(num_queues)
+            .build_tokio()
+    }
+
+    pub(crate) fn from_parts(queues: Vec<TokioQueue>, link: Link, selector: Selector) -> Self {
+        Self {
+            queues,
+            link,
+            teardown: Teardown::default(),
+            cleanup_on_drop: false,
+            selector,
+            shutdown: Shutdown::default(),
+            readable: OnceLock::new(),
+            writable: OnceLock::new(),
         }
+    }
+
+    /// Record configuration applied to this device, which is undone on [`Self::close()`].
+    pub(crate) fn set_teardown(&mut self, teardown: Teardown) {
+        self.teardown = teardown;
+    }
+
+    /// Enable or disable undoing the configuration applied to this device by
+    /// [`DeviceConfig::build()`][crate::DeviceConfig::build()] when this handle is dropped
+    /// without being closed, for instance while unwinding from a panic. This is disabled by
+    /// default, leaving the configuration in place. Any errors encountered while undoing the
+    /// configuration on drop are ignored, use [`Self::close()`] to observe them.
+    ///
+    /// Only the steps applied by the [DeviceConfig][crate::DeviceConfig] are undone, changes made
+    /// afterwards through [`Self::link()`] are not tracked and are left in place.
+    ///
+    /// Dropping the handle always closes the queues it still owns, regardless of this setting.
+    #[inline]
+    pub fn set_cleanup_on_drop(&mut self, on: bool) {
+        self.cleanup_on_drop = on;
+    }
 
-        let (queues, name) = new_queues(name, num_queues)?;
-        Ok(Self { queues, name })
+    /// Return the [SendPolicy] used to select the queue packets are sent via by
+    /// [`TokioTun::send()`].
+    #[inline]
+    pub fn send_policy(&self) -> SendPolicy {
+        self.selector.policy()
+    }
+
+    /// Set the [SendPolicy] used to select the queue packets are sent via by [`TokioTun::send()`].
+    #[inline]
+    pub fn set_send_policy(&mut self, policy: SendPolicy) {
+        self.selector.set_policy(policy);
     }
 
     /// Return the OS determined name of this device.
     #[inline]
     pub fn name(&self) -> &str {
-        self.name.as_str()
+        self.link.name()
+    }
+
+    /// Return a reference to the [Link] backing this device, exposing link level configuration.
+    #[inline]
+    pub fn link(&self) -> &Link {
+        &self.link
+    }
+
+    /// Retrieve the kernel assigned interface index of this device, see [`Link::ifindex()`] for more
+    /// details.
+    #[inline]
+    pub fn ifindex(&self) -> Result<u32> {
+        self.link.ifindex()
+    }
+
+    /// Rename this device, see [`Link::rename()`] for more details. Subsequent calls to
+    /// `name()` return the new name.
+    #[inline]
+    pub fn rename(&mut self, name: &str) -> Result<()> {
+        self.link.rename(name)
+    }
+
+    /// Set the alias (`ifalias`) description of this device, see [`Link::set_alias()`] for more
+    /// details.
+    #[inline]
+    pub fn set_alias(&self, alias: &str) -> Result<()> {
+        self.link.set_alias(alias)
+    }
+
+    /// Move this device into the supplied network namespace, see [`Link::move_to_netns()`] for
+    /// more details. The queues of this device remain valid and usable across the move.
+    #[inline]
+    pub fn move_to_netns(&mut self, netns: &NetNs) -> Result<()> {
+        self.link.move_to_netns(netns)
+    }
+
+    /// Retrieve the hardware (MAC) address of this device, see [`Link::mac_address()`] for more
+    /// details.
+    #[inline]
+    pub fn mac_address(&self) -> Result<MacAddr> {
+        self.link.mac_address()
+    }
+
+    /// Assign the supplied hardware (MAC) address to this device, see [`Link::set_mac_address()`]
+    /// for more details.
+    #[inline]
+    pub fn set_mac_address(&self, addr: MacAddr) -> Result<()> {
+        self.link.set_mac_address(addr)
+    }
+
+    /// Attach this device to the supplied master link, such as a bridge, see [`Link::set_master()`]
+    /// for more details.
+    #[inline]
+    pub fn set_master(&self, master: &Link) -> Result<()> {
+        self.link.set_master(master)
+    }
+
+    /// Release this device from its current master link, see [`Link::clear_master()`] for more
+    /// details.
+    #[inline]
+    pub fn clear_master(&self) -> Result<()> {
+        self.link.clear_master()
+    }
+
+    /// Create a handle exposing the RPS and XPS CPU masks of the queues of this device, see
+    /// [QueueAffinity] for more details. Queue indexes only match those used to access the queues
+    /// of this device until any of its queues are drained or closed.
+    #[inline]
+    pub fn queue_affinity(&self) -> QueueAffinity {
+        self.link.queue_affinity()
+    }
+
+    /// Retrieve the root queueing discipline of this device, see [`Link::qdisc()`] for more
+    /// details.
+    #[inline]
+    pub fn qdisc(&self) -> Result<Qdisc> {
+        self.link.qdisc()
+    }
+
+    /// Replace the root queueing discipline of this device, see [`Link::set_qdisc()`] for more
+    /// details.
+    #[inline]
+    pub fn set_qdisc(&self, qdisc: &Qdisc) -> Result<()> {
+        self.link.set_qdisc(qdisc)
+    }
+
+    /// Create a handle exposing typed access to the per-interface sysctls of this device, see
+    /// [Sysctl] for more details.
+    #[inline]
+    pub fn sysctl(&self) -> Sysctl {
+        self.link.sysctl()
+    }
+
+    /// Retrieve the kernel's interface statistics for this device, see [`Link::stats()`] for more
+    /// details.
+    #[inline]
+    pub fn stats(&self) -> Result<LinkStats> {
+        self.link.stats()
+    }
+
+    /// Create an asynchronous [TokioMonitor] reporting changes to this device as a
+    /// [Stream][futures_util::Stream] of [LinkEvent][crate::LinkEvent]s, see [`Link::monitor()`]
+    /// for more details.
+    pub fn monitor(&self) -> Result<TokioMonitor> {
+        TokioMonitor::new(self.link.monitor()?)
     }
 
     /// Retrieve an immutable reference to the specified queue(s) if the suplied [SliceIndex] is inbounds.
@@ -51,66 +219,90 @@ impl TokioTun {
     where
         I: SliceIndex<[TokioQueue]>,
     {
+        self.invalidate();
         self.queues.get_mut(index)
     }
 
-    /// Close the device destroying all internal queues.
-    /// NOTE: If `drain` is called its on the caller to cleanup the queues.
+    /// Shutdown all internal queues, waking every task pending on them with an error, see
+    /// [`TokioQueue::shutdown()`] for more details. This is useful to stop tasks servicing a shared
+    /// device prior to closing it.
+    pub fn shutdown(&self) {
+        self.shutdown.trigger();
+        self.queues.iter().for_each(TokioQueue::shutdown);
+    }
+
+    /// Close the device destroying all internal queues, and undo the configuration applied to it
+    /// by [DeviceConfig][crate::DeviceConfig], changes made through [`Self::link()`] are left in
+    /// place. Queues previously removed via `drain` are closed when they are dropped.
     pub fn close(&mut self) -> Result<()> {
+        let teardown = self.teardown.run(&self.link);
         for mut queue in self.drain(..) {
             queue.close()?;
         }
-        Ok(())
+        teardown
     }
 
     /// Drain the internal queues, passing ownership of the queue and its lifecycle
     /// to the caller. This is useful in certain scenarios where extreme control over
     /// threading and I/O operations is desired.
     #[inline]
-    pub fn drain<R>(&mut self, range: R) -> Drain<TokioQueue>
+    pub fn drain<R>(&mut self, range: R) -> Drain<'_, TokioQueue>
     where
         R: RangeBounds<usize>,
     {
+        self.invalidate();
         self.queues.drain(range)
     }
 
     /// Iterate over immutable instances internal queues.
     #[inline]
-    pub fn iter(&self) -> Iter<TokioQueue> {
+    pub fn iter(&self) -> Iter<'_, TokioQueue> {
         self.queues.iter()
     }
 
     /// Iterate over mutable instances of the internal queues.
     #[inline]
-    pub fn iter_mut(&mut self) -> IterMut<TokioQueue> {
+    pub fn iter_mut(&mut self) -> IterMut<'_, TokioQueue> {
+        self.invalidate();
         self.queues.iter_mut()
     }
 
-    /// Send a packet asynchronously to an available queue. This method handles collecting
-    /// all of the [`TokioQueue::writable()`] futures. Then leverages [`select_all()`] to await the
-    /// first available queue to send the datagram via.
-    pub async fn send(&self, datagram: &[u8]) -> io::Result<usize> {
-        loop {
-            // First collect all queue writable futures, pinning them as needed.
-            let futures = self.iter().map(|queue| Box::pin(queue.writable()));
-
-            // Select the first available queue to write to.
-            let (result, _, _) = select_all(futures).await;
-
-            // Unwrap the Result returning the AsyncReadyGuard or propagating the error upstream.
-            let mut guard = match result {
-                Ok(guard) => guard,
-                Err(e) => return Err(e),
-            };
+    /// Select the open queue to send the supplied packet via, according to the [SendPolicy] of
+    /// this device, see [`Selector::select()`].
+    fn select(&self, datagram: &[u8]) -> Option<Load> {
+        self.selector.select(datagram, self.queues.len(), |idx| {
+            !self.queues[idx].get_ref().is_closed()
+        })
+    }
 
-            // Using the AsyncReadyGuard try to preform the requested I/O operation,
-            // if the result is an error we know it would have blocked, so retry the whole
-            // process again.
-            match guard.try_io(|queue| queue.get_ref().send(datagram)) {
-                Ok(res) => return res,
-                Err(_) => continue,
-            };
+    /// Send a packet asynchronously via the queue selected by the [SendPolicy] of this device.
+    /// Upon success the number of bytes sent is returned.
+    ///
+    /// By default any ready queue is used. Readiness of all queues is tracked by a single
+    /// persistent epoll instance registered with the reactor, so the cost of each call is
+    /// independent of the number of queues, and ready queues are selected round-robin.
+    pub async fn send(&self, datagram: &[u8]) -> io::Result<usize> {
+        if let Some(load) = self.select(datagram) {
+            return self.queues[load.queue].send(datagram).await;
         }
+
+        let ready = self.ready_set(&self.writable, EpollFlags::EPOLLOUT)?;
+        self.shutdown
+            .guard(async {
+                loop {
+                    let mut guard = ready.readable().await?;
+                    let queue = match guard.try_io(|ready| ready.get_ref().next()) {
+                        Ok(queue) => queue?,
+                        Err(_) => continue,
+                    };
+                    match self.queues[queue].get_ref().send(datagram) {
+                        // Another task raced us to the queue, so wait for readiness again.
+                        Err(err) if err.kind() == io::ErrorKind::WouldBlock => continue,
+                        result => return result,
+                    }
+                }
+            })
+            .await
     }
 
     /// Send a packet asynchronously via the specified TUN queue, see the [`TokioQueue::send()`]
@@ -130,33 +322,186 @@ impl TokioTun {
             .await
     }
 
-    /// Receive a packet asynchronously from an available queue. This method handles collecting
-    /// all of the [`TokioQueue::readable()`] futures. Then leverages [`select_all()`] to await the
-    /// first available queue to send the datagram via.
+    /// Receive a packet asynchronously from any queue with data available. Upon success the
+    /// number of bytes read is returned.
+    ///
+    /// Readiness of all queues is tracked by a single persistent epoll instance registered with
+    /// the reactor, so the cost of each call is independent of the number of queues, and ready
+    /// queues are selected round-robin so a busy queue can't starve the others.
     pub async fn recv(&self, datagram: &mut [u8]) -> io::Result<usize> {
+        self.recv_with(|queue, _| queue.recv(datagram)).await
+    }
+
+    /// Receive a packet asynchronously from any queue with data available, analogous to
+    /// [`TokioTun::recv()`], returning the [RecvMeta] of the packet. This includes the index of
+    /// the queue the packet was read off, so that replies can be sent via the same queue, and the
+    /// parsed packet headers if enabled via [`TunBuilder::packet_info()`] or
+    /// [`TunBuilder::vnet_hdr()`]. Only the packet itself is read into the supplied datagram.
+    pub async fn recv_meta(&self, datagram: &mut [u8]) -> io::Result<RecvMeta> {
+        self.recv_with(|queue, idx| queue.recv_meta(idx, datagram))
+            .await
+    }
+
+    /// Receive a packet asynchronously from any queue with data available, as per
+    /// [`TokioTun::recv()`], into a buffer taken from the supplied [PacketPool]. The packet is read
+    /// following the headroom of the buffer, so that it can be encapsulated and forwarded
+    /// without further copies.
+    #[cfg(feature = "pool-impl")]
+    pub async fn recv_pooled(&self, pool: &PacketPool) -> io::Result<PooledPacket> {
+        self.recv_with(|queue, _| queue.recv_pooled(pool)).await
+    }
+
+    /// Create a [PacketStream] of the packets received by any queue of this device, each
+    /// received into a buffer taken from the supplied [PacketPool] as per
+    /// [`TokioTun::recv_pooled()`].
+    #[cfg(feature = "pool-impl")]
+    pub fn stream(&self, pool: PacketPool) -> PacketStream<'_> {
+        PacketStream::new(self, pool)
+    }
+
+    /// Create a [PacketSink] sending each packet via this device as per [`TokioTun::send()`], so
+    /// that the queue each packet is sent via is determined by the [SendPolicy] of the device.
+    pub fn sink<'a, T>(&'a self) -> PacketSink<'a, T>
+    where
+        T: AsRef<[u8]>,
+    {
+        PacketSink::new(self)
+    }
+
+    async fn recv_with<T, F>(&self, mut op: F) -> io::Result<T>
+    where
+        F: FnMut(&Queue, usize) -> io::Result<T>,
+    {
+        let ready = self.ready_set(&self.readable, EpollFlags::EPOLLIN)?;
+        self.shutdown
+            .guard(async {
+                loop {
+                    let mut guard = ready.readable().await?;
+                    let queue = match guard.try_io(|ready| ready.get_ref().next()) {
+                        Ok(queue) => queue?,
+                        Err(_) => continue,
+                    };
+                    match op(self.queues[queue].get_ref(), queue) {
+                        // Another task raced us to the queue, so wait for readiness again.
+                        Err(err) if err.kind() == io::ErrorKind::WouldBlock => continue,
+                        result => return result,
+                    }
+                }
+            })
+            .await
+    }
+
+    /// Attempt to read a single packet from any queue with data available into the supplied
+    /// buffer, registering the current task for wakeup if no queue is ready for reading, see
+    /// [`TokioQueue::poll_recv()`] for more details.
+    pub fn poll_recv(
+        &self,
+        cx: &mut Context<'_>,
+        datagram: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        self.shutdown.poll_check(cx, Direction::Read)?;
+        let ready = self.ready_set(&self.readable, EpollFlags::EPOLLIN)?;
         loop {
-            // First collect all queue readable futures, pinning them as needed.
-            let futures = self.iter().map(|queue| Box::pin(queue.readable()));
+            let mut guard = ready!(ready.poll_read_ready(cx))?;
+            let queue = match guard.try_io(|ready| ready.get_ref().next()) {
+                Ok(queue) => queue?,
+                Err(_) => continue,
+            };
+            let unfilled = unsafe { datagram.unfilled_mut() };
+            match self.queues[queue].get_ref().recv_uninit(unfilled) {
+                Ok(read) => {
+                    unsafe { datagram.assume_init(read) };
+                    datagram.advance(read);
+                    return Poll::Ready(Ok(()));
+                }
+                // Another task raced us to the queue, so wait for readiness again.
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => continue,
+                Err(err) => return Poll::Ready(Err(err)),
+            }
+        }
+    }
 
-            // Select the first available queue with data to read.
-            let (result, _, _) = select_all(futures).await;
+    /// Attempt to write a single packet via the queue selected by the [SendPolicy] of this
+    /// device, registering the current task for wakeup if no suitable queue is ready for
+    /// writing, see [`TokioQueue::poll_send()`] for more details.
+    ///
+    /// No state is retained between calls, so the queue is selected anew on each poll, and a
+    /// pending call doesn't count against the load of its queue under
+    /// [`SendPolicy::LeastLoaded`]. Sends via [`TokioTun::sink()`] retain the selected queue until
+    /// the packet is sent.
+    #[inline]
+    pub fn poll_send(&self, cx: &mut Context<'_>, datagram: &[u8]) -> Poll<io::Result<usize>> {
+        self.poll_send_with(cx, &mut None, datagram)
+    }
 
-            // Unwrap the Result returning the AsyncReadyGuard or propagating the error upstream.
-            let mut guard = match result {
-                Ok(guard) => guard,
-                Err(e) => return Err(e),
+    /// Attempt to write a single packet as per [`TokioTun::poll_send()`], retaining the queue
+    /// selected by the [SendPolicy], along with its load, in the supplied slot while pending.
+    pub(crate) fn poll_send_with(
+        &self,
+        cx: &mut Context<'_>,
+        load: &mut Option<Load>,
+        datagram: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if load.is_none() {
+            *load = self.select(datagram);
+        }
+        if let Some(queue) = load.as_ref().map(|load| load.queue) {
+            // The queue may have been removed from the device while pending.
+            let res = match self.queues.get(queue) {
+                Some(queue) => ready!(queue.poll_send(cx, datagram)),
+                None => Err(Error::InvalidQueue(queue).into_io()),
             };
+            *load = None;
+            return Poll::Ready(res);
+        }
 
-            // Using the AsyncReadyGuard try to preform the requested I/O operation,
-            // if the result is an error we know it would have blocked, so retry the whole
-            // process again.
-            match guard.try_io(|queue| queue.get_ref().recv(datagram)) {
-                Ok(res) => return res,
+        self.shutdown.poll_check(cx, Direction::Write)?;
+        let ready = self.ready_set(&self.writable, EpollFlags::EPOLLOUT)?;
+        loop {
+            let mut guard = ready!(ready.poll_read_ready(cx))?;
+            let queue = match guard.try_io(|ready| ready.get_ref().next()) {
+                Ok(queue) => queue?,
                 Err(_) => continue,
             };
+            match self.queues[queue].get_ref().send(datagram) {
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => continue,
+                result => return Poll::Ready(result),
+            }
         }
     }
 
+    /// Retrieve the persistent readiness set of the queues, creating it and registering it with
+    /// the reactor on first use.
+    fn ready_set<'a>(
+        &self,
+        set: &'a OnceLock<AsyncFd<ReadySet>>,
+        flags: EpollFlags,
+    ) -> io::Result<&'a AsyncFd<ReadySet>> {
+        let ready = match set.get() {
+            Some(ready) => ready,
+            None => {
+                let fds = self.queues.iter().map(|queue| {
+                    let queue = queue.get_ref();
+                    (!queue.is_closed()).then(|| queue.as_raw_fd())
+                });
+                let ready = ReadySet::new(fds, flags).map_err(Error::into_io)?;
+                let ready = crate::async_fd::new(ready)?;
+                set.get_or_init(|| ready)
+            }
+        };
+        // An empty set never becomes ready, so waiting on it would block forever.
+        if ready.get_ref().is_empty() {
+            return Err(Error::QueueClosed.into_io());
+        }
+        Ok(ready)
+    }
+
+    /// Discard the readiness sets, as the queues they track may be about to change.
+    fn invalidate(&mut self) {
+        self.readable.take();
+        self.writable.take();
+    }
+
     /// Receive a packet asynchronously from the specified TUN queue, see the [`TokioQueue::recv()`]
     /// documentation for more details.
     ///
@@ -173,15 +518,87 @@ impl TokioTun {
             .recv(datagram)
             .await
     }
+
+    /// Receive a packet asynchronously from the specified TUN queue, analogous to
+    /// [`TokioTun::recv_via()`], returning the [RecvMeta] of the packet, see
+    /// [`TokioTun::recv_meta()`] for more details.
+    ///
+    /// # Errors
+    /// General I/O errors are possible, along with a [Error::InvalidQueue] if the specified
+    /// queue is out of range for this device.
+    pub async fn recv_meta_via(&self, queue: usize, datagram: &mut [u8]) -> io::Result<RecvMeta> {
+        self.get(queue)
+            .ok_or_else(|| Error::InvalidQueue(queue).into_io())?
+            .recv_meta(queue, datagram)
+            .await
+    }
+}
+
+impl PollPacket for TokioTun {
+    fn poll_recv_packet(&self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        let mut buf = ReadBuf::new(buf);
+        ready!(self.poll_recv(cx, &mut buf))?;
+        Poll::Ready(Ok(buf.filled().len()))
+    }
+
+    #[inline]
+    fn poll_send_packet(
+        &self,
+        cx: &mut Context<'_>,
+        load: &mut Option<Load>,
+        packet: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_send_with(cx, load, packet)
+    }
+}
+
+#[cfg(feature = "tokio-codec-impl")]
+impl crate::queue::sealed::Sealed for TokioTun {
+    #[inline]
+    fn poll_send_selected(
+        &self,
+        cx: &mut Context<'_>,
+        load: &mut Option<Load>,
+        datagram: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_send_with(cx, load, datagram)
+    }
+}
+
+#[cfg(feature = "tokio-codec-impl")]
+impl crate::queue::PacketTransport for TokioTun {
+    #[inline]
+    fn poll_recv(&self, cx: &mut Context<'_>, datagram: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        TokioTun::poll_recv(self, cx, datagram)
+    }
+
+    #[inline]
+    fn poll_send(&self, cx: &mut Context<'_>, datagram: &[u8]) -> Poll<io::Result<usize>> {
+        TokioTun::poll_send(self, cx, datagram)
+    }
 }
 
 impl IntoIterator for TokioTun {
     type Item = TokioQueue;
     type IntoIter = IntoIter<TokioQueue>;
 
+    /// Consume the device, passing ownership of its queues to the caller. The configuration
+    /// applied to the device by riptun is left in place, regardless of
+    /// [`TokioTun::set_cleanup_on_drop()`].
     #[inline]
-    fn into_iter(self) -> Self::IntoIter {
-        self.queues.into_iter()
+    fn into_iter(mut self) -> Self::IntoIter {
+        self.teardown.forget();
+        std::mem::take(&mut self.queues).into_iter()
+    }
+}
+
+impl Drop for TokioTun {
+    fn drop(&mut self) {
+        if self.cleanup_on_drop {
+            let _ = self.teardown.run(&self.link);
+        } else {
+            self.teardown.forget();
+        }
     }
 }
 
@@ -197,6 +614,7 @@ impl Index<usize> for TokioTun {
 impl IndexMut<usize> for TokioTun {
     #[inline]
     fn index_mut(&mut self, index: usize) -> &mut TokioQueue {
+        self.invalidate();
         self.queues.index_mut(index)
     }
 }
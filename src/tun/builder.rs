@@ -0,0 +1,145 @@
+// (c) Copyright 2021 Christian Saide
+// SPDX-License-Identifier: MIT
+
+use super::*;
+
+/// A builder exposing the full set of options available when creating a device, for the cases
+/// where the [`Tun::new()`] and [`Tun::with_mode()`] style constructors are insufficient.
+///
+/// ```no_run
+/// use riptun::{Mode, NetNs, TunBuilder};
+///
+/// let netns = NetNs::from_name("container").expect("Failed to open namespace.");
+/// let tun = TunBuilder::new("rip%d")
+///     .mode(Mode::Tap)
+///     .queues(4)
+///     .netns(netns)
+///     .build()
+///     .expect("Failed to create device.");
+/// ```
+#[derive(Debug, Clone)]
+pub struct TunBuilder {
+    name: String,
+    mode: Mode,
+    num_queues: usize,
+    headers: Headers,
+    send_policy: SendPolicy,
+    netns: Option<NetNs>,
+}
+
+impl TunBuilder {
+    /// Create a new builder for a single queue [`Mode::Tun`] device using the specified name. The
+    /// name parameter can be augmented with `%d` to denote a OS determined incrementing ID to
+    /// assign the device.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: String::from(name),
+            mode: Mode::Tun,
+            num_queues: 1,
+            headers: Headers::NONE,
+            send_policy: SendPolicy::RoundRobin,
+            netns: None,
+        }
+    }
+
+    /// Set the [Mode] the device operates in.
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Set the number of queues to open against the device.
+    pub fn queues(mut self, num_queues: usize) -> Self {
+        self.num_queues = num_queues;
+        self
+    }
+
+    /// Enable or disable the packet information header (`struct tun_pi`), which the kernel then
+    /// prepends to every packet read off, and expects prepended to every packet written to, the
+    /// queues of the device. This is disabled by default. The header is parsed into a
+    /// [PacketInfo][crate::PacketInfo] by [`Tun::recv_meta()`], while the plain send and receive
+    /// calls exchange it as part of the packet.
+    pub fn packet_info(mut self, on: bool) -> Self {
+        self.headers.packet_info = on;
+        self
+    }
+
+    /// Enable or disable the virtio net header (`struct virtio_net_hdr`), which the kernel then
+    /// prepends to every packet read off, and expects prepended to every packet written to, the
+    /// queues of the device, following the packet information header if also enabled. This is
+    /// disabled by default. The header is parsed into a [VnetHdr][crate::VnetHdr] by
+    /// [`Tun::recv_meta()`], while the plain send and receive calls exchange it as part of the
+    /// packet.
+    pub fn vnet_hdr(mut self, on: bool) -> Self {
+        self.headers.vnet_hdr = on;
+        self
+    }
+
+    /// Set the [SendPolicy] used to select the queue packets are sent via by the device level
+    /// `send` calls, which defaults to [`SendPolicy::RoundRobin`].
+    pub fn send_policy(mut self, policy: SendPolicy) -> Self {
+        self.send_policy = policy;
+        self
+    }
+
+    /// Create the device directly within the supplied network namespace, rather than the
+    /// namespace of the calling thread. The namespace is entered via `setns` on a short lived
+    /// helper thread, so the calling thread is left untouched.
+    pub fn netns(mut self, netns: NetNs) -> Self {
+        self.netns = Some(netns);
+        self
+    }
+
+    /// Create a blocking [Tun] device based on the configured options.
+    pub fn build(self) -> Result<Tun> {
+        let (queues, link, selector) = self.open()?;
+        Ok(Tun::from_parts(queues, link, selector))
+    }
+
+    /// Create an async [TokioTun] device based on the configured options. This must be called
+    /// from within the context of a `tokio` runtime.
+    #[cfg(feature = "tokio-impl")]
+    pub fn build_tokio(self) -> Result<TokioTun> {
+        let (queues, link, selector) = self.open()?;
+        Ok(TokioTun::from_parts(queues, link, selector))
+    }
+
+    /// Create an async [AsyncStdTun] device based on the configured options.
+    #[cfg(feature = "async-std-impl")]
+    pub fn build_async_std(self) -> Result<AsyncStdTun> {
+        let (queues, link, selector) = self.open()?;
+        Ok(AsyncStdTun::from_parts(queues, link, selector))
+    }
+
+    pub(crate) fn open<T: FromQueue>(self) -> Result<(Vec<T>, Link, Selector)> {
+        if self.num_queues < 1 {
+            return Err(Error::InvalidNumQueues);
+        }
+
+        let Self {
+            name,
+            mode,
+            num_queues,
+            headers,
+            send_policy,
+            netns,
+        } = self;
+        let (queues, link) = match netns {
+            Some(netns) => {
+                let (queues, name) = netns.run(|| new_queues(&name, mode, headers, num_queues))?;
+                (queues, Link::with_netns(&name, netns))
+            }
+            None => {
+                let (queues, name) = new_queues(&name, mode, headers, num_queues)?;
+                (queues, Link::new(&name))
+            }
+        };
+
+        let queues = queues
+            .into_iter()
+            .map(T::from_queue)
+            .collect::<Result<Vec<_>>>()?;
+        let selector = Selector::new(send_policy, mode, headers, num_queues);
+        Ok((queues, link, selector))
+    }
+}
@@ -1,17 +1,39 @@
 // (c) Copyright 2021 Christian Saide
 // SPDX-License-Identifier: MIT
 
-use super::{queue::new_queues, Error, Queue, Result};
+use super::config::Teardown;
+use super::queue::{new_queues, FromQueue, Headers};
+use super::{
+    Error, Link, LinkStats, MacAddr, Mode, Monitor, NetNs, Qdisc, Queue, QueueAffinity, RecvMeta,
+    Result, Sysctl,
+};
 
 use cfg_if::cfg_if;
 
+mod builder;
+mod select;
 mod sync;
 
+pub use builder::TunBuilder;
+pub use select::SendPolicy;
+pub(crate) use select::{Load, Selector};
 pub use sync::Tun;
 
+cfg_if! {
+    if #[cfg(any(feature = "async-std-impl", feature = "tokio-impl"))] {
+        use super::queue::{Direction, PacketSink, PollPacket, Shutdown};
+        #[cfg(feature = "pool-impl")]
+        use super::{queue::PacketStream, PacketPool, PooledPacket};
+
+        #[path = "async/ready.rs"]
+        mod ready;
+        use ready::ReadySet;
+    }
+}
+
 cfg_if! {
     if #[cfg(feature = "async-std-impl")] {
-        use super::AsyncStdQueue;
+        use super::{AsyncStdMonitor, AsyncStdQueue};
 
         #[path = "async/std.rs"]
         mod async_std;
@@ -21,7 +43,7 @@ cfg_if! {
 
 cfg_if! {
     if #[cfg(feature = "tokio-impl")] {
-        use super::TokioQueue;
+        use super::{TokioMonitor, TokioQueue};
 
         #[path = "async/tokio.rs"]
         mod async_tokio;
@@ -0,0 +1,299 @@
+// (c) Copyright 2021 Christian Saide
+// SPDX-License-Identifier: MIT
+
+use super::{Headers, Mode};
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// The default Toeplitz key used for receive side scaling by most NICs, and the kernel.
+const RSS_KEY: [u8; 40] = [
+    0x6d, 0x5a, 0x56, 0xda, 0x25, 0x5b, 0x0e, 0xc2, 0x41, 0x67, 0x25, 0x3d, 0x43, 0xa3, 0x8f, 0xb0,
+    0xd0, 0xca, 0x2b, 0xcb, 0xae, 0x7b, 0x30, 0xb4, 0x77, 0xcb, 0x2d, 0xa3, 0x80, 0x30, 0xf2, 0x0c,
+    0x6a, 0x42, 0xb7, 0x3b, 0xbe, 0xac, 0x01, 0xfa,
+];
+
+const ETH_HLEN: usize = 14;
+const ETH_P_IP: u16 = 0x0800;
+const ETH_P_IPV6: u16 = 0x86dd;
+const ETH_P_8021Q: u16 = 0x8100;
+const ETH_P_8021AD: u16 = 0x88a8;
+const IPPROTO_TCP: u8 = 6;
+const IPPROTO_UDP: u8 = 17;
+const IPPROTO_SCTP: u8 = 132;
+
+/// The policy used to select the queue packets are sent via by the device level `send` calls,
+/// such as [`Tun::send()`][crate::Tun::send()].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum SendPolicy {
+    /// Send via any queue ready for writing, selected round-robin. This maximizes throughput,
+    /// however packets of a single flow may be reordered across queues.
+    #[default]
+    RoundRobin,
+    /// Send via a stable queue determined by the Toeplitz hash of the source and destination
+    /// addresses and ports of the packet, as used by the kernel and NICs for receive side
+    /// scaling. This keeps every packet of a TCP, UDP, or SCTP flow on the same queue, and
+    /// therefore in order. Fragments, and other protocols, are hashed on their addresses alone,
+    /// while non-IP packets are sent via the first queue. Flows hashed to a closed queue are
+    /// sent via the next open queue instead.
+    FlowHash,
+    /// Send via the open queue with the fewest sends in progress, waiting for it to become
+    /// writable.
+    LeastLoaded,
+}
+
+/// Selects the queue each packet is sent via according to a [SendPolicy].
+pub(crate) struct Selector {
+    policy: SendPolicy,
+    mode: Mode,
+    headers: Headers,
+    load: Vec<Arc<AtomicUsize>>,
+    next: AtomicUsize,
+}
+
+impl Selector {
+    /// Create a new selector for the queues of a device operating in the supplied [Mode], with
+    /// the supplied packet headers enabled.
+    pub(crate) fn new(policy: SendPolicy, mode: Mode, headers: Headers, num_queues: usize) -> Self {
+        Self {
+            policy,
+            mode,
+            headers,
+            load: (0..num_queues).map(|_| Arc::default()).collect(),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn policy(&self) -> SendPolicy {
+        self.policy
+    }
+
+    #[inline]
+    pub(crate) fn set_policy(&mut self, policy: SendPolicy) {
+        self.policy = policy;
+    }
+
+    /// Select the queue to send the supplied packet via, out of the supplied number of queues,
+    /// skipping those the supplied predicate reports as closed, and returning `None` if any
+    /// ready queue can be used. The send counts against the load of the selected queue until
+    /// the returned [Load] is dropped, which can be retained across polls.
+    pub(crate) fn select<F>(&self, datagram: &[u8], num_queues: usize, is_open: F) -> Option<Load>
+    where
+        F: Fn(usize) -> bool,
+    {
+        // Queues can only be removed from a device, never added, so the load tracking always
+        // covers every queue.
+        let num_queues = num_queues.min(self.load.len());
+        let start = match self.policy {
+            SendPolicy::RoundRobin => return None,
+            SendPolicy::FlowHash => flow_hash(datagram, self.mode, self.headers)
+                .map(|hash| reciprocal_scale(hash, num_queues))
+                .unwrap_or_default(),
+            SendPolicy::LeastLoaded => self.next.fetch_add(1, Ordering::Relaxed),
+        };
+        let mut open = (start..start + num_queues)
+            .map(|idx| idx % num_queues)
+            .filter(|idx| is_open(*idx));
+        let queue = match self.policy {
+            // Flows hashed to a closed queue move to the next open one, leaving the remaining
+            // flows on their queues.
+            SendPolicy::FlowHash => open.next(),
+            _ => open.min_by_key(|idx| self.load[*idx].load(Ordering::Relaxed)),
+        }?;
+        self.load[queue].fetch_add(1, Ordering::Relaxed);
+        Some(Load {
+            queue,
+            load: Arc::clone(&self.load[queue]),
+        })
+    }
+}
+
+/// A send in progress via a selected queue. This is public, although unreachable, as it is
+/// retained by the sealed transport of `TokioFramed`.
+#[derive(Debug)]
+pub struct Load {
+    pub(crate) queue: usize,
+    load: Arc<AtomicUsize>,
+}
+
+impl Drop for Load {
+    fn drop(&mut self) {
+        self.load.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Map the supplied hash onto the supplied number of queues, analogous to the kernel's
+/// `reciprocal_scale()`.
+fn reciprocal_scale(hash: u32, num_queues: usize) -> usize {
+    ((hash as u64 * num_queues as u64) >> 32) as usize
+}
+
+/// Compute the Toeplitz hash of the supplied input, which must not exceed 36 bytes.
+fn toeplitz(input: &[u8]) -> u32 {
+    let mut hash = 0;
+    let mut key = u32::from_be_bytes([RSS_KEY[0], RSS_KEY[1], RSS_KEY[2], RSS_KEY[3]]);
+    for (idx, byte) in input.iter().enumerate() {
+        for bit in 0..8 {
+            if byte & (0x80 >> bit) != 0 {
+                hash ^= key;
+            }
+            key = (key << 1) | ((RSS_KEY[idx + 4] >> (7 - bit)) & 1) as u32;
+        }
+    }
+    hash
+}
+
+/// Compute the flow hash of the supplied packet, returning `None` for non-IP packets.
+fn flow_hash(datagram: &[u8], mode: Mode, headers: Headers) -> Option<u32> {
+    let packet = datagram.get(headers.len()..)?;
+    let (ethertype, packet) = match mode {
+        Mode::Tun => match packet.first()? >> 4 {
+            4 => (ETH_P_IP, packet),
+            6 => (ETH_P_IPV6, packet),
+            _ => return None,
+        },
+        Mode::Tap => {
+            let mut offset = ETH_HLEN;
+            let mut ethertype = u16_at(packet, offset - 2)?;
+            while ethertype == ETH_P_8021Q || ethertype == ETH_P_8021AD {
+                offset += 4;
+                ethertype = u16_at(packet, offset - 2)?;
+            }
+            (ethertype, packet.get(offset..)?)
+        }
+    };
+
+    let mut key = [0u8; 36];
+    let (addrs, proto, ports) = match ethertype {
+        ETH_P_IP => {
+            let ihl = (*packet.first()? as usize & 0x0f) * 4;
+            let fragmented = u16_at(packet, 6)? & 0x3fff != 0;
+            let ports = (!fragmented).then_some(ihl);
+            (packet.get(12..20)?, *packet.get(9)?, ports)
+        }
+        ETH_P_IPV6 => (packet.get(8..40)?, *packet.get(6)?, Some(40)),
+        _ => return None,
+    };
+    key[..addrs.len()].copy_from_slice(addrs);
+    let mut len = addrs.len();
+
+    let ports = ports
+        .filter(|_| matches!(proto, IPPROTO_TCP | IPPROTO_UDP | IPPROTO_SCTP))
+        .and_then(|offset| packet.get(offset..offset + 4));
+    if let Some(ports) = ports {
+        key[len..len + 4].copy_from_slice(ports);
+        len += 4;
+    }
+    Some(toeplitz(&key[..len]))
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An IPv4 TCP packet from 66.9.149.187:2794 to 161.142.100.80:1766.
+    fn ipv4_tcp(flags_frag: u16) -> Vec<u8> {
+        let mut packet = vec![0u8; 40];
+        packet[0] = 0x45;
+        packet[6..8].copy_from_slice(&flags_frag.to_be_bytes());
+        packet[9] = IPPROTO_TCP;
+        packet[12..16].copy_from_slice(&[66, 9, 149, 187]);
+        packet[16..20].copy_from_slice(&[161, 142, 100, 80]);
+        packet[20..22].copy_from_slice(&2794u16.to_be_bytes());
+        packet[22..24].copy_from_slice(&1766u16.to_be_bytes());
+        packet
+    }
+
+    #[test]
+    fn test_flow_hash() {
+        // The reference hashes published alongside the default RSS key.
+        let packet = ipv4_tcp(0);
+        assert_eq!(
+            Some(0x51cc_c178),
+            flow_hash(&packet, Mode::Tun, Headers::NONE)
+        );
+        assert_eq!(
+            Some(0x323e_8fc2),
+            flow_hash(&ipv4_tcp(0x2000), Mode::Tun, Headers::NONE)
+        );
+
+        let mut frame = vec![0u8; 12];
+        frame.extend_from_slice(&ETH_P_8021Q.to_be_bytes());
+        frame.extend_from_slice(&[0, 1]);
+        frame.extend_from_slice(&ETH_P_IP.to_be_bytes());
+        frame.extend_from_slice(&packet);
+        assert_eq!(
+            Some(0x51cc_c178),
+            flow_hash(&frame, Mode::Tap, Headers::NONE)
+        );
+
+        let headers = Headers {
+            packet_info: true,
+            vnet_hdr: false,
+        };
+        let mut datagram = vec![0, 0, 0x08, 0x00];
+        datagram.extend_from_slice(&packet);
+        assert_eq!(Some(0x51cc_c178), flow_hash(&datagram, Mode::Tun, headers));
+
+        assert_eq!(None, flow_hash(&[0x10; 20], Mode::Tun, Headers::NONE));
+        assert_eq!(None, flow_hash(&packet[..16], Mode::Tun, Headers::NONE));
+    }
+
+    #[test]
+    fn test_select() {
+        let packet = ipv4_tcp(0);
+        let mut selector = Selector::new(SendPolicy::RoundRobin, Mode::Tun, Headers::NONE, 4);
+        assert!(selector.select(&packet, 4, |_| true).is_none());
+
+        selector.set_policy(SendPolicy::FlowHash);
+        let queue = reciprocal_scale(0x51cc_c178, 4);
+        assert_eq!(queue, selector.select(&packet, 4, |_| true).unwrap().queue);
+        assert_eq!(0, selector.select(&[0x10; 20], 4, |_| true).unwrap().queue);
+
+        selector.set_policy(SendPolicy::LeastLoaded);
+        let loads = (0..4)
+            .map(|_| selector.select(&packet, 4, |_| true).unwrap())
+            .collect::<Vec<_>>();
+        let mut queues = loads.iter().map(|load| load.queue).collect::<Vec<_>>();
+        queues.sort_unstable();
+        assert_eq!(vec![0, 1, 2, 3], queues);
+
+        // Once its send completes a queue becomes the least loaded.
+        let mut loads = loads;
+        let done = loads.remove(2);
+        let queue = done.queue;
+        drop(done);
+        assert_eq!(queue, selector.select(&packet, 4, |_| true).unwrap().queue);
+    }
+
+    #[test]
+    fn test_select_closed() {
+        let packet = ipv4_tcp(0);
+        let mut selector = Selector::new(SendPolicy::FlowHash, Mode::Tun, Headers::NONE, 4);
+        let queue = reciprocal_scale(0x51cc_c178, 4);
+        let open = |idx: usize| idx != queue;
+        assert_eq!(
+            (queue + 1) % 4,
+            selector.select(&packet, 4, open).unwrap().queue
+        );
+        assert!(selector.select(&packet, 4, |_| false).is_none());
+
+        selector.set_policy(SendPolicy::LeastLoaded);
+        let loads = (0..6)
+            .map(|_| selector.select(&packet, 4, open).unwrap())
+            .collect::<Vec<_>>();
+        assert!(loads.iter().all(|load| load.queue != queue));
+        assert!(selector.select(&packet, 4, |_| false).is_none());
+    }
+}
@@ -5,13 +5,23 @@ use super::*;
 
 use std::io;
 use std::ops::{Index, IndexMut, RangeBounds};
+use std::os::unix::io::AsRawFd;
 use std::slice::{Iter, IterMut, SliceIndex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 use std::vec::{Drain, IntoIter};
 
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags};
+
 /// A named virtual device comprised of one or more virutal queues.
 pub struct Tun {
     queues: Vec<Queue>,
-    name: String,
+    link: Link,
+    teardown: Teardown,
+    cleanup_on_drop: bool,
+    selector: Selector,
+    next: AtomicUsize,
 }
 
 impl Tun {
@@ -19,19 +29,170 @@ impl Tun {
     /// The name parameter can be augmented with `%d` to denote a OS determined incrementing
     /// ID to assign this device. To get the real device name call [`Tun::name()`].
     pub fn new(name: &str, num_queues: usize) -> Result<Self> {
-        if num_queues < 1 {
-            return Err(Error::InvalidNumQueues);
+        Self::with_mode(name, Mode::Tun, num_queues)
+    }
+
+    /// Create a new multi-queue device operating in the specified [Mode], using the supplied name
+    /// and number of queues. This is analogous to [`Tun::new()`], but allows for creating layer 2
+    /// [`Mode::Tap`] devices.
+    pub fn with_mode(name: &str, mode: Mode, num_queues: usize) -> Result<Self> {
+        TunBuilder::new(name).mode(mode).queues(num_queues).build()
+    }
+
+    pub(crate) fn from_parts(queues: Vec<Queue>, link: Link, selector: Selector) -> Self {
+        Self {
+            queues,
+            link,
+            teardown: Teardown::default(),
+            cleanup_on_drop: false,
+            selector,
+            next: AtomicUsize::new(0),
         }
+    }
 
-        let (queues, name) = new_queues(name, num_queues)?;
-        Ok(Self { queues, name })
+    /// Record configuration applied to this device, which is undone on [`Self::close()`].
+    pub(crate) fn set_teardown(&mut self, teardown: Teardown) {
+        self.teardown = teardown;
+    }
+
+    /// Enable or disable undoing the configuration applied to this device by
+    /// [`DeviceConfig::build()`][crate::DeviceConfig::build()] when this handle is dropped
+    /// without being closed, for instance while unwinding from a panic. This is disabled by
+    /// default, leaving the configuration in place. Any errors encountered while undoing the
+    /// configuration on drop are ignored, use [`Self::close()`] to observe them.
+    ///
+    /// Only the steps applied by the [DeviceConfig][crate::DeviceConfig] are undone, changes made
+    /// afterwards through [`Self::link()`] are not tracked and are left in place.
+    ///
+    /// Dropping the handle always closes the queues it still owns, regardless of this setting.
+    #[inline]
+    pub fn set_cleanup_on_drop(&mut self, on: bool) {
+        self.cleanup_on_drop = on;
+    }
+
+    /// Return the [SendPolicy] used to select the queue packets are sent via by
+    /// [`Tun::send()`].
+    #[inline]
+    pub fn send_policy(&self) -> SendPolicy {
+        self.selector.policy()
+    }
+
+    /// Set the [SendPolicy] used to select the queue packets are sent via by [`Tun::send()`].
+    #[inline]
+    pub fn set_send_policy(&mut self, policy: SendPolicy) {
+        self.selector.set_policy(policy);
     }
 
     /// Return the OS determined name of this device. Note this can and usually does differ somewhat from
     /// the supplied name during creation.
     #[inline]
     pub fn name(&self) -> &str {
-        self.name.as_str()
+        self.link.name()
+    }
+
+    /// Return a reference to the [Link] backing this device, exposing link level configuration.
+    #[inline]
+    pub fn link(&self) -> &Link {
+        &self.link
+    }
+
+    /// Retrieve the kernel assigned interface index of this device, see [`Link::ifindex()`] for more
+    /// details.
+    #[inline]
+    pub fn ifindex(&self) -> Result<u32> {
+        self.link.ifindex()
+    }
+
+    /// Rename this device, see [`Link::rename()`] for more details. Subsequent calls to
+    /// `name()` return the new name.
+    #[inline]
+    pub fn rename(&mut self, name: &str) -> Result<()> {
+        self.link.rename(name)
+    }
+
+    /// Set the alias (`ifalias`) description of this device, see [`Link::set_alias()`] for more
+    /// details.
+    #[inline]
+    pub fn set_alias(&self, alias: &str) -> Result<()> {
+        self.link.set_alias(alias)
+    }
+
+    /// Move this device into the supplied network namespace, see [`Link::move_to_netns()`] for
+    /// more details. The queues of this device remain valid and usable across the move.
+    #[inline]
+    pub fn move_to_netns(&mut self, netns: &NetNs) -> Result<()> {
+        self.link.move_to_netns(netns)
+    }
+
+    /// Retrieve the hardware (MAC) address of this device, see [`Link::mac_address()`] for more
+    /// details.
+    #[inline]
+    pub fn mac_address(&self) -> Result<MacAddr> {
+        self.link.mac_address()
+    }
+
+    /// Assign the supplied hardware (MAC) address to this device, see [`Link::set_mac_address()`]
+    /// for more details.
+    #[inline]
+    pub fn set_mac_address(&self, addr: MacAddr) -> Result<()> {
+        self.link.set_mac_address(addr)
+    }
+
+    /// Attach this device to the supplied master link, such as a bridge, see [`Link::set_master()`]
+    /// for more details.
+    #[inline]
+    pub fn set_master(&self, master: &Link) -> Result<()> {
+        self.link.set_master(master)
+    }
+
+    /// Release this device from its current master link, see [`Link::clear_master()`] for more
+    /// details.
+    #[inline]
+    pub fn clear_master(&self) -> Result<()> {
+        self.link.clear_master()
+    }
+
+    /// Create a handle exposing the RPS and XPS CPU masks of the queues of this device, see
+    /// [QueueAffinity] for more details. Queue indexes only match those used to access the queues
+    /// of this device until any of its queues are drained or closed.
+    #[inline]
+    pub fn queue_affinity(&self) -> QueueAffinity {
+        self.link.queue_affinity()
+    }
+
+    /// Retrieve the root queueing discipline of this device, see [`Link::qdisc()`] for more
+    /// details.
+    #[inline]
+    pub fn qdisc(&self) -> Result<Qdisc> {
+        self.link.qdisc()
+    }
+
+    /// Replace the root queueing discipline of this device, see [`Link::set_qdisc()`] for more
+    /// details.
+    #[inline]
+    pub fn set_qdisc(&self, qdisc: &Qdisc) -> Result<()> {
+        self.link.set_qdisc(qdisc)
+    }
+
+    /// Create a handle exposing typed access to the per-interface sysctls of this device, see
+    /// [Sysctl] for more details.
+    #[inline]
+    pub fn sysctl(&self) -> Sysctl {
+        self.link.sysctl()
+    }
+
+    /// Retrieve the kernel's interface statistics for this device, see [`Link::stats()`] for more
+    /// details.
+    #[inline]
+    pub fn stats(&self) -> Result<LinkStats> {
+        self.link.stats()
+    }
+
+    /// Create a blocking [Monitor] reporting changes to this device, see [`Link::monitor()`] for more
+    /// details.
+    #[inline]
+    pub fn monitor(&self) -> Result<Monitor> {
+        self.link.monitor()
     }
 
     /// Retrieve am immutable reference to the specified [Queue] if the suplied [SliceIndex] is inbounds.
@@ -52,20 +213,22 @@ impl Tun {
         self.queues.get_mut(index)
     }
 
-    /// Close the device destroying all internal queues.
-    /// NOTE: If `drain` is called its on the caller to cleanup the queues.
+    /// Close the device destroying all internal queues, and undo the configuration applied to it
+    /// by [DeviceConfig][crate::DeviceConfig], changes made through [`Self::link()`] are left in
+    /// place. Queues previously removed via `drain` are closed when they are dropped.
     pub fn close(&mut self) -> Result<()> {
+        let teardown = self.teardown.run(&self.link);
         for mut queue in self.drain(..) {
             queue.close()?;
         }
-        Ok(())
+        teardown
     }
 
     /// Drain the internal queues, passing ownership of the queue and its lifecycle
     /// to the caller. This is useful in certain scenarios where extreme control over
     /// threading and I/O operations is desired.
     #[inline]
-    pub fn drain<R>(&mut self, range: R) -> Drain<Queue>
+    pub fn drain<R>(&mut self, range: R) -> Drain<'_, Queue>
     where
         R: RangeBounds<usize>,
     {
@@ -74,16 +237,183 @@ impl Tun {
 
     /// Iterate over immutable references to the internal [Queue] structs.
     #[inline]
-    pub fn iter(&self) -> Iter<Queue> {
+    pub fn iter(&self) -> Iter<'_, Queue> {
         self.queues.iter()
     }
 
     /// Iterate over mutable references to the internal [Queue] structs.
     #[inline]
-    pub fn iter_mut(&mut self) -> IterMut<Queue> {
+    pub fn iter_mut(&mut self) -> IterMut<'_, Queue> {
         self.queues.iter_mut()
     }
 
+    /// Send a packet via the queue selected by the [SendPolicy] of this device, blocking until it
+    /// is ready for writing, see the [`Queue::send()`] documentation for more details. Upon
+    /// success the number of bytes sent is returned, along with the index of the queue the packet
+    /// was sent via.
+    ///
+    /// By default any ready queue is used, selected round-robin, so packets are spread across all
+    /// queues.
+    pub fn send(&self, datagram: &[u8]) -> io::Result<(usize, usize)> {
+        self.send_deadline(datagram, None)
+    }
+
+    /// Send a packet via the queue selected by the [SendPolicy] of this device, analogous to
+    /// [`Tun::send()`], failing with [`TimedOut`][std::io::ErrorKind::TimedOut] if the queue
+    /// doesn't become ready within the supplied timeout.
+    pub fn send_timeout(&self, datagram: &[u8], timeout: Duration) -> io::Result<(usize, usize)> {
+        self.send_deadline(datagram, Some(Instant::now() + timeout))
+    }
+
+    fn send_deadline(
+        &self,
+        datagram: &[u8],
+        deadline: Option<Instant>,
+    ) -> io::Result<(usize, usize)> {
+        if let Some(load) = self.select(datagram) {
+            let queue = load.queue;
+            loop {
+                self.wait(queue, PollFlags::POLLOUT, deadline)?;
+                match self.queues[queue].send(datagram) {
+                    Ok(written) => return Ok((written, queue)),
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => continue,
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+
+        loop {
+            let queue = self.ready(PollFlags::POLLOUT, deadline)?;
+            match self.queues[queue].send(datagram) {
+                Ok(written) => return Ok((written, queue)),
+                // Another thread raced us to a non-blocking queue, so wait for readiness again.
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Read a packet off any queue with data available, blocking until one does, see the
+    /// [`Queue::recv()`] documentation for more details. Upon success the number of bytes read
+    /// is returned, along with the index of the queue the packet was read off.
+    ///
+    /// Ready queues are selected round-robin, so a busy queue can't starve the others. Note that
+    /// if multiple threads receive from the same blocking queues concurrently, a thread losing
+    /// the race for a packet blocks on that queue until its next packet arrives. Put the queues
+    /// in non-blocking mode via [`Queue::set_non_blocking()`] to avoid this.
+    pub fn recv(&self, datagram: &mut [u8]) -> io::Result<(usize, usize)> {
+        self.recv_deadline(datagram, None)
+    }
+
+    /// Read a packet off any queue with data available, analogous to [`Tun::recv()`], failing
+    /// with [`TimedOut`][std::io::ErrorKind::TimedOut] if no packet arrives within the supplied
+    /// timeout.
+    pub fn recv_timeout(
+        &self,
+        datagram: &mut [u8],
+        timeout: Duration,
+    ) -> io::Result<(usize, usize)> {
+        self.recv_deadline(datagram, Some(Instant::now() + timeout))
+    }
+
+    fn recv_deadline(
+        &self,
+        datagram: &mut [u8],
+        deadline: Option<Instant>,
+    ) -> io::Result<(usize, usize)> {
+        self.recv_with(deadline, |queue, idx| {
+            queue.recv(datagram).map(|read| (read, idx))
+        })
+    }
+
+    /// Read a packet off any queue with data available, analogous to [`Tun::recv()`], returning
+    /// the [RecvMeta] of the packet. This includes the index of the queue the packet was read
+    /// off, so that replies can be sent via the same queue, and the parsed packet headers if
+    /// enabled via [`TunBuilder::packet_info()`] or [`TunBuilder::vnet_hdr()`]. Only the packet
+    /// itself is read into the supplied datagram.
+    pub fn recv_meta(&self, datagram: &mut [u8]) -> io::Result<RecvMeta> {
+        self.recv_with(None, |queue, idx| queue.recv_meta(idx, datagram))
+    }
+
+    /// Read a packet off any queue with data available, analogous to [`Tun::recv_meta()`],
+    /// failing with [`TimedOut`][std::io::ErrorKind::TimedOut] if no packet arrives within the
+    /// supplied timeout.
+    pub fn recv_meta_timeout(
+        &self,
+        datagram: &mut [u8],
+        timeout: Duration,
+    ) -> io::Result<RecvMeta> {
+        self.recv_with(Some(Instant::now() + timeout), |queue, idx| {
+            queue.recv_meta(idx, datagram)
+        })
+    }
+
+    fn recv_with<T, F>(&self, deadline: Option<Instant>, mut op: F) -> io::Result<T>
+    where
+        F: FnMut(&Queue, usize) -> io::Result<T>,
+    {
+        loop {
+            let queue = self.ready(PollFlags::POLLIN, deadline)?;
+            match op(&self.queues[queue], queue) {
+                // Another thread raced us to a non-blocking queue, so wait for readiness again.
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => continue,
+                result => return result,
+            }
+        }
+    }
+
+    /// Wait until any queue is ready for the supplied events, or the deadline passes, returning
+    /// the index of the ready queue following the last one served.
+    fn ready(&self, events: PollFlags, deadline: Option<Instant>) -> io::Result<usize> {
+        // Closed queues are never ready, and must not be waited on.
+        let (queues, mut fds): (Vec<_>, Vec<_>) = self
+            .queues
+            .iter()
+            .enumerate()
+            .filter(|(_, queue)| !queue.is_closed())
+            .map(|(idx, queue)| (idx, PollFd::new(queue.as_raw_fd(), events)))
+            .unzip();
+        if fds.is_empty() {
+            return Err(Error::QueueClosed.into_io());
+        }
+        poll_deadline(&mut fds, deadline)?;
+
+        let num_queues = self.queues.len();
+        let start = self.next.load(Ordering::Relaxed) % num_queues;
+        let ready = |idx: &usize| {
+            fds[*idx]
+                .revents()
+                .map(|revents| !revents.is_empty())
+                .unwrap_or_default()
+        };
+        // Serve the first ready queue at or after the one following the last served.
+        let pos = queues.partition_point(|queue| *queue < start);
+        let queue = (pos..pos + fds.len())
+            .map(|idx| idx % fds.len())
+            .find(ready)
+            .map_or(queues[pos % fds.len()], |idx| queues[idx]);
+        self.next.store(queue + 1, Ordering::Relaxed);
+        Ok(queue)
+    }
+
+    /// Select the open queue to send the supplied packet via, according to the [SendPolicy] of
+    /// this device, see [`Selector::select()`].
+    fn select(&self, datagram: &[u8]) -> Option<Load> {
+        self.selector.select(datagram, self.queues.len(), |idx| {
+            !self.queues[idx].is_closed()
+        })
+    }
+
+    /// Wait until the specified queue is ready for the supplied events, or the deadline passes.
+    fn wait(&self, queue: usize, events: PollFlags, deadline: Option<Instant>) -> io::Result<()> {
+        let queue = &self.queues[queue];
+        if queue.is_closed() {
+            return Err(Error::QueueClosed.into_io());
+        }
+        let mut fds = [PollFd::new(queue.as_raw_fd(), events)];
+        poll_deadline(&mut fds, deadline)
+    }
+
     /// Send a packet via the specified TUN queue, see the [`Queue::send()`] documentation for
     /// more details.
     ///
@@ -107,15 +437,62 @@ impl Tun {
             .ok_or_else(|| Error::from(queue).into_io())?
             .recv(datagram)
     }
+
+    /// Read a packet off the specified TUN queue, analogous to [`Tun::recv_via()`], returning the
+    /// [RecvMeta] of the packet, see [`Tun::recv_meta()`] for more details.
+    ///
+    /// # Errors
+    /// General I/O errors are possible, along with a [Error::InvalidQueue] if the specified
+    /// queue is out of range for this device.
+    pub fn recv_meta_via(&self, queue: usize, datagram: &mut [u8]) -> io::Result<RecvMeta> {
+        self.get(queue)
+            .ok_or_else(|| Error::from(queue).into_io())?
+            .recv_meta(queue, datagram)
+    }
+}
+
+/// Poll the supplied descriptors until at least one is ready, or the deadline passes.
+fn poll_deadline(fds: &mut [PollFd], deadline: Option<Instant>) -> io::Result<()> {
+    loop {
+        let timeout = match deadline {
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                // Round up, so that we never wake prior to the deadline and spin.
+                let millis = remaining.as_micros().div_ceil(1000);
+                millis.min(i32::MAX as u128) as i32
+            }
+            None => -1,
+        };
+        match poll(fds, timeout) {
+            Ok(0) => return Err(io::Error::from(io::ErrorKind::TimedOut)),
+            Ok(_) => return Ok(()),
+            Err(Errno::EINTR) => continue,
+            Err(err) => return Err(Error::from(err).into_io()),
+        }
+    }
 }
 
 impl IntoIterator for Tun {
     type Item = Queue;
     type IntoIter = IntoIter<Queue>;
 
+    /// Consume the device, passing ownership of its queues to the caller. The configuration
+    /// applied to the device by riptun is left in place, regardless of
+    /// [`Tun::set_cleanup_on_drop()`].
     #[inline]
-    fn into_iter(self) -> Self::IntoIter {
-        self.queues.into_iter()
+    fn into_iter(mut self) -> Self::IntoIter {
+        self.teardown.forget();
+        std::mem::take(&mut self.queues).into_iter()
+    }
+}
+
+impl Drop for Tun {
+    fn drop(&mut self) {
+        if self.cleanup_on_drop {
+            let _ = self.teardown.run(&self.link);
+        } else {
+            self.teardown.forget();
+        }
     }
 }
 
@@ -134,3 +511,162 @@ impl IndexMut<usize> for Tun {
         self.queues.index_mut(index)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::os::unix::io::{FromRawFd, RawFd};
+
+    use nix::sys::socket::{socketpair, AddressFamily, SockFlag, SockType};
+
+    /// Create a device backed by datagram socket pairs, returning it along with the peer of each
+    /// of its queues.
+    fn socket_pairs(num: usize) -> (Tun, Vec<Queue>) {
+        let (local, peer): (Vec<RawFd>, Vec<RawFd>) = (0..num)
+            .map(|_| {
+                socketpair(
+                    AddressFamily::Unix,
+                    SockType::Datagram,
+                    None,
+                    SockFlag::empty(),
+                )
+                .unwrap()
+            })
+            .unzip();
+        let queues = |fds: Vec<RawFd>| {
+            fds.into_iter()
+                .map(|fd| unsafe { Queue::from_raw_fd(fd) })
+                .collect::<Vec<_>>()
+        };
+        let selector = Selector::new(SendPolicy::RoundRobin, Mode::Tun, Headers::NONE, num);
+        (
+            Tun::from_parts(queues(local), Link::new("rip0"), selector),
+            queues(peer),
+        )
+    }
+
+    #[test]
+    fn test_recv_any() {
+        let (tun, peers) = socket_pairs(3);
+        let mut buf = [0u8; 16];
+
+        let err = tun
+            .recv_timeout(&mut buf, Duration::from_millis(10))
+            .unwrap_err();
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+
+        peers[1].send(b"one").unwrap();
+        assert_eq!((3, 1), tun.recv(&mut buf).unwrap());
+
+        // Queue 0 holds two packets, yet every other ready queue is served before its second.
+        for peer in peers.iter() {
+            peer.send(b"two").unwrap();
+        }
+        peers[0].send(b"two").unwrap();
+        let served = (0..4)
+            .map(|_| tun.recv(&mut buf).unwrap().1)
+            .collect::<Vec<_>>();
+        assert_eq!(vec![2, 0, 1, 0], served);
+
+        let sent = (0..3)
+            .map(|_| tun.send(b"three").unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(vec![(5, 1), (5, 2), (5, 0)], sent);
+    }
+
+    #[test]
+    fn test_closed_queues() {
+        let (mut tun, peers) = socket_pairs(3);
+        let mut buf = [0u8; 16];
+
+        // Closed queues are skipped, while the remaining queues are still served in turn.
+        tun.get_mut(1).unwrap().close().unwrap();
+        for idx in [0, 2] {
+            peers[idx].send(b"one").unwrap();
+        }
+        let served = (0..2)
+            .map(|_| tun.recv(&mut buf).unwrap().1)
+            .collect::<Vec<_>>();
+        assert_eq!(vec![0, 2], served);
+
+        // Once every queue is closed, waiting without a deadline fails rather than blocking.
+        for idx in [0, 2] {
+            tun.get_mut(idx).unwrap().close().unwrap();
+        }
+        assert_eq!(
+            io::ErrorKind::NotConnected,
+            tun.recv(&mut buf).unwrap_err().kind()
+        );
+    }
+
+    #[test]
+    fn test_recv_meta() {
+        let (tun, peers) = socket_pairs(2);
+        let mut buf = [0u8; 4];
+
+        peers[1].send(b"ping").unwrap();
+        let meta = tun.recv_meta(&mut buf).unwrap();
+        assert_eq!((1, 4, false), (meta.queue, meta.len, meta.truncated));
+        assert_eq!(None, meta.packet_info);
+
+        peers[0].send(b"truncated").unwrap();
+        let meta = tun.recv_meta_via(0, &mut buf).unwrap();
+        assert_eq!((0, 4, true), (meta.queue, meta.len, meta.truncated));
+        assert_eq!(b"trun", &buf);
+    }
+
+    #[test]
+    fn test_send_policy() {
+        let (mut tun, peers) = socket_pairs(4);
+        tun.set_send_policy(SendPolicy::FlowHash);
+
+        let mut packet = [0u8; 24];
+        packet[0] = 0x45;
+        packet[9] = 17;
+        let queues = (0..8u8)
+            .map(|port| {
+                packet[21] = port;
+                let (_, queue) = tun.send(&packet).unwrap();
+                // Every packet of the flow is sent via the same queue.
+                assert_eq!(queue, tun.send(&packet).unwrap().1);
+                queue
+            })
+            .collect::<Vec<_>>();
+        assert!(queues.iter().any(|queue| *queue != queues[0]));
+
+        let mut buf = [0u8; 24];
+        peers[queues[0]].recv(&mut buf).unwrap();
+        assert_eq!(0, buf[21]);
+    }
+
+    #[test]
+    fn test_send_policy_closed() {
+        let (mut tun, peers) = socket_pairs(3);
+        tun.set_send_policy(SendPolicy::FlowHash);
+
+        let mut packet = [0u8; 24];
+        packet[0] = 0x45;
+        packet[9] = 17;
+        let (_, queue) = tun.send(&packet).unwrap();
+
+        // A flow hashed to a closed queue moves to the next open queue, and stays there.
+        tun.get_mut(queue).unwrap().close().unwrap();
+        let next = (queue + 1) % 3;
+        assert_eq!(next, tun.send(&packet).unwrap().1);
+        assert_eq!(next, tun.send(&packet).unwrap().1);
+
+        let mut buf = [0u8; 24];
+        for _ in 0..2 {
+            assert_eq!(24, peers[next].recv(&mut buf).unwrap());
+        }
+
+        for idx in 0..3 {
+            tun.get_mut(idx).unwrap().close().unwrap();
+        }
+        assert_eq!(
+            io::ErrorKind::NotConnected,
+            tun.send(&packet).unwrap_err().kind()
+        );
+    }
+}